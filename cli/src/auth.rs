@@ -0,0 +1,522 @@
+//! Bearer token authentication for RPC endpoints that require it, such as
+//! GenesysGo premium endpoints.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use shadow_drive_sdk::{
+    auth::HttpAuth, error::Error as ShadowDriveError, models::ShadowDriveResult,
+};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
+    rpc_response::RpcSimulateTransactionResult,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signer};
+use tokio::sync::Mutex;
+
+/// Message signed to sign in to GenesysGo.
+pub const GENESYSGO_AUTH_MESSAGE: &str = "Sign in to GenesysGo Shadow Platform.";
+pub const GENESYSGO_SIGN_IN_URL: &str = "https://portal.genesysgo.net/api/signin";
+/// Followed by the account id of a premium RPC endpoint.
+pub const GENESYSGO_ACCOUNT_TOKEN_URL: &str = "https://portal.genesysgo.net/api/premium/token/";
+
+/// JSON-RPC error code of a transaction that failed simulation.
+const SEND_TRANSACTION_PREFLIGHT_FAILURE: i64 = -32002;
+
+/// Where the tokens for an RPC endpoint come from.
+enum TokenSource {
+    /// Given on the command line and used as is
+    Static(String),
+    GenesysGo(GenesysGoSignIn),
+}
+
+/// The sign in message never changes, so it is signed once up front and the
+/// same request is sent again whenever a new token is needed.
+struct GenesysGoSignIn {
+    account_id: String,
+    signer: Pubkey,
+    signature: String,
+    /// [GENESYSGO_SIGN_IN_URL] outside of tests
+    sign_in_url: String,
+    /// [GENESYSGO_ACCOUNT_TOKEN_URL] outside of tests
+    account_token_url: String,
+}
+
+impl GenesysGoSignIn {
+    async fn fetch_token(&self, http_client: &reqwest::Client) -> anyhow::Result<String> {
+        let sign_in: Value = http_client
+            .post(&self.sign_in_url)
+            .json(&json!({
+                "message": GENESYSGO_AUTH_MESSAGE,
+                "signer": self.signer.to_string(),
+                "signature": self.signature,
+            }))
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| anyhow!("failed to sign in to GenesysGo: {}", e))?
+            .json()
+            .await?;
+        let session = token_field(&sign_in)?;
+
+        let account: Value = http_client
+            .post(format!("{}{}", self.account_token_url, self.account_id))
+            .bearer_auth(session)
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| anyhow!("failed to get a token for {}: {}", self.account_id, e))?
+            .json()
+            .await?;
+        token_field(&account).map(str::to_string)
+    }
+}
+
+fn token_field(response: &Value) -> anyhow::Result<&str> {
+    response
+        .get("token")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("GenesysGo response has no token: {}", response))
+}
+
+/// Supplies the bearer tokens for one RPC endpoint. Tokens from GenesysGo are
+/// cached in the user's config directory and fetched again when rejected.
+pub struct RpcAuth {
+    url: String,
+    source: TokenSource,
+    token: Mutex<Option<String>>,
+    /// Where tokens are cached, if there is a config directory
+    cache_path: Option<PathBuf>,
+    http_client: reqwest::Client,
+}
+
+impl RpcAuth {
+    /// Sends `token` with every request to `url`.
+    pub fn with_token(url: &str, token: String) -> Self {
+        Self::new(url, TokenSource::Static(token))
+    }
+
+    /// Signs in to GenesysGo with `signer` to get tokens for the premium
+    /// endpoint at `url`, whose account id is inferred from the URL.
+    pub fn genesysgo<S: Signer + ?Sized>(url: &str, signer: &S) -> anyhow::Result<Self> {
+        let sign_in = GenesysGoSignIn {
+            account_id: parse_account_id_from_url(url)?,
+            signer: signer.try_pubkey()?,
+            signature: signer
+                .try_sign_message(GENESYSGO_AUTH_MESSAGE.as_bytes())?
+                .to_string(),
+            sign_in_url: GENESYSGO_SIGN_IN_URL.to_string(),
+            account_token_url: GENESYSGO_ACCOUNT_TOKEN_URL.to_string(),
+        };
+        Ok(Self::new(url, TokenSource::GenesysGo(sign_in)))
+    }
+
+    fn new(url: &str, source: TokenSource) -> Self {
+        Self {
+            url: url.to_string(),
+            source,
+            token: Mutex::new(None),
+            cache_path: token_cache_path(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// The token to send. Pass the token of a rejected request as `rejected`
+    /// to get a new one.
+    pub async fn token(&self, rejected: Option<&str>) -> anyhow::Result<String> {
+        let sign_in = match &self.source {
+            TokenSource::Static(token) => return Ok(token.clone()),
+            TokenSource::GenesysGo(sign_in) => sign_in,
+        };
+
+        let mut token = self.token.lock().await;
+        if token.is_none() {
+            *token = self
+                .cache_path
+                .as_deref()
+                .and_then(|path| load_cached_token(path, &self.url));
+        }
+        match token.as_deref() {
+            // Another request may have fetched a new token already
+            Some(current) if Some(current) != rejected => return Ok(current.to_string()),
+            _ => {}
+        }
+
+        let fresh = sign_in.fetch_token(&self.http_client).await?;
+        let saved = match &self.cache_path {
+            Some(path) => save_cached_token(path, &self.url, &fresh),
+            None => Err(anyhow!("no config directory")),
+        };
+        if let Err(e) = saved {
+            log::warn!("failed to cache auth token: {}", e);
+        }
+        *token = Some(fresh.clone());
+        Ok(fresh)
+    }
+}
+
+/// Storage requests to the RPC endpoint, if it serves any, send the same tokens.
+#[async_trait]
+impl HttpAuth for RpcAuth {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    async fn token(&self, rejected: Option<&str>) -> ShadowDriveResult<String> {
+        RpcAuth::token(self, rejected)
+            .await
+            .map_err(|e| ShadowDriveError::AuthFailed(e.to_string()))
+    }
+}
+
+/// The GenesysGo account id of a premium RPC URL, which is the last segment
+/// of its path, e.g. `https://us-west-1.genesysgo.net/<account id>`.
+pub fn parse_account_id_from_url(url: &str) -> anyhow::Result<String> {
+    let url = reqwest::Url::parse(url).map_err(|e| anyhow!("invalid RPC URL {}: {}", url, e))?;
+    url.path_segments()
+        .and_then(|segments| segments.filter(|segment| !segment.is_empty()).last())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("{} does not contain a GenesysGo account id", url))
+}
+
+/// Tokens by RPC URL.
+fn token_cache_path() -> Option<PathBuf> {
+    dirs_next::config_dir().map(|dir| dir.join("shadow-drive").join("auth.json"))
+}
+
+fn load_cached_tokens(path: &Path) -> HashMap<String, String> {
+    fs::read(path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn load_cached_token(path: &Path, url: &str) -> Option<String> {
+    load_cached_tokens(path).remove(url)
+}
+
+fn save_cached_token(path: &Path, url: &str, token: &str) -> anyhow::Result<()> {
+    let mut tokens = load_cached_tokens(path);
+    tokens.insert(url.to_string(), token.to_string());
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    serde_json::to_writer(options.open(path)?, &tokens)?;
+    Ok(())
+}
+
+/// An [RpcSender] that sends the token from an [RpcAuth] with every request,
+/// and fetches a new token once when a request is rejected with 401.
+pub struct AuthSender {
+    url: String,
+    auth: Arc<RpcAuth>,
+    http_client: reqwest::Client,
+    request_id: AtomicU64,
+}
+
+impl AuthSender {
+    pub fn new(url: &str, auth: Arc<RpcAuth>) -> Self {
+        Self {
+            url: url.to_string(),
+            auth,
+            http_client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(120))
+                .build()
+                .expect("failed to build http client"),
+            request_id: AtomicU64::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl RpcSender for AuthSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let request_id = self.request_id.fetch_add(1, Ordering::Relaxed);
+        let body = request.build_request_json(request_id, params);
+
+        let mut rejected = None;
+        let response = loop {
+            let token = self
+                .auth
+                .token(rejected.as_deref())
+                .await
+                .map_err(|e| ClientError::from(ClientErrorKind::Custom(e.to_string())))?;
+            let response = self
+                .http_client
+                .post(&self.url)
+                .bearer_auth(&token)
+                .json(&body)
+                .send()
+                .await?;
+            if response.status() == StatusCode::UNAUTHORIZED && rejected.is_none() {
+                rejected = Some(token);
+                continue;
+            }
+            break response.error_for_status()?;
+        };
+
+        let mut json: Value = response.json().await?;
+        if let Some(error) = json.get("error") {
+            let code = error.get("code").and_then(Value::as_i64);
+            let message = error.get("message").and_then(Value::as_str);
+            return Err(match (code, message) {
+                (Some(code), Some(message)) => {
+                    let data = match code {
+                        SEND_TRANSACTION_PREFLIGHT_FAILURE => error
+                            .get("data")
+                            .cloned()
+                            .and_then(|data| {
+                                serde_json::from_value::<RpcSimulateTransactionResult>(data).ok()
+                            })
+                            .map_or(
+                                RpcResponseErrorData::Empty,
+                                RpcResponseErrorData::SendTransactionPreflightFailure,
+                            ),
+                        _ => RpcResponseErrorData::Empty,
+                    };
+                    RpcError::RpcResponseError {
+                        code,
+                        message: message.to_string(),
+                        data,
+                    }
+                }
+                _ => RpcError::RpcRequestError(format!("invalid RPC error response: {}", error)),
+            }
+            .into());
+        }
+        Ok(json["result"].take())
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// An [RpcClient] for `url` that authenticates with `auth`, configured like
+/// the one made by [shadow_drive_sdk::ShadowDriveClient::new].
+pub fn authenticated_rpc_client(url: &str, auth: Arc<RpcAuth>) -> RpcClient {
+    RpcClient::new_sender(
+        AuthSender::new(url, auth),
+        RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+    )
+}
+
+/// Counts the requests to a stub of GenesysGo and of premium RPC endpoints.
+#[cfg(test)]
+#[derive(Default)]
+struct StubCounts {
+    sign_ins: AtomicU64,
+    tokens: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// Serves GenesysGo's sign in at `/signin` and `/token/<account id>`, an RPC
+/// endpoint at `/rpc` that accepts the tokens issued for `acct`, and one at
+/// `/reject` that accepts none. Returns the stub's URL.
+#[cfg(test)]
+async fn genesysgo_stub() -> (String, Arc<StubCounts>) {
+    use axum::{
+        extract::{Path, State},
+        http::HeaderMap,
+        routing::post,
+        Json, Router,
+    };
+
+    fn bearer(headers: &HeaderMap) -> &str {
+        headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default()
+    }
+
+    async fn sign_in(
+        State(counts): State<Arc<StubCounts>>,
+        Json(body): Json<Value>,
+    ) -> Result<Json<Value>, StatusCode> {
+        if body["message"] != GENESYSGO_AUTH_MESSAGE {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        counts.sign_ins.fetch_add(1, Ordering::Relaxed);
+        Ok(Json(json!({ "token": "session" })))
+    }
+
+    async fn token(
+        State(counts): State<Arc<StubCounts>>,
+        Path(account_id): Path<String>,
+        headers: HeaderMap,
+    ) -> Result<Json<Value>, StatusCode> {
+        if bearer(&headers) != "session" {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        let issued = counts.tokens.fetch_add(1, Ordering::Relaxed) + 1;
+        Ok(Json(json!({ "token": format!("{account_id}-{issued}") })))
+    }
+
+    async fn rpc(headers: HeaderMap, Json(body): Json<Value>) -> Result<Json<Value>, StatusCode> {
+        if !bearer(&headers).starts_with("acct-") {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Ok(Json(
+            json!({ "jsonrpc": "2.0", "id": body["id"], "result": 42 }),
+        ))
+    }
+
+    async fn reject(State(counts): State<Arc<StubCounts>>) -> StatusCode {
+        counts.rejected.fetch_add(1, Ordering::Relaxed);
+        StatusCode::UNAUTHORIZED
+    }
+
+    let counts = Arc::new(StubCounts::default());
+    let router = Router::new()
+        .route("/signin", post(sign_in))
+        .route("/token/:account_id", post(token))
+        .route("/rpc", post(rpc))
+        .route("/reject", post(reject))
+        .with_state(Arc::clone(&counts));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = axum::Server::from_tcp(listener)
+        .unwrap()
+        .serve(router.into_make_service());
+    tokio::spawn(server);
+    (url, counts)
+}
+
+#[tokio::test]
+async fn test_auth_sender() {
+    let (stub, counts) = genesysgo_stub().await;
+    let dir = tempfile::tempdir().unwrap();
+    let cache_path = dir.path().join("auth.json");
+    let auth = |url: &str| {
+        let sign_in = GenesysGoSignIn {
+            account_id: "acct".to_string(),
+            signer: Pubkey::new_unique(),
+            signature: "signature".to_string(),
+            sign_in_url: format!("{stub}/signin"),
+            account_token_url: format!("{stub}/token/"),
+        };
+        let mut auth = RpcAuth::new(url, TokenSource::GenesysGo(sign_in));
+        auth.cache_path = Some(cache_path.clone());
+        Arc::new(auth)
+    };
+    let sign_ins = || counts.sign_ins.load(Ordering::Relaxed);
+
+    // A rejected cached token is replaced, and the new one cached
+    let url = format!("{stub}/rpc");
+    save_cached_token(&cache_path, &url, "stale").unwrap();
+    let sender = AuthSender::new(&url, auth(&url));
+    let result = sender.send(RpcRequest::GetSlot, Value::Null).await;
+    assert_eq!(result.unwrap(), json!(42));
+    assert_eq!(sign_ins(), 1);
+    assert_eq!(
+        load_cached_token(&cache_path, &url).as_deref(),
+        Some("acct-1")
+    );
+
+    // Later requests, and other clients, reuse it
+    assert!(sender.send(RpcRequest::GetSlot, Value::Null).await.is_ok());
+    let sender = AuthSender::new(&url, auth(&url));
+    assert!(sender.send(RpcRequest::GetSlot, Value::Null).await.is_ok());
+    assert_eq!(sign_ins(), 1);
+
+    // A request is only retried once with a new token
+    let url = format!("{stub}/reject");
+    let sender = AuthSender::new(&url, auth(&url));
+    let error = sender
+        .send(RpcRequest::GetSlot, Value::Null)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            error.kind(),
+            ClientErrorKind::Reqwest(e) if e.status() == Some(StatusCode::UNAUTHORIZED)
+        ),
+        "{error}"
+    );
+    assert_eq!(counts.rejected.load(Ordering::Relaxed), 2);
+    assert_eq!(sign_ins(), 3);
+
+    // Static tokens are sent as is
+    let auth = RpcAuth::with_token(&url, "static".to_string());
+    assert_eq!(auth.token(Some("static")).await.unwrap(), "static");
+}
+
+#[test]
+fn test_token_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("shadow-drive").join("auth.json");
+    assert_eq!(load_cached_token(&path, "https://a.example.com/x"), None);
+
+    save_cached_token(&path, "https://a.example.com/x", "a").unwrap();
+    save_cached_token(&path, "https://b.example.com/y", "b").unwrap();
+    save_cached_token(&path, "https://a.example.com/x", "c").unwrap();
+    assert_eq!(
+        load_cached_tokens(&path),
+        HashMap::from([
+            ("https://a.example.com/x".to_string(), "c".to_string()),
+            ("https://b.example.com/y".to_string(), "b".to_string()),
+        ])
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // A corrupt cache is ignored, and replaced on the next save
+    fs::write(&path, "not json").unwrap();
+    assert_eq!(load_cached_token(&path, "https://a.example.com/x"), None);
+    save_cached_token(&path, "https://a.example.com/x", "a").unwrap();
+    assert_eq!(
+        load_cached_token(&path, "https://a.example.com/x").as_deref(),
+        Some("a")
+    );
+}
+
+#[test]
+fn test_parse_account_id_from_url() {
+    for (url, account_id) in [
+        ("https://us-west-1.genesysgo.net/abc", "abc"),
+        ("https://us-west-1.genesysgo.net/abc/", "abc"),
+        ("https://us-west-1.genesysgo.net/v1/abc?x=1", "abc"),
+    ] {
+        assert_eq!(parse_account_id_from_url(url).unwrap(), account_id);
+    }
+    for url in [
+        "https://us-west-1.genesysgo.net",
+        "https://us-west-1.genesysgo.net/",
+        "us-west-1.genesysgo.net/abc",
+    ] {
+        assert!(parse_account_id_from_url(url).is_err(), "{url}");
+    }
+}
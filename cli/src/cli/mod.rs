@@ -1,11 +1,16 @@
+pub mod portal;
 pub mod process;
+pub mod runes;
 
 use std::path::PathBuf;
 
 use byte_unit::Byte;
 use clap::Parser;
-use shadow_drive_cli::{parse_filesize, pubkey_arg, FILE_UPLOAD_BATCH_SIZE};
-use solana_sdk::pubkey::Pubkey;
+use shadow_drive_cli::{
+    callback_arg, output::OutputFormat, parse_filesize, pubkey_arg,
+    FILE_UPLOAD_BATCH_SIZE,
+};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
 
 /// Manually specify a cluster url and/or keypair.
 /// Those values otherwise default to the Solana CLI config file.
@@ -36,10 +41,26 @@ pub struct ConfigOverride {
     /// Use keyword "genesysgo" to automatically
     /// authenticate with a GenesysGo Premium RPC endpoint.
     /// GenesysGo Account ID is inferred from `-u/--url` path.
+    /// Tokens from GenesysGo are cached in the user's config directory
+    /// and fetched again when the endpoint rejects them.
     /// See also the `shadow-rpc-auth` subcommand for manually
     /// acquiring an auth token.
     #[clap(long)]
     pub auth: Option<String>,
+    /// Cluster profile to use: "mainnet", "devnet", "localnet", or any
+    /// profile in the cluster config file. Sets the program IDs, mints and
+    /// storage endpoints, and the RPC URL unless `-u/--url` is given.
+    /// Defaults to mainnet addresses with the Solana CLI's RPC URL.
+    #[clap(long)]
+    pub cluster: Option<String>,
+    /// Cluster config file, defaults to `shadow-drive/clusters.yaml` in the
+    /// user's config directory.
+    #[clap(long)]
+    pub cluster_config: Option<PathBuf>,
+    /// Format of the results printed to stdout. Progress messages and
+    /// confirmation prompts are printed to stderr.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
 }
 
 /// Perform Shadow Drive operations on the command-line.
@@ -56,6 +77,8 @@ pub struct Opts {
 
 #[derive(Debug, Parser)]
 pub enum Command {
+    /// Sign in to GenesysGo and print an auth token for the
+    /// Premium RPC endpoint given by `-u/--url`.
     ShadowRpcAuth,
     /// Create an account on which to store data.
     /// Storage accounts can be globally, irreversibly marked immutable
@@ -68,6 +91,9 @@ pub enum Command {
         /// File size string, accepts KB, MB, GB, e.g. "10MB"
         #[clap(parse(try_from_str = parse_filesize))]
         size: Byte,
+        /// Print the itemized cost without sending anything
+        #[clap(long)]
+        dry_run: bool,
     },
     /// Queues a storage account for deletion. While the request is
     /// still enqueued and not yet carried out, a cancellation
@@ -97,6 +123,9 @@ pub enum Command {
         /// File size string, accepts KB, MB, GB, e.g. "10MB"
         #[clap(parse(try_from_str = parse_filesize))]
         size: Byte,
+        /// Print the itemized cost without sending anything
+        #[clap(long)]
+        dry_run: bool,
     },
     /// Increase the immutable storage capacity of a storage account.
     AddImmutableStorage {
@@ -127,6 +156,9 @@ pub enum Command {
         /// Account whose metadata will be fetched.
         #[clap(parse(try_from_str = pubkey_arg))]
         storage_account: Pubkey,
+        /// Read the account from chain data instead of the Shadow Drive API.
+        #[clap(long)]
+        rpc_only: bool,
     },
     /// Fetch a list of storage accounts owned by a particular pubkey.
     /// If no owner is provided, the configured signer is used.
@@ -134,6 +166,9 @@ pub enum Command {
         /// Searches for storage accounts owned by this owner.
         #[clap(parse(try_from_str = pubkey_arg))]
         owner: Option<Pubkey>,
+        /// Read the accounts from chain data instead of the Shadow Drive API.
+        #[clap(long)]
+        rpc_only: bool,
     },
     /// List all the files in a storage account.
     ListFiles {
@@ -149,6 +184,45 @@ pub enum Command {
         /// Name of the file to fetch
         filename: String,
     },
+    /// Download a file of any content type.
+    Get {
+        /// Storage account where the file is located.
+        #[clap(parse(try_from_str = pubkey_arg))]
+        storage_account: Pubkey,
+        /// Name of the file to download
+        filename: String,
+        /// Path to write the file to, or `-` for stdout. Defaults to the
+        /// file's name in the current directory.
+        #[clap(short = 'o', long = "out")]
+        out: Option<PathBuf>,
+        /// Fail unless the file matches its rune in this runes file
+        #[clap(long)]
+        runes: Option<PathBuf>,
+        /// Decrypt the file with this key, derived from the signer
+        /// (`signer`) or read from a file holding 32 raw or hex bytes
+        #[clap(long, value_name = "signer|PATH")]
+        encryption_key: Option<String>,
+    },
+    /// Download every file in a storage account into a directory, named by
+    /// their paths relative to it.
+    DownloadBucket {
+        /// Storage account to download.
+        #[clap(parse(try_from_str = pubkey_arg))]
+        storage_account: Pubkey,
+        /// Directory to download into, created if missing
+        directory: PathBuf,
+        /// Maximum number of files to download at once
+        #[clap(long, default_value_t = 8)]
+        concurrency: usize,
+        /// Verify every file against its rune in this runes file, and that
+        /// every rune has a file
+        #[clap(long)]
+        runes: Option<PathBuf>,
+        /// Decrypt the files with this key, derived from the signer
+        /// (`signer`) or read from a file holding 32 raw or hex bytes
+        #[clap(long, value_name = "signer|PATH")]
+        encryption_key: Option<String>,
+    },
     /// Get basic file object data from a storage account file.
     GetObjectData {
         /// Storage account where the file is located.
@@ -180,17 +254,209 @@ pub enum Command {
         /// numbers of uploads
         #[clap(long, default_value_t=FILE_UPLOAD_BATCH_SIZE)]
         batch_size: usize,
+        /// Maximum number of upload requests in flight at once
+        #[clap(long, default_value_t = 4)]
+        concurrency: usize,
+        /// Records finished uploads in this file, so that rerunning the same
+        /// command after an interruption skips them. Files whose upload was
+        /// interrupted are uploaded again in full
+        #[clap(long)]
+        manifest: Option<PathBuf>,
         /// The storage account on which to upload the files
         #[clap(parse(try_from_str = pubkey_arg))]
         storage_account: Pubkey,
         /// A list of one or more filepaths, each of which is to be uploaded.
         #[clap(min_values = 1)]
         files: Vec<PathBuf>,
+        /// Encrypt the files first, with a key derived from the signer
+        /// (`signer`) or read from a file holding 32 raw or hex bytes
+        #[clap(long, value_name = "signer|PATH")]
+        encryption_key: Option<String>,
+        /// Print the itemized cost without sending anything
+        #[clap(long)]
+        dry_run: bool,
     },
     /// Creates an archive of metadata (runes) that can be used to summon data using the Shadow Drive Portal. Uploads data, and returns
     /// the metadata to be compiled into a smart contract.
     StoreAndCreateRunes {
+        /// Directory whose files to store, recursively. Runes are named by
+        /// each file's path relative to it.
         directory: PathBuf,
+        /// Path of the runes file to write. Updated in place if it exists
+        /// and `--storage-account` is given.
         target: PathBuf,
+        /// Store into this existing storage account instead of creating one
+        /// named after the target. Files already stored with the same
+        /// contents are skipped.
+        #[clap(long, parse(try_from_str = pubkey_arg))]
+        storage_account: Option<Pubkey>,
+        /// Only store files whose relative paths match one of these globs,
+        /// e.g. `**/*.png`
+        #[clap(long)]
+        include: Vec<String>,
+        /// Skip files whose relative paths match one of these globs
+        #[clap(long)]
+        exclude: Vec<String>,
+        /// Encrypt the files first, with a key derived from the signer
+        /// (`signer`) or read from a file holding 32 raw or hex bytes
+        #[clap(long, value_name = "signer|PATH")]
+        encryption_key: Option<String>,
+        /// Print the itemized cost without sending anything
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Regenerates the runes for an existing storage account by downloading
+    /// and hashing each of its files. If the target runes file already exists,
+    /// prints the differences and updates it in place.
+    RegenerateRunes {
+        /// Storage account whose files to create runes for.
+        #[clap(parse(try_from_str = pubkey_arg))]
+        storage_account: Pubkey,
+        /// Path of the runes file to write.
+        target: PathBuf,
+    },
+    /// Mirrors a local directory to a storage account. Files are compared by
+    /// SHA-256 hash, new files are uploaded and changed ones replaced. Prints
+    /// the plan before changing anything.
+    Sync {
+        /// Directory to mirror, recursively. Objects are named by each
+        /// file's path relative to it.
+        directory: PathBuf,
+        /// The storage account to mirror the directory to
+        #[clap(parse(try_from_str = pubkey_arg))]
+        storage_account: Pubkey,
+        /// Also delete objects that do not exist in the directory
+        #[clap(long)]
+        delete: bool,
+        /// Only sync files and objects whose relative paths match one of
+        /// these globs, e.g. `**/*.png`
+        #[clap(long)]
+        include: Vec<String>,
+        /// Skip files and objects whose relative paths match one of these
+        /// globs
+        #[clap(long)]
+        exclude: Vec<String>,
+        /// Write runes for the synced files to this path. Runes only
+        /// describe files of up to 65535 bytes, so the sync fails before
+        /// changing anything if a larger file is synced
+        #[clap(long)]
+        runes: Option<PathBuf>,
+        /// Print the plan without changing anything
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Summon Shadow Drive objects on-chain through the portal program.
+    Portal {
+        #[clap(subcommand)]
+        command: PortalCommand,
+    },
+    /// Inspect, verify and build runes files.
+    Runes {
+        #[clap(subcommand)]
+        command: RunesCommand,
+    },
+}
+
+#[derive(Debug, Parser)]
+pub enum RunesCommand {
+    /// Print the storage account and the name, length, hash and key id of
+    /// each rune.
+    Show {
+        /// Path of the runes file
+        file: PathBuf,
+    },
+    /// Download each file in a runes file from Shadow Drive and check its
+    /// length and hash.
+    Verify {
+        /// Path of the runes file
+        file: PathBuf,
+        /// Maximum number of files to download at once
+        #[clap(long, default_value_t = 8)]
+        concurrency: usize,
+    },
+    /// Create runes for local files without uploading them. Runes are named
+    /// by each file's path relative to the directory.
+    Build {
+        /// Directory whose files to create runes for, recursively
+        directory: PathBuf,
+        /// Storage account the files are, or will be, stored in
+        #[clap(long, parse(try_from_str = pubkey_arg))]
+        storage_account: Pubkey,
+        /// Path of the runes file to write. Defaults to the directory's
+        /// name in the current directory.
+        #[clap(short = 'o', long = "out")]
+        out: Option<PathBuf>,
+        /// Only include files whose relative paths match one of these globs,
+        /// e.g. `**/*.png`
+        #[clap(long)]
+        include: Vec<String>,
+        /// Skip files whose relative paths match one of these globs
+        #[clap(long)]
+        exclude: Vec<String>,
+        /// Hash the files as `--encryption-key` of `store-and-create-runes`
+        /// would store them, with a key derived from the signer (`signer`)
+        /// or read from a file holding 32 raw or hex bytes
+        #[clap(long, value_name = "signer|PATH")]
+        encryption_key: Option<String>,
+    },
+}
+
+#[derive(Debug, Parser)]
+pub enum PortalCommand {
+    /// Pay to have a file copied into an on-chain account.
+    Summon {
+        /// Storage account where the file is located.
+        #[clap(parse(try_from_str = pubkey_arg))]
+        storage_account: Pubkey,
+        /// Name of the file to summon.
+        filename: String,
+        /// Instruction for the clockwork thread to run once the data is
+        /// uploaded, as JSON: {"program_id": "<pubkey>", "accounts":
+        /// [{"pubkey": "<pubkey>", "is_signer": false, "is_writable": true}],
+        /// "data": [<bytes>]}
+        #[clap(long, parse(try_from_str = callback_arg))]
+        callback_json: Option<Instruction>,
+        /// Seed the summon account with this id instead of the file name,
+        /// so the same file can be summoned more than once at a time.
+        #[clap(long)]
+        unique_thread: Option<u64>,
+        /// Lamports paid to the uploader on top of the SHDW fee.
+        #[clap(long, default_value_t = 0)]
+        extra_lamports: u64,
+        /// Wait up to this many seconds for the data to be uploaded.
+        #[clap(long)]
+        wait: Option<u64>,
+        /// Print the itemized cost without sending anything
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Show the state of a summon.
+    Status {
+        /// The summon's metadata account.
+        #[clap(parse(try_from_str = pubkey_arg))]
+        metadata: Pubkey,
+    },
+    /// List open summons. If no summoner is provided, the configured signer
+    /// is used.
+    List {
+        /// Lists the summons made by this key.
+        #[clap(long, parse(try_from_str = pubkey_arg))]
+        summoner: Option<Pubkey>,
+    },
+    /// Withdraw a summon that has not been uploaded yet, refunding its SHDW
+    /// and rent.
+    Cancel {
+        /// The summon's metadata account.
+        #[clap(parse(try_from_str = pubkey_arg))]
+        metadata: Pubkey,
+    },
+    /// Show the portal's admin and fee.
+    Config,
+    /// Create the portal's config account. Admin only.
+    Init,
+    /// Set the portal fee. Admin only.
+    SetFee {
+        /// The new fee in shades (10^-9 SHDW) per summoned byte.
+        shades_per_byte: u64,
     },
 }
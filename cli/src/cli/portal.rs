@@ -0,0 +1,190 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::Serialize;
+use serde_json::json;
+use shadow_drive_cli::{
+    auth::{authenticated_rpc_client, RpcAuth},
+    output::OutputFormat,
+    process_shadow_api_response, wait_for_user_confirmation,
+};
+use shadow_drive_sdk::{
+    cluster::Cluster,
+    portal::{websocket_url, DataToBeSummoned, PortalClient, SummonOptions},
+    Pubkey,
+};
+use solana_sdk::signature::Signer;
+
+use super::PortalCommand;
+
+impl PortalCommand {
+    pub async fn process<T: Signer>(
+        self,
+        signer: T,
+        rpc_url: &str,
+        cluster: &Cluster,
+        skip_confirm: bool,
+        auth: Option<Arc<RpcAuth>>,
+        output: OutputFormat,
+    ) -> anyhow::Result<()> {
+        let signer_pubkey = signer.pubkey();
+        let client = match auth {
+            Some(auth) => PortalClient::new_with_rpc(
+                signer,
+                authenticated_rpc_client(rpc_url, auth),
+                websocket_url(rpc_url),
+            ),
+            None => PortalClient::new(signer, rpc_url),
+        }
+        .with_cluster(cluster);
+        match self {
+            PortalCommand::Summon {
+                storage_account,
+                filename,
+                callback_json,
+                unique_thread,
+                extra_lamports,
+                wait,
+                dry_run,
+            } => {
+                let mut options =
+                    SummonOptions::default().extra_lamports(extra_lamports);
+                if let Some(callback) = callback_json {
+                    options = options.callback(callback.into());
+                }
+                if let Some(id) = unique_thread {
+                    options = options.unique_thread(id);
+                }
+                eprintln!(
+                    "Summon {} {}",
+                    storage_account.to_string(),
+                    filename
+                );
+                if dry_run {
+                    let quote = client
+                        .estimate_summon(&storage_account, &filename, &options)
+                        .await;
+                    let quote = process_shadow_api_response(quote)?;
+                    output.print_or_text(&quote, &quote)?;
+                    return Ok(());
+                }
+                wait_for_user_confirmation(skip_confirm)?;
+                let response =
+                    client.summon(&storage_account, &filename, options).await;
+                let summon = process_shadow_api_response(response)?;
+
+                match wait {
+                    Some(wait) => {
+                        eprintln!("Summoned in {}", summon.txid);
+                        eprintln!("Waiting for upload to {}", summon.metadata);
+                        let response = client
+                            .wait_for_upload(
+                                &summon.metadata,
+                                Duration::from_secs(wait),
+                            )
+                            .await;
+                        let uploaded = process_shadow_api_response(response)?;
+                        output.print(&SummonInfo::new(
+                            &summon.metadata,
+                            &uploaded,
+                        ))?;
+                    }
+                    None => output.print(&summon)?,
+                }
+            }
+            PortalCommand::Status { metadata } => {
+                eprintln!("Get Summon {}", metadata.to_string());
+                let response = client.get_summon(&metadata).await;
+                let summon = process_shadow_api_response(response)?;
+                if summon.is_none() {
+                    eprintln!(
+                        "No summon at {}, it was closed or never created",
+                        metadata
+                    );
+                }
+                output.print(
+                    &summon.map(|summon| SummonInfo::new(&metadata, &summon)),
+                )?;
+            }
+            PortalCommand::List { summoner } => {
+                let summoner = summoner.unwrap_or(signer_pubkey);
+                eprintln!("List Summons By {}", summoner.to_string());
+                let response = client.list_summons(&summoner).await;
+                let summons: Vec<SummonInfo> =
+                    process_shadow_api_response(response)?
+                        .iter()
+                        .map(|(metadata, summon)| {
+                            SummonInfo::new(metadata, summon)
+                        })
+                        .collect();
+                output.print(&summons)?;
+            }
+            PortalCommand::Cancel { metadata } => {
+                eprintln!("Cancel Summon {}", metadata.to_string());
+                wait_for_user_confirmation(skip_confirm)?;
+                let response = client.cancel(&metadata).await;
+                let resp = process_shadow_api_response(response)?;
+                output.print(&resp)?;
+            }
+            PortalCommand::Config => {
+                let response = client.get_config().await;
+                let config = process_shadow_api_response(response)?;
+                output.print(&json!({
+                    "admin": config.admin.to_string(),
+                    "shades_per_byte": config.shades_per_byte,
+                }))?;
+            }
+            PortalCommand::Init => {
+                eprintln!("Initialize Portal Config");
+                wait_for_user_confirmation(skip_confirm)?;
+                let response = client.init_config().await;
+                let resp = process_shadow_api_response(response)?;
+                output.print(&resp)?;
+            }
+            PortalCommand::SetFee { shades_per_byte } => {
+                eprintln!(
+                    "Set Portal Fee to {} shades per byte",
+                    shades_per_byte
+                );
+                wait_for_user_confirmation(skip_confirm)?;
+                let response = client.set_fee(shades_per_byte).await;
+                let resp = process_shadow_api_response(response)?;
+                output.print(&resp)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A summon as the CLI prints it.
+#[derive(Serialize)]
+struct SummonInfo {
+    metadata: String,
+    storage_account: String,
+    filename: String,
+    hash: String,
+    summoner: String,
+    uploaded: bool,
+    /// Only set once uploaded
+    uploader: Option<String>,
+    /// Bytes of data, only set once uploaded
+    data_len: Option<usize>,
+}
+
+impl SummonInfo {
+    fn new(metadata: &Pubkey, summon: &DataToBeSummoned) -> Self {
+        Self {
+            metadata: metadata.to_string(),
+            storage_account: summon.storage_account.to_string(),
+            filename: summon.filename.clone(),
+            hash: summon
+                .hash
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            summoner: summon.summoner.to_string(),
+            uploaded: summon.uploaded,
+            uploader: summon.uploaded.then(|| summon.uploader.to_string()),
+            data_len: summon.uploaded.then(|| summon.data.len()),
+        }
+    }
+}
@@ -1,67 +1,75 @@
 use std::{
-    fs::{self, DirEntry},
-    io::Read,
+    collections::{HashMap, HashSet},
+    fs, io,
     path::{Path, PathBuf},
     str::FromStr,
-    thread::sleep,
-    time::Duration,
+    sync::Arc,
 };
 
 use anyhow::anyhow;
-use itertools::Itertools;
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use runes::Runes;
+use futures::{pin_mut, stream, Stream, StreamExt};
+use runes::{Rune, Runes, RunesError};
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use shadow_drive_cli::{
-    process_shadow_api_response, wait_for_user_confirmation, FileMetadata,
+    auth::{authenticated_rpc_client, RpcAuth},
+    confirm_with_preview, describe_storage_account,
+    output::OutputFormat,
+    process_shadow_api_response, wait_for_user_confirmation, walk_directory,
+    FileFilter, FileMetadata, Preview,
 };
 use shadow_drive_sdk::{
-    models::ShadowFile, Pubkey, ShadowDriveClient, StorageAccountVersion,
+    cluster::Cluster,
+    encryption::EncryptionKey,
+    models::{BatchUploadStatus, ShadowDriveResult, ShadowFile},
+    Byte, GetObjectOptions, ListObjectsOptions, Pubkey, ShadowDriveClient,
+    ShadowDriveClientBuilder, StorageAccountVersion, UploadOptions,
+    UploadProgress,
 };
-// use shadow_rpc_auth::{
-//     genesysgo_auth::{authenticate, parse_account_id_from_url},
-//     HttpSenderWithHeaders,
-// };
-use solana_client::{nonblocking, rpc_client::RpcClient};
 use solana_sdk::signature::Signer;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 use super::Command;
 
-/// We either create an authenticated client with default auth headers,
-/// or else we simply use the [RpcClient] provided by the normal
-/// [ShadowDriveClient] constructor.
-pub fn shadow_client_factory<T: Signer>(
+/// We either create an authenticated client that sends bearer tokens
+/// from `auth` to the RPC endpoint, and to nothing else, or else we simply
+/// use the [RpcClient] provided by the normal [ShadowDriveClient]
+/// constructor. Either way the client uses the addresses and endpoints of
+/// `cluster`.
+///
+/// [RpcClient]: solana_client::nonblocking::rpc_client::RpcClient
+pub async fn shadow_client_factory<T: Signer>(
     signer: T,
     url: &str,
-    auth: Option<String>,
-) -> ShadowDriveClient<T> {
-    if let Some(_) = auth {
-        // let mut headers = HeaderMap::new();
-        // headers.append(
-        //     HeaderName::from_str("Authorization").unwrap(),
-        //     HeaderValue::from_str(&format!("Bearer {}", auth)).unwrap(),
-        // );
-        // let rpc_client = nonblocking::rpc_client::RpcClient::new_sender(
-        //     HttpSenderWithHeaders::new(url, Some(headers.clone())),
-        //     Default::default(),
-        // );
-        // let client = RpcClient::new_sender(
-        //     HttpSenderWithHeaders::new(url, Some(headers)),
-        //     Default::default(),
-        // );
-        // let balance = client.get_balance(&signer.pubkey());
-        // match balance {
-        //     Ok(balance) => {
-        //         println!("{}: {} lamports", signer.pubkey().to_string(), balance);
-        //     }
-        //     Err(e) => {
-        //         println!("Failed to fetch balance: {:?}", e);
-        //     }
-        // }
-        // ShadowDriveClient::new_with_rpc(signer, rpc_client)
-        ShadowDriveClient::new(signer, url)
-    } else {
-        ShadowDriveClient::new(signer, url)
-    }
+    cluster: &Cluster,
+    auth: Option<Arc<RpcAuth>>,
+) -> anyhow::Result<ShadowDriveClient<T>> {
+    let auth = match auth {
+        Some(auth) => auth,
+        None => {
+            return Ok(ShadowDriveClientBuilder::new(signer, url)
+                .cluster(cluster)
+                .build())
+        }
+    };
+    let rpc_client = authenticated_rpc_client(url, auth.clone());
+    Ok(ShadowDriveClientBuilder::new_with_rpc(signer, rpc_client)
+        .cluster(cluster)
+        .http_auth(auth)
+        .build())
+}
+
+/// Loads the key named by an `--encryption-key` argument: `signer` derives
+/// it from the signer, anything else is the path of a key file.
+pub fn load_encryption_key<T: Signer>(
+    arg: &str,
+    signer: &T,
+) -> anyhow::Result<EncryptionKey> {
+    let key = match arg {
+        "signer" => EncryptionKey::from_signer(signer),
+        path => EncryptionKey::from_file(path),
+    };
+    process_shadow_api_response(key)
 }
 
 impl Command {
@@ -69,22 +77,60 @@ impl Command {
         self,
         signer: T,
         rpc_url: &str,
+        cluster: &Cluster,
         skip_confirm: bool,
-        auth: Option<String>,
+        auth: Option<Arc<RpcAuth>>,
+        output: OutputFormat,
     ) -> anyhow::Result<()> {
         let signer_pubkey = signer.pubkey();
-        println!("Signing with {:?}", signer_pubkey);
-        println!("Sending RPC requests to {}", rpc_url);
+        eprintln!("Signing with {:?}", signer_pubkey);
+        eprintln!("Sending RPC requests to {}", rpc_url);
         match self {
             Command::ShadowRpcAuth => {
-                // let account_id = parse_account_id_from_url(rpc_url.to_string())?;
-                // let resp = authenticate(&signer, &account_id).await?;
-                // println!("{:#?}", resp);
+                let auth = auth.ok_or_else(|| {
+                    anyhow!(
+                        "shadow-rpc-auth needs an RPC endpoint to sign in to"
+                    )
+                })?;
+                let token = auth.token(None).await?;
+                output.print(&json!({ "token": token }))?;
             }
-            Command::CreateStorageAccount { name, size } => {
-                let client = shadow_client_factory(signer, rpc_url, auth);
-                println!("Create Storage Account {}: {}", name, size);
-                wait_for_user_confirmation(skip_confirm)?;
+            Command::CreateStorageAccount {
+                name,
+                size,
+                dry_run,
+            } => {
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!("Create Storage Account {}: {}", name, size);
+                let quote =
+                    client.estimate_create_storage_account(size.clone()).await;
+                let quote = process_shadow_api_response(quote)?;
+                if dry_run {
+                    output.print_or_text(&quote, &quote)?;
+                    return Ok(());
+                }
+                let balance = process_shadow_api_response(
+                    client.get_shdw_balance().await,
+                )?;
+                confirm_with_preview(
+                    skip_confirm,
+                    &Preview {
+                        quote: Some(quote),
+                        shdw_balance: Some(balance),
+                        after: Some(format!(
+                            "{:?}, {} reserved, mutable",
+                            name,
+                            size.get_appropriate_unit(true)
+                        )),
+                        undo: Some(
+                            "delete-storage-account returns the storage stake"
+                                .to_string(),
+                        ),
+                        ..Default::default()
+                    },
+                )?;
                 let response = client
                     .create_storage_account(
                         &name,
@@ -93,24 +139,47 @@ impl Command {
                     )
                     .await;
                 let resp = process_shadow_api_response(response)?;
-                println!("{:#?}", resp);
+                output.print(&resp)?;
             }
             Command::DeleteStorageAccount { storage_account } => {
-                let client = shadow_client_factory(signer, rpc_url, auth);
-                println!(
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
                     "Delete Storage Account {}",
                     storage_account.to_string()
                 );
-                wait_for_user_confirmation(skip_confirm)?;
+                let account = process_shadow_api_response(
+                    client.get_storage_account(&storage_account).await,
+                )?;
+                confirm_with_preview(
+                    skip_confirm,
+                    &Preview {
+                        before: Some(describe_storage_account(&account)),
+                        after: Some(
+                            "deleted with its files at the end of the epoch, \
+                            returning the storage stake"
+                                .to_string(),
+                        ),
+                        undo: Some(
+                            "with cancel-delete-storage-account until the end \
+                            of the epoch"
+                                .to_string(),
+                        ),
+                        ..Default::default()
+                    },
+                )?;
                 let response =
                     client.delete_storage_account(&storage_account).await;
 
                 let resp = process_shadow_api_response(response)?;
-                println!("{:#?}", resp);
+                output.print(&resp)?;
             }
             Command::CancelDeleteStorageAccount { storage_account } => {
-                let client = shadow_client_factory(signer, rpc_url, auth);
-                println!(
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
                     "Cancellation of Delete Storage Account {}",
                     storage_account.to_string()
                 );
@@ -120,11 +189,13 @@ impl Command {
                     .await;
 
                 let resp = process_shadow_api_response(response)?;
-                println!("{:#?}", resp);
+                output.print(&resp)?;
             }
             Command::ClaimStake { storage_account } => {
-                let client = shadow_client_factory(signer, rpc_url, auth);
-                println!(
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
                     "Claim Stake on Storage Account {}",
                     storage_account.to_string()
                 );
@@ -132,14 +203,16 @@ impl Command {
                 let response = client.claim_stake(&storage_account).await;
 
                 let resp = process_shadow_api_response(response)?;
-                println!("{:#?}", resp);
+                output.print(&resp)?;
             }
             Command::ReduceStorage {
                 storage_account,
                 size,
             } => {
-                let client = shadow_client_factory(signer, rpc_url, auth);
-                println!(
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
                     "Reduce Storage Capacity {}: {}",
                     storage_account.to_string(),
                     size
@@ -149,31 +222,66 @@ impl Command {
                     client.reduce_storage(&storage_account, size.clone()).await;
 
                 let resp = process_shadow_api_response(response)?;
-                println!("{:#?}", resp);
+                output.print(&resp)?;
             }
             Command::AddStorage {
                 storage_account,
                 size,
+                dry_run,
             } => {
-                let client = shadow_client_factory(signer, rpc_url, auth);
-                println!(
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
                     "Increase Storage {}: {}",
                     storage_account.to_string(),
                     size
                 );
-                wait_for_user_confirmation(skip_confirm)?;
+                let quote = client.estimate_add_storage(size.clone()).await;
+                let quote = process_shadow_api_response(quote)?;
+                if dry_run {
+                    output.print_or_text(&quote, &quote)?;
+                    return Ok(());
+                }
+                let account = process_shadow_api_response(
+                    client.get_storage_account(&storage_account).await,
+                )?;
+                let balance = process_shadow_api_response(
+                    client.get_shdw_balance().await,
+                )?;
+                let reserved = account.storage() as u128 + size.get_bytes();
+                confirm_with_preview(
+                    skip_confirm,
+                    &Preview {
+                        quote: Some(quote),
+                        shdw_balance: Some(balance),
+                        before: Some(describe_storage_account(&account)),
+                        after: Some(format!(
+                            "{} reserved",
+                            Byte::from_bytes(reserved)
+                                .get_appropriate_unit(true)
+                        )),
+                        undo: Some(
+                            "reduce-storage returns the added stake"
+                                .to_string(),
+                        ),
+                        ..Default::default()
+                    },
+                )?;
                 let response =
                     client.add_storage(&storage_account, size.clone()).await;
 
                 let resp = process_shadow_api_response(response)?;
-                println!("{:#?}", resp);
+                output.print(&resp)?;
             }
             Command::AddImmutableStorage {
                 storage_account,
                 size,
             } => {
-                let client = shadow_client_factory(signer, rpc_url, auth);
-                println!(
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
                     "Increase Immutable Storage {}: {}",
                     storage_account.to_string(),
                     size
@@ -184,88 +292,338 @@ impl Command {
                     .await;
 
                 let resp = process_shadow_api_response(response)?;
-                println!("{:#?}", resp);
+                output.print(&resp)?;
             }
             Command::MakeStorageImmutable { storage_account } => {
-                let client = shadow_client_factory(signer, rpc_url, auth);
-                println!(
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
                     "Make Storage Immutable {}",
                     storage_account.to_string()
                 );
-                wait_for_user_confirmation(skip_confirm)?;
+                let account = process_shadow_api_response(
+                    client.get_storage_account(&storage_account).await,
+                )?;
+                confirm_with_preview(
+                    skip_confirm,
+                    &Preview {
+                        before: Some(describe_storage_account(&account)),
+                        after: Some(
+                            "immutable, its files can never be edited or \
+                            deleted, nor its storage reduced"
+                                .to_string(),
+                        ),
+                        undo: None,
+                        confirm_text: Some(account.identifier().to_string()),
+                        ..Default::default()
+                    },
+                )?;
                 let response =
                     client.make_storage_immutable(&storage_account).await;
 
                 let resp = process_shadow_api_response(response)?;
-                println!("{:#?}", resp);
+                output.print(&resp)?;
             }
-            Command::GetStorageAccount { storage_account } => {
-                let client = ShadowDriveClient::new(signer, rpc_url);
-                println!("Get Storage Account {}", storage_account.to_string());
-                let response =
-                    client.get_storage_account(&storage_account).await;
+            Command::GetStorageAccount {
+                storage_account,
+                rpc_only,
+            } => {
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
+                    "Get Storage Account {}",
+                    storage_account.to_string()
+                );
+                let response = if rpc_only {
+                    client.get_storage_account_rpc(&storage_account).await
+                } else {
+                    client.get_storage_account(&storage_account).await
+                };
 
                 let act = process_shadow_api_response(response)?;
-                println!("{:#?}", act);
+                output.print(&act)?;
             }
-            Command::GetStorageAccounts { owner } => {
+            Command::GetStorageAccounts { owner, rpc_only } => {
                 let client =
-                    shadow_client_factory(signer, rpc_url, auth.clone());
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
                 let owner = owner.as_ref().unwrap_or(&signer_pubkey);
-                println!("Get Storage Accounts Owned By {}", owner.to_string());
-                let response = client.get_storage_accounts(owner).await;
+                eprintln!(
+                    "Get Storage Accounts Owned By {}",
+                    owner.to_string()
+                );
+                let response = if rpc_only {
+                    client.get_storage_accounts_rpc(owner).await
+                } else {
+                    client.get_storage_accounts(owner).await
+                };
                 let accounts = process_shadow_api_response(response)?;
-                println!("{:#?}", accounts);
+                output.print(&accounts)?;
             }
             Command::ListFiles { storage_account } => {
-                let client = ShadowDriveClient::new(signer, rpc_url);
-                println!(
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
                     "List Files for Storage Account {}",
                     storage_account.to_string()
                 );
                 let response = client.list_objects(&storage_account).await;
                 let files = process_shadow_api_response(response)?;
-                println!("{:#?}", files);
+                output.print(&files)?;
             }
             Command::GetText {
                 storage_account,
                 filename,
             } => {
                 let url = shadow_drive_cli::storage_object_url(
+                    &cluster.object_prefix,
                     &storage_account,
                     &filename,
                 );
                 let resp = shadow_drive_cli::get_text(&url).await?;
                 let last_modified =
                     shadow_drive_cli::last_modified(resp.headers())?;
-                println!("Get Text at {}", &url);
-                println!("Last Modified: {}", last_modified);
-                println!("");
-                println!("{}", resp.text().await?);
+                eprintln!("Get Text at {}", &url);
+                let text = resp.text().await?;
+                output.print_or_text(
+                    &json!({
+                        "url": url,
+                        "last_modified": last_modified,
+                        "text": text,
+                    }),
+                    format_args!(
+                        "Last Modified: {}\n\n{}",
+                        last_modified, text
+                    ),
+                )?;
             }
-            Command::DeleteFile {
+            Command::Get {
                 storage_account,
                 filename,
+                out,
+                runes,
+                encryption_key,
             } => {
-                let client = ShadowDriveClient::new(signer, rpc_url);
-                let url = shadow_drive_cli::storage_object_url(
+                let key = encryption_key
+                    .map(|arg| load_encryption_key(&arg, &signer))
+                    .transpose()?;
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!("Get {} {}", storage_account.to_string(), filename);
+                let mut options = GetObjectOptions::default();
+                let verified = runes.is_some();
+                if let Some(path) = runes {
+                    let runes = load_runes(&path, &storage_account)?;
+                    let rune = runes.get_rune(&filename).ok_or_else(|| {
+                        anyhow!("{path:?} has no rune for {filename}")
+                    })?;
+                    options = options.sha256(rune.hash);
+                }
+
+                if out.as_deref() == Some(Path::new("-")) {
+                    // The data is the output, so there is no summary
+                    if let Some(key) = &key {
+                        let data = get_decrypted(
+                            &client,
+                            &storage_account,
+                            &filename,
+                            options,
+                            key,
+                        )
+                        .await?;
+                        let mut stdout = tokio::io::stdout();
+                        stdout.write_all(&data).await?;
+                        stdout.flush().await?;
+                        return Ok(());
+                    }
+                    let chunks = process_shadow_api_response(
+                        client
+                            .get_object_stream(
+                                &storage_account,
+                                &filename,
+                                options,
+                            )
+                            .await,
+                    )?;
+                    write_chunks(chunks, tokio::io::stdout()).await?;
+                    return Ok(());
+                }
+                let path = match out {
+                    Some(out) => out,
+                    None => Path::new(&filename)
+                        .file_name()
+                        .map(PathBuf::from)
+                        .ok_or_else(|| {
+                            anyhow!("{filename} has no file name, pass -o")
+                        })?,
+                };
+                let len = download_to_file(
+                    &client,
                     &storage_account,
                     &filename,
+                    options,
+                    key.as_ref(),
+                    &path,
+                )
+                .await?;
+                output.print(&json!({
+                    "storage_account": storage_account.to_string(),
+                    "filename": filename,
+                    "path": path,
+                    "bytes": len,
+                    "verified": verified,
+                    "decrypted": key.is_some(),
+                }))?;
+            }
+            Command::DownloadBucket {
+                storage_account,
+                directory,
+                concurrency,
+                runes,
+                encryption_key,
+            } => {
+                let key = encryption_key
+                    .map(|arg| load_encryption_key(&arg, &signer))
+                    .transpose()?;
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
+                    "Download Storage Account {} to {}",
+                    storage_account.to_string(),
+                    directory.display()
                 );
-                println!("Delete file {}", &url);
+                let runes = runes
+                    .map(|path| load_runes(&path, &storage_account))
+                    .transpose()?;
+                let filenames = process_shadow_api_response(
+                    client.list_objects(&storage_account).await,
+                )?;
+
+                let results: Vec<(&String, anyhow::Result<u64>)> =
+                    stream::iter(&filenames)
+                        .map(|filename| {
+                            let client = &client;
+                            let storage_account = &storage_account;
+                            let directory = &directory;
+                            let key = key.as_ref();
+                            let rune = runes
+                                .as_ref()
+                                .and_then(|runes| runes.get_rune(filename));
+                            async move {
+                                let mut options = GetObjectOptions::default();
+                                if let Some(rune) = rune {
+                                    options = options.sha256(rune.hash);
+                                }
+                                let result = async {
+                                    let path =
+                                        object_path(directory, filename)?;
+                                    if let Some(parent) = path.parent() {
+                                        tokio::fs::create_dir_all(parent)
+                                            .await?;
+                                    }
+                                    download_to_file(
+                                        client,
+                                        storage_account,
+                                        filename,
+                                        options,
+                                        key,
+                                        &path,
+                                    )
+                                    .await
+                                }
+                                .await;
+                                match &result {
+                                    Ok(_) => {
+                                        eprintln!("Downloaded {}", filename)
+                                    }
+                                    Err(e) => eprintln!(
+                                        "Failed to download {}: {}",
+                                        filename, e
+                                    ),
+                                }
+                                (filename, result)
+                            }
+                        })
+                        .buffer_unordered(concurrency.max(1))
+                        .collect()
+                        .await;
+
+                let mut downloaded = 0;
+                let mut failed = vec![];
+                for (filename, result) in results {
+                    match result {
+                        Ok(_) => downloaded += 1,
+                        Err(e) => failed.push(json!({
+                            "file": filename,
+                            "error": e.to_string(),
+                        })),
+                    }
+                }
+                let stored: HashSet<&String> = filenames.iter().collect();
+                let (missing, unverified): (Vec<String>, Vec<String>) =
+                    match &runes {
+                        Some(runes) => (
+                            runes
+                                .runes
+                                .iter()
+                                .filter(|rune| !stored.contains(&rune.name))
+                                .map(|rune| rune.name.clone())
+                                .collect(),
+                            filenames
+                                .iter()
+                                .filter(|filename| {
+                                    runes.get_rune(filename).is_none()
+                                })
+                                .cloned()
+                                .collect(),
+                        ),
+                        None => (vec![], vec![]),
+                    };
+                output.print(&json!({
+                    "storage_account": storage_account.to_string(),
+                    "directory": directory,
+                    "downloaded": downloaded,
+                    "failed": failed,
+                    "missing": missing,
+                    "unverified": unverified,
+                }))?;
+                if !failed.is_empty() || !missing.is_empty() {
+                    return Err(anyhow!(
+                        "{} files failed to download or verify and {} runes have no file",
+                        failed.len(),
+                        missing.len()
+                    ));
+                }
+            }
+            Command::DeleteFile {
+                storage_account,
+                filename,
+            } => {
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                let url = client.object_url(&storage_account, &filename);
+                eprintln!("Delete file {}", &url);
                 wait_for_user_confirmation(skip_confirm)?;
                 let response =
                     client.delete_file(&storage_account, url.clone()).await;
                 let resp = process_shadow_api_response(response)?;
-                println!("{:#?}", resp);
+                output.print(&resp)?;
             }
             Command::EditFile {
                 storage_account,
                 path,
             } => {
-                let client = ShadowDriveClient::new(signer, rpc_url);
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
                 let shadow_file = shadow_file_with_basename(&path);
-                println!(
+                eprintln!(
                     "Edit file {} {}",
                     storage_account.to_string(),
                     path.display()
@@ -274,17 +632,18 @@ impl Command {
                 let response =
                     client.edit_file(&storage_account, shadow_file).await;
                 let resp = process_shadow_api_response(response)?;
-                println!("{:#?}", resp);
+                output.print(&resp)?;
             }
             Command::GetObjectData {
                 storage_account,
                 file,
             } => {
                 let url = shadow_drive_cli::storage_object_url(
+                    &cluster.object_prefix,
                     &storage_account,
                     &file,
                 );
-                println!(
+                eprintln!(
                     "Get object data {} {}",
                     storage_account.to_string(),
                     file
@@ -292,143 +651,520 @@ impl Command {
                 let http_client = reqwest::Client::new();
                 let response = http_client.head(url).send().await?;
                 let data = FileMetadata::from_headers(response.headers())?;
-                println!("{:#?}", data);
+                output.print(&data)?;
             }
             Command::StoreFiles {
                 batch_size,
+                concurrency,
+                manifest,
                 storage_account,
                 files,
+                encryption_key,
+                dry_run,
             } => {
-                let client = ShadowDriveClient::new(signer, rpc_url);
-                println!(
-                    "Store Files {} {:#?}",
-                    storage_account.to_string(),
-                    files
-                );
-                println!(
-                    "WARNING: This CLI does not add any encryption on its own. \
-                The files in their current state become public as soon as they're uploaded."
-                );
-                wait_for_user_confirmation(skip_confirm)?;
-                for chunk in &files.iter().chunks(batch_size) {
-                    let response = client
-                        .store_files(
-                            &storage_account,
-                            chunk
-                                .map(|path: &PathBuf| {
-                                    shadow_file_with_basename(path)
-                                })
-                                .collect(),
-                        )
+                let key = encryption_key
+                    .map(|arg| load_encryption_key(&arg, &signer))
+                    .transpose()?;
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!("Store {} files in {}", files.len(), storage_account);
+                for file in &files {
+                    eprintln!("  {}", file.display());
+                }
+                let mut files: Vec<ShadowFile> =
+                    files.iter().map(shadow_file_with_basename).collect();
+                if let Some(key) = &key {
+                    eprintln!("Encrypting with key {:?}", key);
+                    let mut encrypted = Vec::with_capacity(files.len());
+                    for file in files {
+                        encrypted.push(process_shadow_api_response(
+                            file.encrypted(key).await,
+                        )?);
+                    }
+                    files = encrypted;
+                }
+                if dry_run {
+                    let quote = client
+                        .estimate_store_files(&storage_account, &files)
                         .await;
-                    let resp = process_shadow_api_response(response)?;
-                    println!("{:#?}", resp);
-                    sleep(Duration::from_millis(150));
+                    let quote = process_shadow_api_response(quote)?;
+                    output.print_or_text(&quote, &quote)?;
+                    return Ok(());
+                }
+                if key.is_none() {
+                    eprintln!(
+                        "WARNING: This CLI does not encrypt files unless \
+                    --encryption-key is given. The files in their current state become public \
+                    as soon as they're uploaded."
+                    );
+                }
+                wait_for_user_confirmation(skip_confirm)?;
+                let mut options = UploadOptions::default()
+                    .batch_size(batch_size)
+                    .concurrency(concurrency)
+                    .on_progress(print_upload_progress);
+                if let Some(manifest) = manifest {
+                    options = options.manifest(manifest);
+                }
+                let response =
+                    client.upload_files(&storage_account, files, options).await;
+                let resp = process_shadow_api_response(response)?;
+                output.print(&resp)?;
+                let failed = resp
+                    .iter()
+                    .filter(|r| matches!(r.status, BatchUploadStatus::Error(_)))
+                    .count();
+                if failed > 0 {
+                    return Err(anyhow!(
+                        "{} of {} files failed to upload",
+                        failed,
+                        resp.len()
+                    ));
                 }
             }
-            Command::StoreAndCreateRunes { directory, target } => {
-                // Get the paths and sizes of files in the given directory
+            Command::StoreAndCreateRunes {
+                directory,
+                target,
+                storage_account,
+                include,
+                exclude,
+                encryption_key,
+                dry_run,
+            } => {
+                let key = encryption_key
+                    .map(|arg| load_encryption_key(&arg, &signer))
+                    .transpose()?;
+                // Read the matching files under the given directory
                 // NOTE: this checks that all file sizes are under MAX_FILE_SIZE
-                let (paths, filesizes) = get_paths_and_sizes(&directory)?;
-                let total_bytes = filesizes.iter().sum::<usize>() as u64;
-
-                // Check if target exists
-                if target.exists() {
-                    return Err(anyhow!("{target:?} already exists"));
-                }
+                let filter = FileFilter::new(&include, &exclude)?;
+                let files =
+                    read_small_files(&directory, &filter, key.as_ref())?;
+                let target = target.with_extension("runes");
                 if let Some(parent) = target.parent() {
                     if !parent.eq(Path::new("")) {
-                        fs::create_dir_all(parent);
+                        fs::create_dir_all(parent)?;
                     }
                 }
 
-                // Check user has enough SHDW
-                let client = ShadowDriveClient::new(signer, rpc_url);
-                let (storage_price, min_size) = client
-                    .get_storage_price_and_min_account_size()
-                    .await
-                    .map_err(|e| anyhow!("{e:?}"))?;
-
-                let cost: u64 = (total_bytes.max(min_size) as u128)
-                    .checked_mul(storage_price as u128)
-                    .unwrap()
-                    .checked_div(2u128.pow(30) as u128)
-                    .unwrap()
-                    .try_into()
-                    .unwrap();
-
-                let balance: u64 = client
-                    .get_shdw_balance()
-                    .await
-                    .map_err(|e| anyhow!("{e:?}"))?;
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                let (storage_account, plan) = match storage_account {
+                    Some(storage_account) => {
+                        // Only upload new or changed files
+                        let plan =
+                            plan_uploads(&client, &storage_account, &files)
+                                .await?;
+                        eprintln!(
+                            "{} new, {} changed and {} unchanged files",
+                            plan.new.len(),
+                            plan.changed.len(),
+                            plan.unchanged
+                        );
+                        let new_files: Vec<ShadowFile> = plan
+                            .new
+                            .iter()
+                            .map(|file| file.shadow_file())
+                            .collect();
+                        let quote = client
+                            .estimate_store_files(&storage_account, &new_files)
+                            .await;
+                        let quote = process_shadow_api_response(quote)?;
+                        if dry_run {
+                            output.print_or_text(&quote, &quote)?;
+                            return Ok(());
+                        }
+                        let account = process_shadow_api_response(
+                            client.get_storage_account(&storage_account).await,
+                        )?;
+                        let balance = process_shadow_api_response(
+                            client.get_shdw_balance().await,
+                        )?;
+                        confirm_with_preview(
+                            skip_confirm,
+                            &Preview {
+                                quote: Some(quote),
+                                shdw_balance: Some(balance),
+                                before: Some(describe_storage_account(
+                                    &account,
+                                )),
+                                after: Some(format!(
+                                    "{} files uploaded and {} replaced",
+                                    plan.new.len(),
+                                    plan.changed.len()
+                                )),
+                                undo: (plan.changed.is_empty()
+                                    && !account.is_immutable())
+                                .then(|| {
+                                    "delete-file removes the uploaded files"
+                                        .to_string()
+                                }),
+                                ..Default::default()
+                            },
+                        )?;
+                        (storage_account, plan)
+                    }
+                    None => {
+                        if target.exists() {
+                            return Err(anyhow!(
+                                "{target:?} already exists, pass --storage-account to update it"
+                            ));
+                        }
+                        let total_bytes = files
+                            .iter()
+                            .map(|file| file.data.len() as u64)
+                            .sum::<u64>();
 
-                if cost > balance {
-                    return Err(anyhow!(
-                        "Insufficient funds, cost = {cost:?}, balance = {balance:?}"
-                    ));
+                        // Check user has enough SHDW
+                        let (_, min_size) = process_shadow_api_response(
+                            client
+                                .get_storage_price_and_min_account_size()
+                                .await,
+                        )?;
+                        let quote = process_shadow_api_response(
+                            client
+                                .estimate_create_storage_account(
+                                    total_bytes.max(min_size).into(),
+                                )
+                                .await,
+                        )?;
+                        if dry_run {
+                            output.print_or_text(&quote, &quote)?;
+                            return Ok(());
+                        }
+
+                        let cost = quote.shades();
+                        let balance: u64 = process_shadow_api_response(
+                            client.get_shdw_balance().await,
+                        )?;
+
+                        if cost > balance {
+                            return Err(anyhow!(
+                                "Insufficient funds, cost = {cost:?}, balance = {balance:?}"
+                            ));
+                        }
+
+                        // Create storage account with target name
+                        let target_name = target
+                            .file_stem()
+                            .ok_or_else(|| {
+                                anyhow!("{target:?} has no file name")
+                            })?
+                            .to_string_lossy();
+                        confirm_with_preview(
+                            skip_confirm,
+                            &Preview {
+                                quote: Some(quote),
+                                shdw_balance: Some(balance),
+                                after: Some(format!(
+                                    "{:?}, {} reserved, mutable, with {} files",
+                                    target_name,
+                                    Byte::from_bytes(
+                                        total_bytes.max(min_size).into()
+                                    )
+                                    .get_appropriate_unit(true),
+                                    files.len()
+                                )),
+                                undo: Some(
+                                    "delete-storage-account returns the storage \
+                                    stake"
+                                        .to_string(),
+                                ),
+                                ..Default::default()
+                            },
+                        )?;
+                        let response = client
+                            .create_storage_account(
+                                &target_name,
+                                total_bytes.max(min_size).into(),
+                                StorageAccountVersion::V2,
+                            )
+                            .await;
+                        let bucket = process_shadow_api_response(response)?
+                            .shdw_bucket
+                            .ok_or_else(|| {
+                                anyhow!(
+                                    "Shadow Drive did not return the new \
+                                    storage account"
+                                )
+                            })?;
+                        let storage_account = Pubkey::from_str(&bucket)
+                            .map_err(|e| {
+                                anyhow!(
+                                    "invalid storage account {bucket:?}: {e}"
+                                )
+                            })?;
+                        eprintln!("Created storage account");
+
+                        let plan = UploadPlan {
+                            new: files.iter().collect(),
+                            ..Default::default()
+                        };
+                        (storage_account, plan)
+                    }
+                };
+
+                // Upload new files and replace changed ones
+                if !plan.new.is_empty() {
+                    let shadow_files = plan
+                        .new
+                        .iter()
+                        .map(|file| file.shadow_file())
+                        .collect();
+                    let response = client
+                        .upload_files(
+                            &storage_account,
+                            shadow_files,
+                            UploadOptions::default(),
+                        )
+                        .await;
+                    let resp = process_shadow_api_response(response)?;
+                    for upload in &resp {
+                        if let BatchUploadStatus::Error(error) = &upload.status
+                        {
+                            return Err(anyhow!(
+                                "failed to upload {}: {}",
+                                upload.file_name,
+                                error
+                            ));
+                        }
+                    }
                 }
+                for file in &plan.changed {
+                    process_shadow_api_response(
+                        client
+                            .edit_file(&storage_account, file.shadow_file())
+                            .await,
+                    )?;
+                }
+                eprintln!("Uploaded data to Shadow Drive.");
+                let summary = json!({
+                    "storage_account": storage_account.to_string(),
+                    "runes": target,
+                    "new": plan.new.len(),
+                    "changed": plan.changed.len(),
+                    "unchanged": plan.unchanged,
+                });
 
-                // Create storage account with target name
-                let target_name = target.file_name().unwrap().to_string_lossy();
-                let response = client
-                    .create_storage_account(
-                        &target_name,
-                        total_bytes.max(min_size).into(),
-                        StorageAccountVersion::V2,
-                    )
-                    .await;
-                let storage_account: Pubkey = Pubkey::from_str(
-                    &process_shadow_api_response(response)?
-                        .shdw_bucket
-                        .unwrap(),
+                // Generate runes, updating an existing runes file in place
+                let runes = Runes {
+                    storage_account: storage_account.to_bytes(),
+                    runes: files.into_iter().map(|file| file.rune).collect(),
+                };
+                let runes = if target.exists() {
+                    let mut existing = Runes::load(&target)
+                        .map_err(|e| anyhow!("failed to load runes: {e}"))?;
+                    existing.merge(runes).map_err(|_| {
+                        anyhow!(
+                            "{target:?} belongs to a different storage account"
+                        )
+                    })?;
+                    existing
+                } else {
+                    runes
+                };
+                runes
+                    .save(target)
+                    .map_err(|e| anyhow!("failed to save runes: {e}"))?;
+                output.print(&summary)?;
+            }
+            Command::RegenerateRunes {
+                storage_account,
+                target,
+            } => {
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
+                    "Regenerate Runes for Storage Account {}",
+                    storage_account.to_string()
+                );
+                let runes = shadow_drive_cli::runes_from_bucket(
+                    &client,
+                    &storage_account,
                 )
-                .unwrap();
-                println!("Created storage account");
+                .await?;
 
-                // Load all filedata
-                let (filedata, filenames): (Vec<Vec<u8>>, Vec<String>) = paths
-                    .iter()
-                    .zip(&filesizes)
-                    .map(|(path, &size)| {
-                        let mut buffer = Vec::with_capacity(size);
-                        let mut file = std::fs::File::open(path)
-                            .expect("file has been checked");
-                        file.read_to_end(&mut buffer)
-                            .expect("file has been checked");
-                        (
-                            buffer,
-                            path.file_name()
-                                .unwrap()
-                                .to_str()
-                                .unwrap()
-                                .to_string(),
-                        )
+                // Update an existing runes file in place, otherwise create it
+                let target = target.with_extension("runes");
+                let mut existing = if target.exists() {
+                    let existing = Runes::load(&target)
+                        .map_err(|e| anyhow!("failed to load runes: {e}"))?;
+                    if existing.storage_account != runes.storage_account {
+                        return Err(anyhow!(
+                            "{target:?} belongs to a different storage account"
+                        ));
+                    }
+                    existing
+                } else {
+                    Runes {
+                        storage_account: runes.storage_account,
+                        runes: vec![],
+                    }
+                };
+                let diff = runes::diff(&existing, &runes);
+                diff.apply(&mut existing);
+                existing
+                    .save(target.clone())
+                    .map_err(|e| anyhow!("failed to save runes: {e}"))?;
+                let names = |runes: &[Rune]| {
+                    runes
+                        .iter()
+                        .map(|rune| rune.name.clone())
+                        .collect::<Vec<_>>()
+                };
+                output.print(&json!({
+                    "storage_account": storage_account.to_string(),
+                    "runes": target,
+                    "added": names(&diff.added),
+                    "removed": names(&diff.removed),
+                    "changed": diff
+                        .changed
+                        .iter()
+                        .map(|(_, rune)| rune.name.clone())
+                        .collect::<Vec<_>>(),
+                }))?;
+            }
+            Command::Sync {
+                directory,
+                storage_account,
+                delete,
+                include,
+                exclude,
+                runes,
+                dry_run,
+            } => {
+                let filter = FileFilter::new(&include, &exclude)?;
+                let files = walk_directory(&directory, &filter)?
+                    .into_iter()
+                    .map(|(path, name)| SyncFile::new(path, name))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                // Check that runes can be made before changing anything
+                let runes = runes
+                    .map(|target| {
+                        sync_runes(&storage_account, &files).map(|runes| {
+                            (target.with_extension("runes"), runes)
+                        })
+                    })
+                    .transpose()?;
+
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
+                    "Sync {} to Storage Account {}",
+                    directory.display(),
+                    storage_account.to_string()
+                );
+                let plan =
+                    plan_sync(&client, &storage_account, &files, |key| {
+                        delete && filter.matches(key)
                     })
-                    .unzip();
-
-                // Generate runes
-                let runes = Runes::new(
-                    storage_account.to_bytes(),
-                    filenames.clone(),
-                    &filedata,
-                    filesizes.clone(),
+                    .await?;
+                for file in &plan.upload {
+                    eprintln!("  upload {}", file.name);
+                }
+                for file in &plan.edit {
+                    eprintln!("  edit   {}", file.name);
+                }
+                for name in &plan.delete {
+                    eprintln!("  delete {}", name);
+                }
+                eprintln!(
+                    "{} to upload, {} to edit, {} to delete and {} unchanged",
+                    plan.upload.len(),
+                    plan.edit.len(),
+                    plan.delete.len(),
+                    plan.unchanged
                 );
+                let names = |files: &[&SyncFile]| {
+                    files
+                        .iter()
+                        .map(|file| file.name.clone())
+                        .collect::<Vec<_>>()
+                };
+                let summary = json!({
+                    "storage_account": storage_account.to_string(),
+                    "upload": names(&plan.upload),
+                    "edit": names(&plan.edit),
+                    "delete": plan.delete,
+                    "unchanged": plan.unchanged,
+                });
+                if dry_run {
+                    output.print(&summary)?;
+                    return Ok(());
+                }
+                if !plan.upload.is_empty()
+                    || !plan.edit.is_empty()
+                    || !plan.delete.is_empty()
+                {
+                    wait_for_user_confirmation(skip_confirm)?;
+                }
 
-                // Upload data to account
-                let shadow_files: Vec<ShadowFile> = filedata
-                    .into_iter()
-                    .zip(filenames)
-                    .map(|(data, name)| ShadowFile::bytes(name, data))
-                    .collect();
-                process_shadow_api_response(
-                    client.store_files(&storage_account, shadow_files).await,
-                )?;
-                println!("Uploaded data to Shadow Drive.");
+                if !plan.upload.is_empty() {
+                    let shadow_files = plan
+                        .upload
+                        .iter()
+                        .map(|file| file.shadow_file())
+                        .collect();
+                    let options = UploadOptions::default()
+                        .on_progress(print_upload_progress);
+                    let response = client
+                        .upload_files(&storage_account, shadow_files, options)
+                        .await;
+                    let resp = process_shadow_api_response(response)?;
+                    for upload in &resp {
+                        if let BatchUploadStatus::Error(error) = &upload.status
+                        {
+                            return Err(anyhow!(
+                                "failed to upload {}: {}",
+                                upload.file_name,
+                                error
+                            ));
+                        }
+                    }
+                }
+                for file in &plan.edit {
+                    process_shadow_api_response(
+                        client
+                            .edit_file(&storage_account, file.shadow_file())
+                            .await,
+                    )?;
+                    eprintln!("Edited {}", file.name);
+                }
+                for name in &plan.delete {
+                    let url = client.object_url(&storage_account, name);
+                    process_shadow_api_response(
+                        client.delete_file(&storage_account, url).await,
+                    )?;
+                    eprintln!("Deleted {}", name);
+                }
 
-                // Save runes to disk
-                runes
-                    .save(target)
-                    .map_err(|e| anyhow!("failed to save runes {e:?}"))?;
+                if let Some((target, runes)) = runes {
+                    runes
+                        .save(target.clone())
+                        .map_err(|e| anyhow!("failed to save runes: {e}"))?;
+                    eprintln!("Saved runes to {}", target.display());
+                }
+                output.print(&summary)?;
+            }
+            Command::Runes { command } => {
+                command
+                    .process(signer, rpc_url, cluster, auth, output)
+                    .await?;
+            }
+            Command::Portal { command } => {
+                command
+                    .process(
+                        signer,
+                        rpc_url,
+                        cluster,
+                        skip_confirm,
+                        auth,
+                        output,
+                    )
+                    .await?;
             }
         }
         Ok(())
@@ -436,34 +1172,350 @@ impl Command {
 }
 
 pub const MAX_FILE_SIZE: usize = 1000;
-/// Gets the paths and sizes of files in the given directory
-fn get_paths_and_sizes(
-    directory: &PathBuf,
-) -> anyhow::Result<(Vec<PathBuf>, Vec<usize>)> {
-    let mut filesizes = vec![];
-    let mut paths = vec![];
-    if let Ok(paths_iter) = fs::read_dir(directory) {
-        for path in paths_iter {
-            if let Ok(path) = path {
-                if let Ok(metadata) = path.metadata() {
-                    if (metadata.len() as usize) > MAX_FILE_SIZE {
-                        return Err(anyhow!(
-                            "{path:?} is larger than {MAX_FILE_SIZE} bytes"
-                        ));
-                    }
-                    filesizes.push(metadata.len() as usize);
-                    paths.push(path.path());
+
+/// A file to be stored and described by a rune.
+struct LocalFile {
+    rune: Rune,
+    data: Vec<u8>,
+}
+
+impl LocalFile {
+    fn shadow_file(&self) -> ShadowFile {
+        ShadowFile::bytes(self.rune.name.clone(), self.data.clone())
+    }
+}
+
+/// Reads the files under `directory` that `filter` matches, named by their
+/// relative paths, and encrypts them with `key` if it is given. Fails if any
+/// is larger than [MAX_FILE_SIZE] as stored.
+fn read_small_files(
+    directory: &Path,
+    filter: &FileFilter,
+    key: Option<&EncryptionKey>,
+) -> anyhow::Result<Vec<LocalFile>> {
+    let mut files = vec![];
+    for (path, name) in walk_directory(directory, filter)? {
+        let mut data = fs::read(&path)
+            .map_err(|e| anyhow!("failed to read {path:?}: {e}"))?;
+        if let Some(key) = key {
+            data = process_shadow_api_response(key.encrypt(&name, &data))?;
+        }
+        if data.len() > MAX_FILE_SIZE {
+            return Err(anyhow!(
+                "{path:?} is larger than {MAX_FILE_SIZE} bytes"
+            ));
+        }
+        let mut rune = Rune::from_data(name, &data)?;
+        if let Some(key) = key {
+            rune = rune.with_key_id(key.id());
+        }
+        files.push(LocalFile { rune, data });
+    }
+    Ok(files)
+}
+
+#[derive(Default)]
+struct UploadPlan<'a> {
+    new: Vec<&'a LocalFile>,
+    changed: Vec<&'a LocalFile>,
+    unchanged: usize,
+}
+
+/// Sorts `files` into those missing from the storage account, those whose
+/// contents differ from the stored object, and those already stored.
+async fn plan_uploads<'a, T: Signer>(
+    client: &ShadowDriveClient<T>,
+    storage_account: &Pubkey,
+    files: &'a [LocalFile],
+) -> anyhow::Result<UploadPlan<'a>> {
+    let stored = stored_hashes(client, storage_account).await?;
+
+    let mut plan = UploadPlan::default();
+    for file in files {
+        match stored.get(&file.rune.name) {
+            None => plan.new.push(file),
+            Some(Some(hash)) if *hash == file.rune.hash => plan.unchanged += 1,
+            Some(Some(_)) => plan.changed.push(file),
+            Some(None) => {
+                // The server did not report a hash, so compare the contents
+                let data = client
+                    .get_object(storage_account, &file.rune.name)
+                    .await
+                    .map_err(|e| {
+                        anyhow!("failed to download {}: {}", file.rune.name, e)
+                    })?;
+                if data[..] == file.data[..] {
+                    plan.unchanged += 1;
                 } else {
-                    return Err(anyhow!("could not get metadata for {path:?}"));
+                    plan.changed.push(file);
                 }
-            } else {
-                return Err(anyhow!("{path:?} not valid or does not exist"));
             }
         }
-    } else {
-        return Err(anyhow!("{directory:?} not valid or does not exist"));
     }
-    Ok((paths, filesizes))
+    Ok(plan)
+}
+
+/// A local file to be synced, named by its path relative to the synced
+/// directory.
+struct SyncFile {
+    path: PathBuf,
+    name: String,
+    len: u64,
+    hash: [u8; 32],
+}
+
+impl SyncFile {
+    fn new(path: PathBuf, name: String) -> anyhow::Result<Self> {
+        let mut file = fs::File::open(&path)
+            .map_err(|e| anyhow!("failed to read {path:?}: {e}"))?;
+        let mut hasher = Sha256::new();
+        let len = io::copy(&mut file, &mut hasher)
+            .map_err(|e| anyhow!("failed to read {path:?}: {e}"))?;
+        Ok(Self {
+            path,
+            name,
+            len,
+            hash: hasher.finalize().into(),
+        })
+    }
+
+    fn shadow_file(&self) -> ShadowFile {
+        ShadowFile::file(self.name.clone(), &self.path)
+    }
+}
+
+#[derive(Default)]
+struct SyncPlan<'a> {
+    upload: Vec<&'a SyncFile>,
+    edit: Vec<&'a SyncFile>,
+    delete: Vec<String>,
+    unchanged: usize,
+}
+
+/// Sorts `files` into those missing from the storage account, those whose
+/// contents differ from the stored object, and those already stored, and
+/// lists the stored objects without a local file for which `delete` is true.
+async fn plan_sync<'a, T: Signer>(
+    client: &ShadowDriveClient<T>,
+    storage_account: &Pubkey,
+    files: &'a [SyncFile],
+    delete: impl Fn(&str) -> bool,
+) -> anyhow::Result<SyncPlan<'a>> {
+    let mut stored = stored_hashes(client, storage_account).await?;
+
+    let mut plan = SyncPlan::default();
+    for file in files {
+        let stored_hash = match stored.remove(&file.name) {
+            None => {
+                plan.upload.push(file);
+                continue;
+            }
+            Some(Some(hash)) => hash,
+            Some(None) => {
+                // The server did not report a hash, so hash the stored data
+                let data = client
+                    .get_object(storage_account, &file.name)
+                    .await
+                    .map_err(|e| {
+                        anyhow!("failed to download {}: {}", file.name, e)
+                    })?;
+                Sha256::digest(&data).into()
+            }
+        };
+        if stored_hash == file.hash {
+            plan.unchanged += 1;
+        } else {
+            plan.edit.push(file);
+        }
+    }
+    plan.delete = stored.into_keys().filter(|key| delete(key)).collect();
+    plan.delete.sort();
+    Ok(plan)
+}
+
+/// Runes for the synced files, which must each fit in a [Rune].
+fn sync_runes(
+    storage_account: &Pubkey,
+    files: &[SyncFile],
+) -> anyhow::Result<Runes> {
+    let runes = files
+        .iter()
+        .map(|file| {
+            Ok(Rune {
+                name: file.name.clone(),
+                len: file.len.try_into().map_err(|_| {
+                    RunesError::RuneTooLarge {
+                        name: file.name.clone(),
+                        len: file.len as usize,
+                    }
+                })?,
+                hash: file.hash,
+                key_id: None,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok(Runes {
+        storage_account: storage_account.to_bytes(),
+        runes,
+    })
+}
+
+/// Hashes of the objects in a storage account by name, `None` for objects
+/// the server did not report a hash for.
+async fn stored_hashes<T: Signer>(
+    client: &ShadowDriveClient<T>,
+    storage_account: &Pubkey,
+) -> anyhow::Result<HashMap<String, Option<[u8; 32]>>> {
+    let mut stored = HashMap::new();
+    let mut options = ListObjectsOptions::default();
+    loop {
+        let page = process_shadow_api_response(
+            client
+                .list_objects_detailed(storage_account, options.clone())
+                .await,
+        )?;
+        stored.extend(
+            page.objects
+                .into_iter()
+                .map(|object| (object.key, object.sha256)),
+        );
+        match page.next_cursor {
+            Some(cursor) => options = options.cursor(cursor),
+            None => break,
+        }
+    }
+    Ok(stored)
+}
+
+fn print_upload_progress(progress: &UploadProgress) {
+    match progress {
+        UploadProgress::Started { file_name, attempt } if *attempt > 1 => {
+            eprintln!("Retrying {} (attempt {})", file_name, attempt)
+        }
+        UploadProgress::Uploaded { location, .. } => {
+            eprintln!("Uploaded {}", location)
+        }
+        UploadProgress::Skipped { file_name } => {
+            eprintln!("Skipped {}, already uploaded", file_name)
+        }
+        UploadProgress::Failed { file_name, error } => {
+            eprintln!("Failed to upload {}: {}", file_name, error)
+        }
+        _ => {}
+    }
+}
+
+/// Loads the runes file at `path`, which must be for `storage_account`.
+fn load_runes(path: &Path, storage_account: &Pubkey) -> anyhow::Result<Runes> {
+    let runes =
+        Runes::load(path).map_err(|e| anyhow!("failed to load runes: {e}"))?;
+    if runes.storage_account != storage_account.to_bytes() {
+        return Err(anyhow!("{path:?} belongs to a different storage account"));
+    }
+    Ok(runes)
+}
+
+/// Path of the object named `key` under `directory`. Fails for names that
+/// could point outside of it.
+fn object_path(directory: &Path, key: &str) -> anyhow::Result<PathBuf> {
+    let mut path = directory.to_path_buf();
+    for segment in key.split('/') {
+        if segment.is_empty()
+            || segment == "."
+            || segment == ".."
+            || segment.contains('\\')
+        {
+            return Err(anyhow!("{key:?} is not a safe relative path"));
+        }
+        path.push(segment);
+    }
+    Ok(path)
+}
+
+/// Streams an object into the file at `path`, through a temporary file next
+/// to it so that `path` only ever holds complete, verified data. Objects
+/// decrypted with `key` are downloaded whole instead. Returns the number of
+/// bytes written.
+async fn download_to_file<T: Signer>(
+    client: &ShadowDriveClient<T>,
+    storage_account: &Pubkey,
+    filename: &str,
+    options: GetObjectOptions,
+    key: Option<&EncryptionKey>,
+    path: &Path,
+) -> anyhow::Result<u64> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{path:?} is not a file path"))?;
+    let partial =
+        path.with_file_name(format!("{}.part", name.to_string_lossy()));
+    let result = async {
+        let len = match key {
+            Some(key) => {
+                let data = get_decrypted(
+                    client,
+                    storage_account,
+                    filename,
+                    options,
+                    key,
+                )
+                .await?;
+                let file = tokio::fs::File::create(&partial).await?;
+                write_chunks(stream::iter([ShadowDriveResult::Ok(data)]), file)
+                    .await?
+            }
+            None => {
+                let chunks = process_shadow_api_response(
+                    client
+                        .get_object_stream(storage_account, filename, options)
+                        .await,
+                )?;
+                let file = tokio::fs::File::create(&partial).await?;
+                write_chunks(chunks, file).await?
+            }
+        };
+        tokio::fs::rename(&partial, path).await?;
+        Ok::<_, anyhow::Error>(len)
+    }
+    .await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&partial).await;
+    }
+    result
+}
+
+/// Downloads an object whole, checking it against `options` before it is
+/// decrypted with `key`, so that runes hashes are of the stored ciphertext.
+async fn get_decrypted<T: Signer>(
+    client: &ShadowDriveClient<T>,
+    storage_account: &Pubkey,
+    filename: &str,
+    options: GetObjectOptions,
+    key: &EncryptionKey,
+) -> anyhow::Result<Vec<u8>> {
+    let data = process_shadow_api_response(
+        client
+            .get_object_with_options(storage_account, filename, options)
+            .await,
+    )?;
+    process_shadow_api_response(key.decrypt(filename, &data))
+}
+
+/// Writes the chunks of an object to `writer`, returning the number of bytes
+/// written.
+async fn write_chunks<B: AsRef<[u8]>, W: AsyncWrite + Unpin>(
+    chunks: impl Stream<Item = ShadowDriveResult<B>>,
+    mut writer: W,
+) -> anyhow::Result<u64> {
+    pin_mut!(chunks);
+    let mut len = 0;
+    while let Some(chunk) = chunks.next().await {
+        let chunk = process_shadow_api_response(chunk)?;
+        writer.write_all(chunk.as_ref()).await?;
+        len += chunk.as_ref().len() as u64;
+    }
+    writer.flush().await?;
+    Ok(len)
 }
 
 // TODO Maybe make this a result type.
@@ -478,3 +1530,257 @@ pub fn shadow_file_with_basename(path: &PathBuf) -> ShadowFile {
     };
     ShadowFile::file(basename, path.clone())
 }
+
+/// A client for `drive` with a random wallet that owns `storage_account`.
+/// Its RPC URL points at a closed port, so only storage API requests can
+/// succeed.
+#[cfg(test)]
+fn test_client(
+    drive: &shadow_drive_mock::MockShadowDrive,
+    storage_account: &Pubkey,
+) -> ShadowDriveClient<solana_sdk::signature::Keypair> {
+    use shadow_drive_mock::MockStorageAccount;
+    use solana_sdk::signature::Keypair;
+
+    let wallet = Keypair::new();
+    drive.add_storage_account(MockStorageAccount::new(
+        storage_account,
+        wallet.pubkey(),
+    ));
+    ShadowDriveClientBuilder::new(wallet, "http://127.0.0.1:1")
+        .storage_endpoint(drive.endpoint())
+        .object_prefix(drive.object_prefix())
+        .build()
+}
+
+/// Writes `files` of relative paths and contents under `dir`.
+#[cfg(test)]
+fn write_test_files(dir: &Path, files: &[(&str, &str)]) {
+    for (name, data) in files {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_store_nested_directory() {
+    use shadow_drive_mock::MockShadowDrive;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let storage_account = Pubkey::new_unique();
+    let client = test_client(&drive, &storage_account);
+    let dir = tempfile::tempdir().unwrap();
+    write_test_files(
+        dir.path(),
+        &[("b.txt", "b"), ("a/c.png", "c"), ("a/d/e.png", "e")],
+    );
+    let filter = FileFilter::new(&[], &[]).unwrap();
+    let files = read_small_files(dir.path(), &filter, None).unwrap();
+    let names: Vec<_> = files.iter().map(|file| &file.rune.name).collect();
+    assert_eq!(names, ["a/c.png", "a/d/e.png", "b.txt"]);
+
+    let plan = plan_uploads(&client, &storage_account, &files)
+        .await
+        .unwrap();
+    assert_eq!(plan.new.len(), 3);
+    let shadow_files = plan.new.iter().map(|file| file.shadow_file()).collect();
+    let uploads = client
+        .upload_files(&storage_account, shadow_files, UploadOptions::default())
+        .await
+        .unwrap();
+    assert!(uploads
+        .iter()
+        .all(|upload| !matches!(upload.status, BatchUploadStatus::Error(_))));
+    assert_eq!(
+        drive.get_object(storage_account, "a/d/e.png").unwrap(),
+        b"e"
+    );
+
+    // Objects are named by the relative paths, so nothing is uploaded twice
+    let plan = plan_uploads(&client, &storage_account, &files)
+        .await
+        .unwrap();
+    assert!(plan.new.is_empty());
+    assert!(plan.changed.is_empty());
+    assert_eq!(plan.unchanged, 3);
+}
+
+#[tokio::test]
+async fn test_plan_sync() {
+    use shadow_drive_mock::MockShadowDrive;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let storage_account = Pubkey::new_unique();
+    let client = test_client(&drive, &storage_account);
+    for (name, data) in [
+        ("same.txt", "same"),
+        ("changed.txt", "old"),
+        ("remote.txt", "remote"),
+        ("keep/remote.png", "remote"),
+    ] {
+        drive
+            .put_object(storage_account, name, data.as_bytes())
+            .unwrap();
+    }
+    let dir = tempfile::tempdir().unwrap();
+    write_test_files(
+        dir.path(),
+        &[
+            ("same.txt", "same"),
+            ("changed.txt", "new"),
+            ("new.txt", "new"),
+        ],
+    );
+    let files: Vec<_> = ["changed.txt", "new.txt", "same.txt"]
+        .into_iter()
+        .map(|name| {
+            SyncFile::new(dir.path().join(name), name.to_string()).unwrap()
+        })
+        .collect();
+    let names = |files: &[&SyncFile]| -> Vec<String> {
+        files.iter().map(|file| file.name.clone()).collect()
+    };
+
+    // Objects are only deleted if `delete` is true for them, which sync
+    // uses to honour the filters
+    let filter = FileFilter::new(&[], &["keep/**".to_string()]).unwrap();
+    let plan =
+        plan_sync(&client, &storage_account, &files, |key| filter.matches(key))
+            .await
+            .unwrap();
+    assert_eq!(names(&plan.upload), ["new.txt"]);
+    assert_eq!(names(&plan.edit), ["changed.txt"]);
+    assert_eq!(plan.delete, ["remote.txt"]);
+    assert_eq!(plan.unchanged, 1);
+    let plan = plan_sync(&client, &storage_account, &files, |_| false)
+        .await
+        .unwrap();
+    assert!(plan.delete.is_empty());
+    assert_eq!(drive.requests(&format!("/{storage_account}/same.txt")), 0);
+
+    // Objects listed without hashes are downloaded to hash them
+    drive.set_legacy_listing(true);
+    let plan = plan_sync(&client, &storage_account, &files, |_| true)
+        .await
+        .unwrap();
+    assert_eq!(names(&plan.upload), ["new.txt"]);
+    assert_eq!(names(&plan.edit), ["changed.txt"]);
+    assert_eq!(plan.delete, ["keep/remote.png", "remote.txt"]);
+    assert_eq!(plan.unchanged, 1);
+    assert_eq!(drive.requests(&format!("/{storage_account}/same.txt")), 1);
+    assert_eq!(
+        drive.requests(&format!("/{storage_account}/changed.txt")),
+        1
+    );
+}
+
+#[test]
+fn test_sync_runes() {
+    let storage_account = Pubkey::new_unique();
+    let dir = tempfile::tempdir().unwrap();
+    write_test_files(dir.path(), &[("a.txt", "alpha")]);
+    fs::write(dir.path().join("big.bin"), vec![0; u16::MAX as usize + 1])
+        .unwrap();
+    let file = |name: &str| {
+        SyncFile::new(dir.path().join(name), name.to_string()).unwrap()
+    };
+
+    let runes = sync_runes(&storage_account, &[file("a.txt")]).unwrap();
+    assert_eq!(runes.storage_account, storage_account.to_bytes());
+    assert_eq!(
+        runes.runes,
+        [Rune::from_data("a.txt".to_string(), &b"alpha".to_vec()).unwrap()]
+    );
+
+    // Files too large for a rune fail the whole sync
+    let error = sync_runes(&storage_account, &[file("a.txt"), file("big.bin")])
+        .unwrap_err();
+    assert!(error.to_string().contains("at most 65535 bytes"), "{error}");
+}
+
+#[test]
+fn test_object_path() {
+    let dir = Path::new("downloads");
+    assert_eq!(object_path(dir, "a.txt").unwrap(), dir.join("a.txt"));
+    assert_eq!(
+        object_path(dir, "images/2023/logo.png").unwrap(),
+        dir.join("images").join("2023").join("logo.png")
+    );
+    for key in [
+        "",
+        ".",
+        "..",
+        "../a.txt",
+        "images/../../a.txt",
+        "images/./a.txt",
+        "/etc/passwd",
+        "images//a.txt",
+        "images/",
+        "..\\a.txt",
+        "images\\a.txt",
+    ] {
+        assert!(object_path(dir, key).is_err(), "{key:?}");
+    }
+}
+
+#[tokio::test]
+async fn test_download_to_file() {
+    use shadow_drive_mock::MockShadowDrive;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let storage_account = Pubkey::new_unique();
+    let client = test_client(&drive, &storage_account);
+    drive
+        .put_object(storage_account, "a/b.txt", b"data")
+        .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("b.txt");
+    let partial = dir.path().join("b.txt.part");
+    let hash = Sha256::digest(b"data").into();
+
+    let options = GetObjectOptions::default().sha256(hash);
+    let len = download_to_file(
+        &client,
+        &storage_account,
+        "a/b.txt",
+        options,
+        None,
+        &path,
+    )
+    .await
+    .unwrap();
+    assert_eq!(len, 4);
+    assert_eq!(fs::read(&path).unwrap(), b"data");
+    assert!(!partial.exists());
+
+    // Failed downloads leave the previous file in place and clean up after
+    // themselves
+    fs::write(&path, "old").unwrap();
+    let options = GetObjectOptions::default().sha256([0; 32]);
+    let result = download_to_file(
+        &client,
+        &storage_account,
+        "a/b.txt",
+        options,
+        None,
+        &path,
+    )
+    .await;
+    assert!(result.is_err());
+    assert_eq!(fs::read(&path).unwrap(), b"old");
+    assert!(!partial.exists());
+
+    let result = download_to_file(
+        &client,
+        &storage_account,
+        "a/missing.txt",
+        GetObjectOptions::default(),
+        None,
+        &path,
+    )
+    .await;
+    assert!(result.is_err());
+    assert_eq!(fs::read(&path).unwrap(), b"old");
+    assert!(!partial.exists());
+}
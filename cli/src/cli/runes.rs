@@ -0,0 +1,241 @@
+use std::{fmt, fs, path::PathBuf, sync::Arc};
+
+use anyhow::anyhow;
+use futures::{future, stream, StreamExt};
+use runes::{Rune, Runes};
+use serde::Serialize;
+use serde_json::json;
+use shadow_drive_cli::{
+    auth::RpcAuth, output::OutputFormat, walk_directory, FileFilter,
+};
+use shadow_drive_sdk::{cluster::Cluster, GetObjectOptions, Pubkey};
+use solana_sdk::signature::Signer;
+
+use super::{
+    process::{load_encryption_key, shadow_client_factory},
+    RunesCommand,
+};
+
+impl RunesCommand {
+    pub async fn process<T: Signer>(
+        self,
+        signer: T,
+        rpc_url: &str,
+        cluster: &Cluster,
+        auth: Option<Arc<RpcAuth>>,
+        output: OutputFormat,
+    ) -> anyhow::Result<()> {
+        match self {
+            RunesCommand::Show { file } => {
+                let runes = Runes::load(&file)
+                    .map_err(|e| anyhow!("failed to load runes: {e}"))?;
+                let info = RunesInfo::new(&runes);
+                output.print_or_text(&info, &info)?;
+            }
+            RunesCommand::Verify { file, concurrency } => {
+                let runes = Runes::load(&file)
+                    .map_err(|e| anyhow!("failed to load runes: {e}"))?;
+                let storage_account =
+                    Pubkey::new_from_array(runes.storage_account);
+                let client =
+                    shadow_client_factory(signer, rpc_url, cluster, auth)
+                        .await?;
+                eprintln!(
+                    "Verify {} runes against Storage Account {}",
+                    runes.runes.len(),
+                    storage_account
+                );
+
+                let failed: Vec<_> = stream::iter(&runes.runes)
+                    .map(|rune| {
+                        let client = &client;
+                        let storage_account = &storage_account;
+                        async move {
+                            let options =
+                                GetObjectOptions::default().sha256(rune.hash);
+                            let result = client
+                                .get_object_with_options(
+                                    storage_account,
+                                    &rune.name,
+                                    options,
+                                )
+                                .await
+                                .map_err(|e| e.to_string())
+                                .and_then(|data| {
+                                    if data.len() == rune.len as usize {
+                                        Ok(())
+                                    } else {
+                                        Err(format!(
+                                            "expected {} bytes but got {}",
+                                            rune.len,
+                                            data.len()
+                                        ))
+                                    }
+                                });
+                            match &result {
+                                Ok(()) => match rune.key_id {
+                                    Some(key_id) => eprintln!(
+                                        "Verified {}, encrypted with key {}",
+                                        rune.name,
+                                        hex(key_id)
+                                    ),
+                                    None => eprintln!("Verified {}", rune.name),
+                                },
+                                Err(e) => eprintln!(
+                                    "Failed to verify {}: {}",
+                                    rune.name, e
+                                ),
+                            }
+                            result.err().map(|error| {
+                                json!({
+                                    "name": rune.name,
+                                    "key_id": rune.key_id.map(hex),
+                                    "error": error,
+                                })
+                            })
+                        }
+                    })
+                    .buffer_unordered(concurrency.max(1))
+                    .filter_map(future::ready)
+                    .collect()
+                    .await;
+
+                output.print(&json!({
+                    "storage_account": storage_account.to_string(),
+                    "verified": runes.runes.len() - failed.len(),
+                    "failed": failed,
+                }))?;
+                if !failed.is_empty() {
+                    return Err(anyhow!(
+                        "{} of {} runes do not match Shadow Drive",
+                        failed.len(),
+                        runes.runes.len()
+                    ));
+                }
+            }
+            RunesCommand::Build {
+                directory,
+                storage_account,
+                out,
+                include,
+                exclude,
+                encryption_key,
+            } => {
+                let key = encryption_key
+                    .map(|arg| load_encryption_key(&arg, &signer))
+                    .transpose()?;
+                let filter = FileFilter::new(&include, &exclude)?;
+                let mut runes = Runes {
+                    storage_account: storage_account.to_bytes(),
+                    runes: vec![],
+                };
+                for (path, name) in walk_directory(&directory, &filter)? {
+                    let mut data = fs::read(&path)
+                        .map_err(|e| anyhow!("failed to read {path:?}: {e}"))?;
+                    if let Some(key) = &key {
+                        data = key.encrypt(&name, &data)?;
+                    }
+                    let mut rune = Rune::from_data(name, &data)?;
+                    if let Some(key) = &key {
+                        rune = rune.with_key_id(key.id());
+                    }
+                    runes.runes.push(rune);
+                }
+
+                let target = match out {
+                    Some(out) => out,
+                    None => directory
+                        .canonicalize()?
+                        .file_name()
+                        .map(PathBuf::from)
+                        .ok_or_else(|| {
+                            anyhow!("{directory:?} has no name, pass -o")
+                        })?,
+                }
+                .with_extension("runes");
+                let info = RunesInfo::new(&runes);
+                runes
+                    .save(target.clone())
+                    .map_err(|e| anyhow!("failed to save runes: {e}"))?;
+                eprintln!(
+                    "Wrote {} runes to {}",
+                    info.runes.len(),
+                    target.display()
+                );
+                output.print_or_text(&info, &info)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A runes file as the CLI prints it.
+#[derive(Serialize)]
+struct RunesInfo {
+    storage_account: String,
+    runes: Vec<RuneInfo>,
+}
+
+#[derive(Serialize)]
+struct RuneInfo {
+    name: String,
+    len: u16,
+    /// Hex encoded SHA-256 of the file
+    hash: String,
+    /// Hex encoded id of the key the file is encrypted with
+    key_id: Option<String>,
+}
+
+impl RunesInfo {
+    fn new(runes: &Runes) -> Self {
+        Self {
+            storage_account: Pubkey::new_from_array(runes.storage_account)
+                .to_string(),
+            runes: runes
+                .runes
+                .iter()
+                .map(|rune| RuneInfo {
+                    name: rune.name.clone(),
+                    len: rune.len,
+                    hash: hex(rune.hash),
+                    key_id: rune.key_id.map(hex),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for RunesInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "storage account: {}", self.storage_account)?;
+        let width = self
+            .runes
+            .iter()
+            .map(|rune| rune.name.chars().count())
+            .max()
+            .unwrap_or_default();
+        for rune in &self.runes {
+            write!(
+                f,
+                "{:width$}  {:>5}  {}",
+                rune.name,
+                rune.len,
+                rune.hash,
+                width = width
+            )?;
+            match &rune.key_id {
+                Some(key_id) => writeln!(f, "  key {}", key_id)?,
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn hex(bytes: impl AsRef<[u8]>) -> String {
+    bytes
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,160 @@
+//! Resolving `--cluster` to the endpoints and addresses of a Shadow Drive
+//! deployment, from the built-in profiles and the user's cluster config file.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use serde_json::Value;
+use shadow_drive_sdk::cluster::Cluster;
+
+/// Names of the profiles that exist without a config file.
+pub const BUILTIN_CLUSTERS: [&str; 3] = ["mainnet", "devnet", "localnet"];
+
+/// `shadow-drive/clusters.yaml` in the user's config directory.
+pub fn default_cluster_config_path() -> Option<PathBuf> {
+    dirs_next::config_dir().map(|dir| dir.join("shadow-drive").join("clusters.yaml"))
+}
+
+/// Loads the profile called `name`. A profile in the config file at `path`,
+/// or the default path if `None`, overrides the fields of the built-in
+/// profile with the same name, or of `mainnet` for other names, e.g.
+///
+/// ```yaml
+/// custom:
+///   rpc_url: http://validator.internal:8899
+///   storage_endpoint: http://storage.internal
+///   portal_program_id: G6xPudzNNM8CwfLHC9ByzrF67LcwyiRe4t9vHg34eqpR
+/// ```
+pub fn load_cluster(name: &str, path: Option<&Path>) -> anyhow::Result<Cluster> {
+    // Only a config file that was asked for has to exist
+    let path = match path {
+        Some(path) if !path.exists() => return Err(anyhow!("{path:?} does not exist")),
+        Some(path) => Some(path.to_path_buf()),
+        None => default_cluster_config_path().filter(|path| path.exists()),
+    };
+    let profiles = match &path {
+        Some(path) => {
+            let text =
+                fs::read_to_string(path).map_err(|e| anyhow!("failed to read {path:?}: {e}"))?;
+            serde_yaml::from_str::<Option<BTreeMap<String, Value>>>(&text)
+                .map_err(|e| anyhow!("invalid cluster config {path:?}: {e}"))?
+                .unwrap_or_default()
+        }
+        None => BTreeMap::new(),
+    };
+    let source = path
+        .as_deref()
+        .unwrap_or_else(|| Path::new("the built-in clusters"));
+
+    let builtin = Cluster::builtin(name);
+    let overrides = match profiles.get(name) {
+        Some(Value::Object(overrides)) => overrides.clone(),
+        Some(Value::Null) => Default::default(),
+        Some(_) => return Err(anyhow!("cluster {name:?} in {source:?} is not a mapping")),
+        None if builtin.is_some() => Default::default(),
+        None => {
+            let known: Vec<&str> = BUILTIN_CLUSTERS
+                .into_iter()
+                .chain(profiles.keys().map(String::as_str))
+                .collect();
+            return Err(anyhow!(
+                "unknown cluster {name:?}, expected one of {}",
+                known.join(", ")
+            ));
+        }
+    };
+
+    let mut cluster = serde_json::to_value(builtin.unwrap_or_else(Cluster::mainnet))?;
+    for (field, value) in overrides {
+        cluster[field] = value;
+    }
+    serde_json::from_value(cluster)
+        .map_err(|e| anyhow!("invalid cluster {name:?} in {source:?}: {e}"))
+}
+
+#[test]
+fn test_load_cluster() {
+    use solana_sdk::pubkey::Pubkey;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clusters.yaml");
+    let portal = Pubkey::new_unique();
+    fs::write(
+        &path,
+        format!(
+            "devnet:\n  rpc_url: http://devnet.internal\n\
+             custom:\n  storage_endpoint: http://storage.internal\n  portal_program_id: {portal}\n\
+             empty:\n\
+             broken: http://validator.internal\n\
+             typo:\n  rpc: http://validator.internal\n\
+             invalid:\n  admin: not-a-pubkey\n"
+        ),
+    )
+    .unwrap();
+    let load = |name| load_cluster(name, Some(&path));
+
+    // Overrides replace single fields of the built-in profile of the same name
+    assert_eq!(
+        load("devnet").unwrap(),
+        Cluster {
+            rpc_url: "http://devnet.internal".to_string(),
+            ..Cluster::devnet()
+        }
+    );
+    assert_eq!(load("localnet").unwrap(), Cluster::localnet());
+
+    // Or of mainnet for other names
+    assert_eq!(
+        load("custom").unwrap(),
+        Cluster {
+            storage_endpoint: "http://storage.internal".to_string(),
+            portal_program_id: portal,
+            ..Cluster::mainnet()
+        }
+    );
+    assert_eq!(load("empty").unwrap(), Cluster::mainnet());
+
+    let error = |name| load(name).unwrap_err().to_string();
+    assert_eq!(
+        error("staging"),
+        "unknown cluster \"staging\", expected one of mainnet, devnet, localnet, \
+         broken, custom, devnet, empty, invalid, typo"
+    );
+    assert_eq!(
+        error("broken"),
+        format!("cluster \"broken\" in {path:?} is not a mapping")
+    );
+    let typo = error("typo");
+    assert!(
+        typo.starts_with(&format!(
+            "invalid cluster \"typo\" in {path:?}: unknown field `rpc`"
+        )),
+        "{typo}"
+    );
+    let invalid = error("invalid");
+    assert!(
+        invalid.starts_with(&format!("invalid cluster \"invalid\" in {path:?}")),
+        "{invalid}"
+    );
+
+    // Only the built-in profiles exist without a config file
+    let missing = dir.path().join("missing.yaml");
+    assert_eq!(
+        load_cluster("devnet", Some(&missing))
+            .unwrap_err()
+            .to_string(),
+        format!("{missing:?} does not exist")
+    );
+    fs::write(&path, "").unwrap();
+    assert_eq!(load("devnet").unwrap(), Cluster::devnet());
+    assert_eq!(
+        error("custom"),
+        "unknown cluster \"custom\", expected one of mainnet, devnet, localnet"
+    );
+    fs::write(&path, "- devnet\n").unwrap();
+    assert!(error("devnet").starts_with(&format!("invalid cluster config {path:?}")));
+}
@@ -1,15 +1,30 @@
-use std::{io::stdin, str::FromStr};
+pub mod auth;
+pub mod cluster;
+pub mod output;
+
+use std::{
+    fmt, fs,
+    io::stdin,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use anyhow::anyhow;
 use byte_unit::Byte;
 use chrono::DateTime;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use reqwest::{header::HeaderMap, Response};
+use runes::{Rune, Runes};
+use serde::Serialize;
+use serde_json::Value;
 use shadow_drive_sdk::{
-    constants::SHDW_DRIVE_OBJECT_PREFIX,
-    error::{Error, FileError},
-    models::ShadowDriveResult,
+    encryption::encryption_key_id,
+    estimate::Quote,
+    models::{storage_acct::StorageAcct, ShadowDriveResult},
+    ShadowDriveClient,
 };
 use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
     signature::{Signature, Signer, SignerError},
 };
@@ -22,6 +37,141 @@ pub fn pubkey_arg(pubkey: &str) -> anyhow::Result<Pubkey> {
     Pubkey::from_str(pubkey).map_err(|e| anyhow!("invalid pubkey: {}", e.to_string()))
 }
 
+/// Clap value parser for a summon callback given as JSON, e.g.
+/// `{"program_id": "<pubkey>", "accounts": [{"pubkey": "<pubkey>", "is_signer": false,
+/// "is_writable": true}], "data": [0, 1, 2]}`.
+pub fn callback_arg(json: &str) -> anyhow::Result<Instruction> {
+    let callback: Value =
+        serde_json::from_str(json).map_err(|e| anyhow!("invalid callback json: {}", e))?;
+    let pubkey = |value: &Value, field: &str| {
+        value
+            .get(field)
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("callback is missing {}", field))
+            .and_then(pubkey_arg)
+    };
+    let flag = |value: &Value, field: &str| value.get(field).and_then(Value::as_bool) == Some(true);
+
+    let accounts = callback
+        .get("accounts")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|account| {
+            Ok(AccountMeta {
+                pubkey: pubkey(account, "pubkey")?,
+                is_signer: flag(account, "is_signer"),
+                is_writable: flag(account, "is_writable"),
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let data = callback
+        .get("data")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|byte| {
+            byte.as_u64()
+                .and_then(|byte| u8::try_from(byte).ok())
+                .ok_or_else(|| anyhow!("callback data must be an array of bytes"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(Instruction {
+        program_id: pubkey(&callback, "program_id")?,
+        accounts,
+        data,
+    })
+}
+
+/// Include and exclude globs over `/` separated relative paths. `*` does not match
+/// `/`, so use `**/*.png` to match at any depth.
+pub struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl FileFilter {
+    /// Matches every path when `include` is empty.
+    pub fn new(include: &[String], exclude: &[String]) -> anyhow::Result<Self> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(glob_set(include)?)
+        };
+        Ok(Self {
+            include,
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.include
+            .as_ref()
+            .map_or(true, |include| include.is_match(path))
+            && !self.exclude.is_match(path)
+    }
+}
+
+fn glob_set(patterns: &[String]) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(
+            GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| anyhow!("invalid glob {}: {}", pattern, e))?,
+        );
+    }
+    builder.build().map_err(|e| anyhow!("invalid globs: {}", e))
+}
+
+/// Lists the files under `directory` that `filter` matches, recursively, with their
+/// paths relative to `directory` using `/` separators, sorted by relative path. These
+/// are the names files are stored under, e.g. `images/logo.png`.
+///
+/// Symbolic links to files are listed, but links to directories are not followed, so
+/// links can't make the walk loop.
+pub fn walk_directory(
+    directory: &Path,
+    filter: &FileFilter,
+) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    fn walk(
+        dir: &Path,
+        prefix: &str,
+        filter: &FileFilter,
+        files: &mut Vec<(PathBuf, String)>,
+    ) -> anyhow::Result<()> {
+        let entries =
+            fs::read_dir(dir).map_err(|e| anyhow!("failed to read {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let entry = entry?;
+            let name = entry
+                .file_name()
+                .into_string()
+                .map_err(|name| anyhow!("{:?} is not valid UTF-8", name))?;
+            let relative = format!("{}{}", prefix, name);
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                walk(&path, &format!("{}/", relative), filter, files)?;
+            } else if file_type.is_symlink() && path.is_dir() {
+                continue;
+            } else if filter.matches(&relative) {
+                files.push((path, relative));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = vec![];
+    walk(directory, "", filter, &mut files)?;
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(files)
+}
+
 /// To get around using a [Box<dyn Signer>] with [ShadowDriveClient].
 pub struct WrappedSigner(Box<dyn Signer>);
 
@@ -45,51 +195,16 @@ impl Signer for WrappedSigner {
     }
 }
 
-/// Further diagnostic printing wherever possible.
+/// Converts the error of a failed Shadow Drive request to an [anyhow::Error],
+/// keeping it so that [output::ExitCode::of] can classify it.
 pub fn process_shadow_api_response<T>(response: ShadowDriveResult<T>) -> anyhow::Result<T> {
-    match response {
-        Ok(response) => Ok(response),
-        Err(err) => match err {
-            Error::ShadowDriveServerError { status, message } => {
-                let err = format!(
-                    "Shadow Drive Server Error {}: {:#?}",
-                    status,
-                    message.to_string()
-                );
-                println!("{}", err);
-                Err(anyhow!("{}", err))
-            }
-            Error::FileSystemError(err) => {
-                let err = format!("Filesystem Error: {:#?}", err.to_string());
-                println!("{}", err);
-                Err(anyhow!("{}", err))
-            }
-            Error::FileValidationError(errs) => {
-                let mut err_vec = vec![];
-                for err in errs {
-                    let FileError { file, error } = err;
-                    let err = format!("File Validation Error for {}: {}", file, error);
-                    err_vec.push(err);
-                }
-                println!("{:#?}", err_vec);
-                Err(anyhow!("{:#?}", err_vec))
-            }
-            e => {
-                println!("{:#?}", e);
-                Err(anyhow!("{:#?}", e))
-            }
-        },
-    }
+    response.map_err(anyhow::Error::from)
 }
 
-/// Generate a Shadow Drive file URL from storage account and filename.
-pub fn storage_object_url(storage_account: &Pubkey, file: &str) -> String {
-    format!(
-        "{}/{}/{}",
-        SHDW_DRIVE_OBJECT_PREFIX,
-        storage_account.to_string(),
-        file
-    )
+/// Generate a Shadow Drive file URL from the cluster's object prefix,
+/// storage account and filename.
+pub fn storage_object_url(object_prefix: &str, storage_account: &Pubkey, file: &str) -> String {
+    format!("{}/{}/{}", object_prefix, storage_account.to_string(), file)
 }
 
 /// Returns false when "Content-Type" header is not "text/plain".
@@ -112,7 +227,34 @@ pub async fn get_text(url: &String) -> anyhow::Result<Response> {
     Ok(http_client.get(url).send().await?)
 }
 
-#[derive(Debug)]
+/// Regenerate the [Runes] for an existing storage account by downloading
+/// every object in it and hashing its contents. Runes of encrypted objects
+/// record the key id from their header.
+pub async fn runes_from_bucket<T: Signer>(
+    client: &ShadowDriveClient<T>,
+    storage_account: &Pubkey,
+) -> anyhow::Result<Runes> {
+    let filenames = process_shadow_api_response(client.list_objects(storage_account).await)?;
+
+    let mut runes = Runes {
+        storage_account: storage_account.to_bytes(),
+        runes: Vec::with_capacity(filenames.len()),
+    };
+    for filename in filenames {
+        let data = client
+            .get_object(storage_account, &filename)
+            .await
+            .map_err(|e| anyhow!("failed to download {}: {}", filename, e))?;
+        let mut rune = Rune::from_data(filename, &data)?;
+        if let Some(key_id) = encryption_key_id(&data) {
+            rune = rune.with_key_id(key_id);
+        }
+        runes.upsert_rune(rune);
+    }
+    Ok(runes)
+}
+
+#[derive(Debug, Serialize)]
 pub struct FileMetadata {
     pub timestamp: i64,
     pub content_type: String,
@@ -176,8 +318,179 @@ pub fn wait_for_user_confirmation(skip: bool) -> anyhow::Result<()> {
     if skip {
         return Ok(());
     }
-    println!("Press ENTER to continue, or CTRL+C to abort");
+    eprintln!("Press ENTER to continue, or CTRL+C to abort");
     let mut proceed = String::new();
     stdin().read_line(&mut proceed)?;
     Ok(())
 }
+
+/// What an operation will cost and change, shown by [confirm_with_preview]
+/// before the user is asked to confirm it.
+#[derive(Debug, Default)]
+pub struct Preview {
+    pub quote: Option<Quote>,
+    /// SHDW balance of the signer in shades, before the operation
+    pub shdw_balance: Option<u64>,
+    /// State of the storage account before the operation, if it exists
+    pub before: Option<String>,
+    pub after: Option<String>,
+    /// How to undo the operation, `None` if it cannot be undone
+    pub undo: Option<String>,
+    /// Text the user has to type to confirm, for irreversible operations
+    pub confirm_text: Option<String>,
+}
+
+impl fmt::Display for Preview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(quote) = &self.quote {
+            writeln!(f, "Cost:\n{}", quote)?;
+        }
+        if let Some(balance) = self.shdw_balance {
+            let cost = self.quote.as_ref().map_or(0, Quote::shades);
+            match balance.checked_sub(cost) {
+                Some(left) => writeln!(
+                    f,
+                    "SHDW balance: {} ({} after)",
+                    format_shades(balance),
+                    format_shades(left)
+                )?,
+                None => writeln!(
+                    f,
+                    "SHDW balance: {} (insufficient, {} short)",
+                    format_shades(balance),
+                    format_shades(cost - balance)
+                )?,
+            }
+        }
+        if let Some(before) = &self.before {
+            writeln!(f, "Before: {}", before)?;
+        }
+        if let Some(after) = &self.after {
+            writeln!(f, "After:  {}", after)?;
+        }
+        match &self.undo {
+            Some(undo) => writeln!(f, "Reversible: yes, {}", undo),
+            None => writeln!(f, "Reversible: NO, this cannot be undone"),
+        }
+    }
+}
+
+fn format_shades(shades: u64) -> String {
+    format!(
+        "{}.{:09} SHDW",
+        shades / 1_000_000_000,
+        shades % 1_000_000_000
+    )
+}
+
+/// Describes a storage account's state for a [Preview].
+pub fn describe_storage_account(account: &StorageAcct) -> String {
+    let mut description = format!(
+        "{:?}, {} reserved, {} used, {}",
+        account.identifier(),
+        Byte::from_bytes(account.storage().into()).get_appropriate_unit(true),
+        Byte::from_bytes(account.current_usage().into()).get_appropriate_unit(true),
+        if account.is_immutable() {
+            "immutable"
+        } else {
+            "mutable"
+        }
+    );
+    if account.to_be_deleted() {
+        description.push_str(", marked for deletion");
+    }
+    description
+}
+
+/// Prints `preview` and confirms from the user that the operation should
+/// go ahead. If the preview has a [confirm_text](Preview::confirm_text), the
+/// user has to type it, otherwise pressing ENTER is enough. With `skip` the
+/// preview is still printed but nothing is asked.
+pub fn confirm_with_preview(skip: bool, preview: &Preview) -> anyhow::Result<()> {
+    eprint!("{}", preview);
+    let confirm_text = match &preview.confirm_text {
+        Some(confirm_text) if !skip => confirm_text,
+        _ => return wait_for_user_confirmation(skip),
+    };
+    eprintln!(
+        "Type {:?} to continue, or anything else to abort",
+        confirm_text
+    );
+    let mut typed = String::new();
+    stdin().read_line(&mut typed)?;
+    if typed.trim_end_matches(&['\r', '\n'][..]) != confirm_text {
+        return Err(anyhow!(
+            "confirmation did not match {:?}, aborted",
+            confirm_text
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_file_filter() {
+    let filter = |include: &[&str], exclude: &[&str]| {
+        let strings =
+            |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        FileFilter::new(&strings(include), &strings(exclude)).unwrap()
+    };
+
+    // Everything by default
+    let all = filter(&[], &[]);
+    assert!(all.matches("a.png"));
+    assert!(all.matches("images/a.png"));
+
+    // `*` stops at `/`, `**` does not
+    let top = filter(&["*.png"], &[]);
+    assert!(top.matches("a.png"));
+    assert!(!top.matches("images/a.png"));
+    assert!(!top.matches("a.jpg"));
+    let any = filter(&["**/*.png"], &[]);
+    assert!(any.matches("a.png"));
+    assert!(any.matches("images/icons/a.png"));
+
+    // Excludes win over includes
+    let filter = filter(&["**/*.png"], &["drafts/**", "**/*.tmp.png"]);
+    assert!(filter.matches("images/a.png"));
+    assert!(!filter.matches("drafts/a.png"));
+    assert!(!filter.matches("images/a.tmp.png"));
+
+    assert!(FileFilter::new(&["[".to_string()], &[]).is_err());
+}
+
+#[test]
+fn test_walk_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    for path in ["b.txt", "a/c.png", "a/d/e.png"] {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"data").unwrap();
+    }
+
+    let names = |filter: &FileFilter| -> Vec<String> {
+        walk_directory(dir.path(), filter)
+            .unwrap()
+            .into_iter()
+            .map(|(path, name)| {
+                assert_eq!(path, dir.path().join(&name));
+                name
+            })
+            .collect()
+    };
+    let all = FileFilter::new(&[], &[]).unwrap();
+    assert_eq!(names(&all), ["a/c.png", "a/d/e.png", "b.txt"]);
+    let png = FileFilter::new(&["**/*.png".to_string()], &["a/d/**".to_string()]).unwrap();
+    assert_eq!(names(&png), ["a/c.png"]);
+
+    // Links to files are listed, links to directories are not followed
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+
+        symlink(dir.path().join("b.txt"), dir.path().join("a/link.txt")).unwrap();
+        symlink(dir.path(), dir.path().join("a/d/loop")).unwrap();
+        assert_eq!(names(&all), ["a/c.png", "a/d/e.png", "a/link.txt", "b.txt"]);
+    }
+
+    assert!(walk_directory(&dir.path().join("missing"), &all).is_err());
+}
@@ -1,10 +1,13 @@
 mod cli;
 
+use std::sync::Arc;
+
 use anyhow::anyhow;
 use clap::{IntoApp, Parser};
-use cli::Opts;
-use shadow_drive_cli::WrappedSigner;
-// use shadow_rpc_auth::{authenticate, parse_account_id_from_url};
+use cli::{Command, Opts};
+use shadow_drive_cli::{
+    auth::RpcAuth, cluster::load_cluster, output::ExitCode, WrappedSigner,
+};
 use solana_clap_v3_utils::keypair::signer_from_path;
 
 pub const GENESYSGO_AUTH_KEYWORD: &str = "genesysgo";
@@ -16,7 +19,14 @@ See https://docs.solana.com/cli/install-solana-cli-tools for installation detail
 ";
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(ExitCode::of(&e).code());
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
     // CLI Parse
     let opts = Opts::parse();
 
@@ -44,19 +54,48 @@ async fn main() -> anyhow::Result<()> {
             .map_err(|e| anyhow!("Could not resolve signer: {:?}", e))?;
     let signer = WrappedSigner::new(signer);
 
-    // Resolve the RPC URL from either a command-line arg or the Solana CLI config file.
-    let url = opts.cfg_override.url.unwrap_or(config.json_rpc_url);
+    // Resolve the cluster profile, which defaults to mainnet addresses and
+    // the portal addresses compiled into chain-drive
+    let cluster = opts
+        .cfg_override
+        .cluster
+        .as_deref()
+        .map(|name| {
+            load_cluster(name, opts.cfg_override.cluster_config.as_deref())
+        })
+        .transpose()?;
+
+    // Resolve the RPC URL from either a command-line arg, the cluster profile
+    // or the Solana CLI config file.
+    let url = opts
+        .cfg_override
+        .url
+        .or_else(|| cluster.as_ref().map(|cluster| cluster.rpc_url.clone()))
+        .unwrap_or(config.json_rpc_url);
+    let cluster = cluster.unwrap_or_default();
 
-    // Possibly perform a sign-in operation
-    let mut auth: Option<String> = opts.cfg_override.auth.clone();
-    // if opts.cfg_override.auth == Some(GENESYSGO_AUTH_KEYWORD.to_string()) {
-    //     let account_id = parse_account_id_from_url(url.to_string())?;
-    //     let token = authenticate(&signer, &account_id).await?;
-    //     auth = Some(token)
-    // };
+    // Either sign in to GenesysGo for tokens or send the given token as is
+    let auth = match opts.cfg_override.auth.as_deref() {
+        Some(GENESYSGO_AUTH_KEYWORD) => {
+            Some(RpcAuth::genesysgo(&url, &signer)?)
+        }
+        None if matches!(opts.command, Command::ShadowRpcAuth) => {
+            Some(RpcAuth::genesysgo(&url, &signer)?)
+        }
+        Some(token) => Some(RpcAuth::with_token(&url, token.to_string())),
+        None => None,
+    }
+    .map(Arc::new);
 
     opts.command
-        .process(signer, &url, opts.cfg_override.skip_confirm, auth)
+        .process(
+            signer,
+            &url,
+            &cluster,
+            opts.cfg_override.skip_confirm,
+            auth,
+            opts.cfg_override.output,
+        )
         .await?;
     Ok(())
 }
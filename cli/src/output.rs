@@ -0,0 +1,388 @@
+//! Printing command results for people and scripts, and the exit codes a
+//! failed command reports.
+
+use std::fmt::{self, Write};
+
+use clap::ValueEnum;
+use reqwest::StatusCode;
+use serde::Serialize;
+use serde_json::Value;
+use shadow_drive_sdk::error::Error as ShadowDriveError;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_sdk::signature::SignerError;
+
+/// Format of the results printed to stdout. Progress messages and
+/// confirmation prompts always go to stderr.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    Table,
+}
+
+impl OutputFormat {
+    /// Prints `value` to stdout.
+    pub fn print<T: Serialize + ?Sized>(self, value: &T) -> anyhow::Result<()> {
+        print!("{}", self.render(value)?);
+        Ok(())
+    }
+
+    fn render<T: Serialize + ?Sized>(self, value: &T) -> anyhow::Result<String> {
+        let value = serde_json::to_value(value)?;
+        Ok(match self {
+            OutputFormat::Json => format!("{}\n", serde_json::to_string_pretty(&value)?),
+            OutputFormat::Yaml => serde_yaml::to_string(&value)?,
+            OutputFormat::Table => table(&value),
+        })
+    }
+
+    /// Prints `value`, or `text` instead in table mode for values that read
+    /// better as prose, such as cost quotes.
+    pub fn print_or_text<T: Serialize + ?Sized>(
+        self,
+        value: &T,
+        text: impl fmt::Display,
+    ) -> anyhow::Result<()> {
+        match self {
+            OutputFormat::Table => {
+                println!("{}", text);
+                Ok(())
+            }
+            _ => self.print(value),
+        }
+    }
+}
+
+/// Renders a list of objects as columns, an object as one row per field and
+/// anything else one value per line. Nested values are rendered as JSON.
+fn table(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Array(rows) if !rows.is_empty() && rows.iter().all(Value::is_object) => {
+            let mut columns: Vec<&str> = vec![];
+            for row in rows.iter().filter_map(Value::as_object) {
+                for key in row.keys() {
+                    if !columns.contains(&key.as_str()) {
+                        columns.push(key);
+                    }
+                }
+            }
+            let cells: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| {
+                    columns
+                        .iter()
+                        .map(|column| row.get(*column).map(cell).unwrap_or_default())
+                        .collect()
+                })
+                .collect();
+            let widths: Vec<usize> = columns
+                .iter()
+                .enumerate()
+                .map(|(i, column)| {
+                    cells
+                        .iter()
+                        .map(|row| row[i].chars().count())
+                        .chain([column.len()])
+                        .max()
+                        .unwrap_or_default()
+                })
+                .collect();
+            let header: Vec<String> = columns.iter().map(|column| column.to_string()).collect();
+            for row in [header].iter().chain(&cells) {
+                write_row(&mut out, row, &widths);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                let _ = writeln!(out, "{}", cell(item));
+            }
+        }
+        Value::Object(fields) => {
+            let width = fields.keys().map(|key| key.len()).max().unwrap_or_default();
+            for (key, value) in fields {
+                let _ = writeln!(out, "{:width$}  {}", key, cell(value), width = width);
+            }
+        }
+        Value::Null => {}
+        value => {
+            let _ = writeln!(out, "{}", cell(value));
+        }
+    }
+    out
+}
+
+fn write_row(out: &mut String, row: &[String], widths: &[usize]) {
+    let last = row.len().saturating_sub(1);
+    for (i, (cell, width)) in row.iter().zip(widths).enumerate() {
+        if i == last {
+            out.push_str(cell);
+        } else {
+            let _ = write!(out, "{:width$}  ", cell, width = width);
+        }
+    }
+    out.push('\n');
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Exit codes by the class of error a command failed with. These are stable
+/// so that scripts can rely on them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Any error not covered below
+    Failure = 1,
+    /// Invalid arguments, reported by clap before any command runs
+    Usage = 2,
+    /// Shadow Drive rejected the request, or would have, e.g. a file was too large
+    ShadowDrive = 3,
+    /// A Solana RPC request, transaction or program failed
+    Rpc = 4,
+    /// A server could not be reached or did not respond in time
+    Network = 5,
+    /// Reading or writing local files failed
+    Io = 6,
+    /// The endpoint rejected our credentials, or the signer failed to sign
+    Auth = 7,
+}
+
+impl ExitCode {
+    /// Classifies `error` by the first cause in its chain that is recognized.
+    pub fn of(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(error) = cause.downcast_ref::<ShadowDriveError>() {
+                return Self::of_shadow_drive(error);
+            }
+            if let Some(error) = cause.downcast_ref::<ClientError>() {
+                return Self::of_rpc(error);
+            }
+            if let Some(error) = cause.downcast_ref::<reqwest::Error>() {
+                return Self::of_http(error);
+            }
+            if cause.is::<std::io::Error>() {
+                return ExitCode::Io;
+            }
+            if cause.is::<SignerError>() {
+                return ExitCode::Auth;
+            }
+        }
+        ExitCode::Failure
+    }
+
+    fn of_shadow_drive(error: &ShadowDriveError) -> Self {
+        match error {
+            ShadowDriveError::InsufficientStorage { .. }
+            | ShadowDriveError::FileAlreadyExists { .. }
+            | ShadowDriveError::RateLimited { .. }
+            | ShadowDriveError::ShadowDriveServerError { .. }
+            | ShadowDriveError::FileTooLarge(_)
+            | ShadowDriveError::FileValidationError(_)
+            | ShadowDriveError::NotFileOwner
+            | ShadowDriveError::StorageAccountIsNotImmutable
+            | ShadowDriveError::InvalidRange { .. }
+            | ShadowDriveError::ObjectHashMismatch { .. }
+            | ShadowDriveError::UploadManifestMismatch { .. } => ExitCode::ShadowDrive,
+            ShadowDriveError::SolanaRpcError(error) => Self::of_rpc(error),
+            ShadowDriveError::PortalError(_)
+            | ShadowDriveError::SummonClosed(_)
+            | ShadowDriveError::UploadTimeout(_)
+            | ShadowDriveError::NotPortalAdmin(_)
+            | ShadowDriveError::PubsubError(_)
+            | ShadowDriveError::TransactionSerializationFailed(_)
+            | ShadowDriveError::AccountDeserializeError(_)
+            | ShadowDriveError::AccountOwnerMismatch { .. }
+            | ShadowDriveError::AnchorError(_)
+            | ShadowDriveError::UserInfoNotCreated => ExitCode::Rpc,
+            ShadowDriveError::ReqwestError(error) => Self::of_http(error),
+            ShadowDriveError::FileSystemError(_) => ExitCode::Io,
+            ShadowDriveError::SignerError(_) | ShadowDriveError::AuthFailed(_) => ExitCode::Auth,
+            _ => ExitCode::Failure,
+        }
+    }
+
+    fn of_rpc(error: &ClientError) -> Self {
+        match error.kind() {
+            ClientErrorKind::Reqwest(error) => match Self::of_http(error) {
+                ExitCode::ShadowDrive | ExitCode::Failure => ExitCode::Rpc,
+                code => code,
+            },
+            ClientErrorKind::Io(_) => ExitCode::Network,
+            ClientErrorKind::SigningError(_) => ExitCode::Auth,
+            _ => ExitCode::Rpc,
+        }
+    }
+
+    fn of_http(error: &reqwest::Error) -> Self {
+        match error.status() {
+            Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => ExitCode::Auth,
+            Some(_) => ExitCode::ShadowDrive,
+            None if error.is_connect() || error.is_timeout() => ExitCode::Network,
+            None => ExitCode::Failure,
+        }
+    }
+
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+#[test]
+fn test_render() {
+    use serde_json::json;
+
+    let rows = json!([
+        {"name": "a.txt", "size": 5},
+        {"name": "images/logo.png", "size": null, "tags": ["x"]},
+    ]);
+    for (format, expected) in [
+        (
+            OutputFormat::Table,
+            "name             size  tags\n\
+             a.txt            5     \n\
+             images/logo.png        [\"x\"]\n",
+        ),
+        (
+            OutputFormat::Json,
+            "[\n  {\n    \"name\": \"a.txt\",\n    \"size\": 5\n  },\n  \
+             {\n    \"name\": \"images/logo.png\",\n    \"size\": null,\n    \
+             \"tags\": [\n      \"x\"\n    ]\n  }\n]\n",
+        ),
+        (
+            OutputFormat::Yaml,
+            "- name: a.txt\n  size: 5\n- name: images/logo.png\n  size: null\n  tags:\n  - x\n",
+        ),
+    ] {
+        assert_eq!(format.render(&rows).unwrap(), expected, "{:?}", format);
+    }
+
+    // Objects get a row per field, other values a line each
+    let table = |value| OutputFormat::Table.render(&value).unwrap();
+    assert_eq!(
+        table(json!({"storage_account": "abc", "bytes": 10})),
+        "bytes            10\nstorage_account  abc\n"
+    );
+    assert_eq!(table(json!(["a", 1, null])), "a\n1\n\n");
+    assert_eq!(table(json!([])), "");
+    assert_eq!(table(json!("done")), "done\n");
+    assert_eq!(table(json!(null)), "");
+}
+
+#[tokio::test]
+async fn test_exit_code() {
+    use anyhow::anyhow;
+    use shadow_drive_mock::MockShadowDrive;
+    use solana_sdk::pubkey::Pubkey;
+
+    let of = |error: ShadowDriveError| ExitCode::of(&error.into());
+    for error in [
+        ShadowDriveError::InsufficientStorage {
+            message: String::new(),
+        },
+        ShadowDriveError::RateLimited { retry_after: None },
+        ShadowDriveError::ShadowDriveServerError {
+            status: 500,
+            message: Value::Null,
+        },
+        ShadowDriveError::NotFileOwner,
+        ShadowDriveError::ObjectHashMismatch {
+            expected: [0; 32],
+            actual: [1; 32],
+        },
+    ] {
+        assert_eq!(of(error), ExitCode::ShadowDrive);
+    }
+    for error in [
+        ShadowDriveError::UserInfoNotCreated,
+        ShadowDriveError::UploadTimeout(Pubkey::new_unique()),
+        ShadowDriveError::AccountOwnerMismatch {
+            account: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+        },
+    ] {
+        assert_eq!(of(error), ExitCode::Rpc);
+    }
+    let io = || std::io::Error::from(std::io::ErrorKind::NotFound);
+    assert_eq!(of(ShadowDriveError::FileSystemError(io())), ExitCode::Io);
+    assert_eq!(
+        of(ShadowDriveError::AuthFailed(String::new())),
+        ExitCode::Auth
+    );
+    assert_eq!(
+        of(ShadowDriveError::SignerError(SignerError::Custom(
+            String::new()
+        ))),
+        ExitCode::Auth
+    );
+    assert_eq!(of(ShadowDriveError::InvalidStorage(0)), ExitCode::Failure);
+
+    // RPC errors are classified by their cause
+    let rpc = ClientError::from;
+    assert_eq!(
+        of(ShadowDriveError::SolanaRpcError(rpc(ClientErrorKind::Io(
+            io()
+        )))),
+        ExitCode::Network
+    );
+    assert_eq!(
+        of(ShadowDriveError::SolanaRpcError(rpc(
+            ClientErrorKind::Custom(String::new())
+        ))),
+        ExitCode::Rpc
+    );
+    assert_eq!(
+        ExitCode::of(
+            &rpc(ClientErrorKind::SigningError(SignerError::Custom(
+                String::new()
+            )))
+            .into()
+        ),
+        ExitCode::Auth
+    );
+
+    // HTTP errors by status, or failure to connect
+    async fn status_error(drive: &MockShadowDrive, status: u16) -> reqwest::Error {
+        drive.fail_next("/list-objects", 1, status);
+        let url = format!("{}/list-objects", drive.endpoint());
+        reqwest::Client::new()
+            .post(url)
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap_err()
+    }
+    let drive = MockShadowDrive::start().unwrap();
+    let error = status_error(&drive, 401).await;
+    assert_eq!(ExitCode::of(&error.into()), ExitCode::Auth);
+    let error = status_error(&drive, 403).await;
+    assert_eq!(
+        ExitCode::of(&rpc(ClientErrorKind::Reqwest(error)).into()),
+        ExitCode::Auth
+    );
+    let error = status_error(&drive, 500).await;
+    assert_eq!(
+        of(ShadowDriveError::ReqwestError(error)),
+        ExitCode::ShadowDrive
+    );
+    let error = status_error(&drive, 502).await;
+    assert_eq!(
+        ExitCode::of(&rpc(ClientErrorKind::Reqwest(error)).into()),
+        ExitCode::Rpc
+    );
+    let error = reqwest::get("http://127.0.0.1:1").await.unwrap_err();
+    assert_eq!(ExitCode::of(&error.into()), ExitCode::Network);
+
+    // The first recognized cause in the chain counts
+    let error = anyhow::Error::from(io()).context("failed to read runes");
+    assert_eq!(ExitCode::of(&error), ExitCode::Io);
+    assert_eq!(
+        ExitCode::of(&anyhow!("no files to upload")),
+        ExitCode::Failure
+    );
+}
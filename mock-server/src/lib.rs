@@ -0,0 +1,637 @@
+//! An in-process mock of the Shadow Drive storage API, so that the SDK, the
+//! CLI and the portal can be tested without network access.
+//!
+//! ```ignore
+//! let drive = MockShadowDrive::start()?;
+//! drive.add_storage_account(MockStorageAccount::new(storage_account, owner));
+//!
+//! let client = ShadowDriveClientBuilder::new(keypair, rpc_url)
+//!     .storage_endpoint(drive.endpoint())
+//!     .object_prefix(drive.object_prefix())
+//!     .build();
+//! ```
+//!
+//! Objects are kept in a temporary directory that is removed when the
+//! [`MockShadowDrive`] is dropped. Uploads, edits and deletes must carry a
+//! valid signature over the same message the real server expects.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    net::{SocketAddr, TcpListener, ToSocketAddrs},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use axum::http::StatusCode;
+use tempfile::TempDir;
+use tokio::sync::oneshot;
+
+mod routes;
+mod signature;
+
+pub use signature::{delete_message, edit_message, upload_message};
+
+/// A storage account known to the mock server.
+///
+/// Accounts that are not registered are accepted by any signer, and
+/// `/storage-account-info` reports them as missing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockStorageAccount {
+    /// Base58 address of the storage account
+    pub storage_account: String,
+    /// Base58 key that must sign uploads, edits and deletes
+    pub owner: String,
+    pub identifier: String,
+    pub reserved_bytes: u64,
+    pub immutable: bool,
+}
+
+impl MockStorageAccount {
+    /// A mutable account with 1 GiB of storage.
+    pub fn new(storage_account: impl ToString, owner: impl ToString) -> Self {
+        Self {
+            storage_account: storage_account.to_string(),
+            owner: owner.to_string(),
+            identifier: String::from("mock"),
+            reserved_bytes: 1 << 30,
+            immutable: false,
+        }
+    }
+}
+
+pub(crate) struct Shared {
+    pub root: PathBuf,
+    pub addr: SocketAddr,
+    pub accounts: Mutex<HashMap<String, MockStorageAccount>>,
+    /// Signer of the first upload to each unregistered storage account
+    pub uploaders: Mutex<HashMap<String, String>>,
+    /// Number of requests received, by path
+    pub requests: Mutex<HashMap<String, usize>>,
+    /// Injected failures still to be returned, by path
+    pub faults: Mutex<HashMap<String, (usize, StatusCode)>>,
+    /// Whether `/list-objects` ignores paging, like older servers
+    pub legacy_listing: AtomicBool,
+}
+
+impl Shared {
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn object_url(&self, storage_account: &str, filename: &str) -> String {
+        format!("{}/{}/{}", self.endpoint(), storage_account, filename)
+    }
+
+    pub fn account_dir(&self, storage_account: &str) -> PathBuf {
+        self.root.join(storage_account)
+    }
+
+    pub fn account(&self, storage_account: &str) -> Option<MockStorageAccount> {
+        self.accounts.lock().unwrap().get(storage_account).cloned()
+    }
+
+    /// Counts a request to `path` and takes one of its injected failures.
+    pub fn record_request(&self, path: &str) -> Option<StatusCode> {
+        *self
+            .requests
+            .lock()
+            .unwrap()
+            .entry(path.to_string())
+            .or_default() += 1;
+
+        let mut faults = self.faults.lock().unwrap();
+        let (remaining, status) = faults.get_mut(path)?;
+        let status = *status;
+        *remaining -= 1;
+        if *remaining == 0 {
+            faults.remove(path);
+        }
+        Some(status)
+    }
+
+    /// The key allowed to modify a storage account, if it is known.
+    pub fn owner(&self, storage_account: &str) -> Option<String> {
+        self.account(storage_account)
+            .map(|account| account.owner)
+            .or_else(|| {
+                self.uploaders.lock().unwrap().get(storage_account).cloned()
+            })
+    }
+}
+
+/// A Shadow Drive storage server running on a background thread.
+///
+/// Serves `/upload`, `/edit`, `/delete-file`, `/list-objects`,
+/// `/storage-account-info` and `/get-object-data` like the storage endpoint,
+/// and `GET /<storage account>/<filename>`, with single `Range` requests, like
+/// the object CDN. Both are served from the same address, so
+/// [`endpoint`](Self::endpoint) and [`object_prefix`](Self::object_prefix)
+/// are equal.
+pub struct MockShadowDrive {
+    shared: Arc<Shared>,
+    // Kept alive until the server stops
+    _dir: TempDir,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockShadowDrive {
+    /// Binds to a free port on localhost and starts serving. Works from both
+    /// synchronous code and inside an async runtime.
+    pub fn start() -> io::Result<Self> {
+        Self::start_on("127.0.0.1:0")
+    }
+
+    /// Like [`start`](Self::start), but binds to `addr`, e.g.
+    /// `127.0.0.1:8080` to serve the `localnet` cluster profile.
+    pub fn start_on(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        let shared = Arc::new(Shared {
+            root: dir.path().to_path_buf(),
+            addr: listener.local_addr()?,
+            accounts: Mutex::default(),
+            uploaders: Mutex::default(),
+            requests: Mutex::default(),
+            faults: Mutex::default(),
+            legacy_listing: AtomicBool::new(false),
+        });
+
+        // The server is created on its own thread, since its runtime can't be
+        // entered from a thread that is already running one
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let (ready, ready_rx) = mpsc::channel();
+        let router = routes::router(Arc::clone(&shared));
+        let thread = std::thread::spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    ready.send(Err(e)).ok();
+                    return;
+                }
+            };
+            runtime.block_on(async move {
+                let server = match axum::Server::from_tcp(listener) {
+                    Ok(server) => server,
+                    Err(e) => {
+                        ready
+                            .send(Err(io::Error::new(io::ErrorKind::Other, e)))
+                            .ok();
+                        return;
+                    }
+                };
+                ready.send(Ok(())).ok();
+
+                let server =
+                    server.serve(router).with_graceful_shutdown(async {
+                        shutdown_rx.await.ok();
+                    });
+                if let Err(e) = server.await {
+                    eprintln!("mock shadow drive stopped: {e}");
+                }
+            });
+        });
+        ready_rx.recv().map_err(|_| {
+            io::Error::new(io::ErrorKind::Other, "mock server thread exited")
+        })??;
+
+        Ok(Self {
+            shared,
+            _dir: dir,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        })
+    }
+
+    /// URL to use as the storage endpoint, e.g. `http://127.0.0.1:41234`.
+    pub fn endpoint(&self) -> String {
+        self.shared.endpoint()
+    }
+
+    /// URL prefix that objects are served from.
+    pub fn object_prefix(&self) -> String {
+        self.shared.endpoint()
+    }
+
+    /// URL of an object, in the same format as the real CDN.
+    pub fn object_url(
+        &self,
+        storage_account: impl ToString,
+        filename: &str,
+    ) -> String {
+        self.shared
+            .object_url(&storage_account.to_string(), filename)
+    }
+
+    /// Directory that objects are stored in, one subdirectory per storage
+    /// account.
+    pub fn root(&self) -> &Path {
+        &self.shared.root
+    }
+
+    /// Registers a storage account, replacing any with the same address.
+    pub fn add_storage_account(&self, account: MockStorageAccount) {
+        self.shared
+            .accounts
+            .lock()
+            .unwrap()
+            .insert(account.storage_account.clone(), account);
+    }
+
+    /// Answers the next `count` requests to `path`, e.g. `/upload`, with
+    /// `status` instead of handling them, to test retries.
+    pub fn fail_next(&self, path: &str, count: usize, status: u16) {
+        let status =
+            StatusCode::from_u16(status).expect("invalid HTTP status code");
+        let mut faults = self.shared.faults.lock().unwrap();
+        if count == 0 {
+            faults.remove(path);
+        } else {
+            faults.insert(path.to_string(), (count, status));
+        }
+    }
+
+    /// Makes `/list-objects` ignore prefixes, cursors, limits and the
+    /// detailed flag, and return every key at once, like servers that predate
+    /// paged listings.
+    pub fn set_legacy_listing(&self, legacy: bool) {
+        self.shared.legacy_listing.store(legacy, Ordering::Relaxed);
+    }
+
+    /// Number of requests received for `path`, including failed ones.
+    pub fn requests(&self, path: &str) -> usize {
+        self.shared
+            .requests
+            .lock()
+            .unwrap()
+            .get(path)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Stores an object directly, without a signed upload. Names containing
+    /// `/` are stored in subdirectories.
+    pub fn put_object(
+        &self,
+        storage_account: impl ToString,
+        filename: &str,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let path = self
+            .shared
+            .account_dir(&storage_account.to_string())
+            .join(filename);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, data)
+    }
+
+    /// Reads an object directly.
+    pub fn get_object(
+        &self,
+        storage_account: impl ToString,
+        filename: &str,
+    ) -> io::Result<Vec<u8>> {
+        fs::read(
+            self.shared
+                .account_dir(&storage_account.to_string())
+                .join(filename),
+        )
+    }
+}
+
+impl Drop for MockShadowDrive {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_signer(seed: u8) -> (String, impl Fn(&str) -> String) {
+    use ed25519_dalek::{ExpandedSecretKey, PublicKey, SecretKey};
+
+    let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+    let public = PublicKey::from(&secret);
+    let sign = move |message: &str| {
+        let signature =
+            ExpandedSecretKey::from(&secret).sign(message.as_bytes(), &public);
+        bs58::encode(signature.to_bytes()).into_string()
+    };
+    (bs58::encode(public.to_bytes()).into_string(), sign)
+}
+
+#[test]
+fn test_upload_edit_and_fetch() {
+    use reqwest::blocking::{multipart::Form, multipart::Part, Client};
+    use serde_json::{json, Value};
+
+    let drive = MockShadowDrive::start().unwrap();
+    let (owner, sign) = test_signer(1);
+    let storage_account = bs58::encode([2; 32]).into_string();
+    drive
+        .add_storage_account(MockStorageAccount::new(&storage_account, &owner));
+    let client = Client::new();
+
+    // Upload
+    let upload = |message: String| {
+        let form = Form::new()
+            .part("file", Part::bytes(&b"hello"[..]).file_name("hello.txt"))
+            .text("message", message)
+            .text("signer", owner.clone())
+            .text("storage_account", storage_account.clone())
+            .text("fileNames", "hello.txt");
+        client
+            .post(format!("{}/upload", drive.endpoint()))
+            .multipart(form)
+            .send()
+            .unwrap()
+    };
+    let response = upload(sign("not the upload message"));
+    assert_eq!(response.status(), 401);
+    let response: Value =
+        upload(sign(&upload_message(&storage_account, "hello.txt")))
+            .json()
+            .unwrap();
+    let url = drive.object_url(&storage_account, "hello.txt");
+    assert_eq!(response["finalized_locations"], json!([url]));
+
+    // Fetch
+    let data = reqwest::blocking::get(&url).unwrap().bytes().unwrap();
+    assert_eq!(&data[..], b"hello");
+
+    // Edit
+    let message = edit_message(&storage_account, "hello.txt", b"bye");
+    let form = Form::new()
+        .part("file", Part::bytes(&b"bye"[..]).file_name("hello.txt"))
+        .text("message", sign(&message))
+        .text("signer", owner.clone())
+        .text("storage_account", storage_account.clone())
+        .text("url", url.clone());
+    let response = client
+        .post(format!("{}/edit", drive.endpoint()))
+        .multipart(form)
+        .send()
+        .unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        drive.get_object(&storage_account, "hello.txt").unwrap(),
+        b"bye"
+    );
+
+    // List
+    let response: Value = client
+        .post(format!("{}/list-objects", drive.endpoint()))
+        .json(&json!({ "storageAccount": storage_account }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(response["keys"], json!(["hello.txt"]));
+}
+
+#[test]
+fn test_nested_names() {
+    use reqwest::blocking::{multipart::Form, multipart::Part, Client};
+    use serde_json::{json, Value};
+
+    let drive = MockShadowDrive::start().unwrap();
+    let (owner, sign) = test_signer(1);
+    let storage_account = bs58::encode([2; 32]).into_string();
+    drive
+        .add_storage_account(MockStorageAccount::new(&storage_account, &owner));
+    drive
+        .put_object(&storage_account, "top.txt", b"top")
+        .unwrap();
+    let client = Client::new();
+    let upload = |filename: &str| -> Value {
+        let form = Form::new()
+            .part(
+                "file",
+                Part::bytes(&b"nested"[..]).file_name(filename.to_string()),
+            )
+            .text("message", sign(&upload_message(&storage_account, filename)))
+            .text("signer", owner.clone())
+            .text("storage_account", storage_account.clone())
+            .text("fileNames", filename.to_string());
+        client
+            .post(format!("{}/upload", drive.endpoint()))
+            .multipart(form)
+            .send()
+            .unwrap()
+            .json()
+            .unwrap()
+    };
+
+    // Names with `/` are stored in subdirectories and served at their URL
+    let response = upload("images/icons/logo.png");
+    let url = drive.object_url(&storage_account, "images/icons/logo.png");
+    assert_eq!(response["finalized_locations"], json!([url]));
+    assert!(drive
+        .root()
+        .join(&storage_account)
+        .join("images/icons/logo.png")
+        .is_file());
+    let data = reqwest::blocking::get(&url).unwrap().bytes().unwrap();
+    assert_eq!(&data[..], b"nested");
+    let response = reqwest::blocking::get(
+        drive.object_url(&storage_account, "images/icons"),
+    )
+    .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Listings are recursive
+    let response: Value = client
+        .post(format!("{}/list-objects", drive.endpoint()))
+        .json(&json!({ "storageAccount": storage_account }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(
+        response["keys"],
+        json!(["images/icons/logo.png", "top.txt"])
+    );
+
+    // Names can't escape the storage account or collide with directories
+    for name in [
+        "../escape.txt",
+        "a//b.txt",
+        "/abs.txt",
+        "a/./b.txt",
+        "top.txt/x",
+    ] {
+        let response = upload(name);
+        assert_eq!(response["finalized_locations"], json!([]), "{name}");
+        assert_eq!(response["upload_errors"].as_array().unwrap().len(), 1);
+    }
+    assert_eq!(
+        upload("images")["upload_errors"][0]["error"],
+        json!("file already exists")
+    );
+
+    // Deleting the last file in a directory removes the directory
+    let response = client
+        .post(format!("{}/delete-file", drive.endpoint()))
+        .json(&json!({
+            "signer": owner,
+            "message": sign(&delete_message(&storage_account, &url)),
+            "location": url,
+        }))
+        .send()
+        .unwrap();
+    assert!(response.status().is_success());
+    assert!(!drive.root().join(&storage_account).join("images").exists());
+    assert_eq!(
+        upload("images")["finalized_locations"]
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn test_rejects_other_signers() {
+    use serde_json::json;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let (owner, _) = test_signer(1);
+    let (other, sign) = test_signer(3);
+    let storage_account = bs58::encode([2; 32]).into_string();
+    drive
+        .add_storage_account(MockStorageAccount::new(&storage_account, &owner));
+    drive
+        .put_object(&storage_account, "hello.txt", b"hello")
+        .unwrap();
+
+    let location = drive.object_url(&storage_account, "hello.txt");
+    let response = reqwest::blocking::Client::new()
+        .post(format!("{}/delete-file", drive.endpoint()))
+        .json(&json!({
+            "signer": other,
+            "message": sign(&delete_message(&storage_account, &location)),
+            "location": location,
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert!(drive.get_object(&storage_account, "hello.txt").is_ok());
+}
+
+#[test]
+fn test_list_objects_pages() {
+    use serde_json::{json, Value};
+
+    let drive = MockShadowDrive::start().unwrap();
+    let storage_account = bs58::encode([2; 32]).into_string();
+    for name in ["a.txt", "b.txt", "b.json", "c.txt"] {
+        drive
+            .put_object(&storage_account, name, name.as_bytes())
+            .unwrap();
+    }
+    let list = |request: Value| -> Value {
+        reqwest::blocking::Client::new()
+            .post(format!("{}/list-objects", drive.endpoint()))
+            .json(&request)
+            .send()
+            .unwrap()
+            .json()
+            .unwrap()
+    };
+
+    let page = list(json!({
+        "storageAccount": storage_account,
+        "limit": 2,
+    }));
+    assert_eq!(page["keys"], json!(["a.txt", "b.json"]));
+    assert_eq!(page["next_cursor"], json!("b.json"));
+    assert!(page.get("objects").is_none());
+
+    let page = list(json!({
+        "storageAccount": storage_account,
+        "cursor": "b.json",
+        "limit": 2,
+    }));
+    assert_eq!(page["keys"], json!(["b.txt", "c.txt"]));
+    assert_eq!(page["next_cursor"], Value::Null);
+
+    let page = list(json!({
+        "storageAccount": storage_account,
+        "prefix": "b.",
+        "detailed": true,
+    }));
+    assert_eq!(page["keys"], json!(["b.json", "b.txt"]));
+    assert_eq!(page["objects"][1]["key"], json!("b.txt"));
+    assert_eq!(page["objects"][1]["size"], json!(5));
+    assert_eq!(
+        page["objects"][1]["sha256"],
+        json!(signature::sha256_hex(b"b.txt"))
+    );
+
+    // Older servers list every key, whatever the request
+    drive.set_legacy_listing(true);
+    let page = list(json!({
+        "storageAccount": storage_account,
+        "prefix": "b.",
+        "limit": 1,
+        "detailed": true,
+    }));
+    assert_eq!(page["keys"].as_array().unwrap().len(), 4);
+    assert!(page.get("next_cursor").is_none());
+    assert!(page.get("objects").is_none());
+}
+
+#[tokio::test]
+async fn test_start_in_runtime() {
+    let drive = MockShadowDrive::start().unwrap();
+    let storage_account = bs58::encode([2; 32]).into_string();
+    drive
+        .put_object(&storage_account, "hello.txt", b"hello")
+        .unwrap();
+
+    let data = reqwest::get(drive.object_url(&storage_account, "hello.txt"))
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(&data[..], b"hello");
+}
+
+#[test]
+fn test_fail_next() {
+    let drive = MockShadowDrive::start().unwrap();
+    let storage_account = bs58::encode([3; 32]).into_string();
+    let client = reqwest::blocking::Client::new();
+    let list = || {
+        client
+            .post(format!("{}/list-objects", drive.endpoint()))
+            .json(&serde_json::json!({ "storageAccount": storage_account }))
+            .send()
+            .unwrap()
+            .status()
+    };
+
+    drive.fail_next("/list-objects", 2, 503);
+    assert_eq!(list(), 503);
+    assert_eq!(list(), 503);
+    assert!(list().is_success());
+    assert_eq!(drive.requests("/list-objects"), 3);
+    assert_eq!(drive.requests("/upload"), 0);
+}
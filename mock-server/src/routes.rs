@@ -0,0 +1,625 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::Path,
+    sync::{atomic::Ordering, Arc},
+    time::UNIX_EPOCH,
+};
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Multipart, Path as UrlPath, State},
+    http::{
+        header::{CONTENT_RANGE, RANGE},
+        HeaderMap, Request, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, IntoMakeService},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    signature::{
+        delete_message, edit_message, sha256_hex, upload_message, verify,
+    },
+    MockStorageAccount, Shared,
+};
+
+pub(crate) fn router(shared: Arc<Shared>) -> IntoMakeService<Router> {
+    Router::new()
+        .route("/upload", post(upload))
+        .route("/edit", post(edit))
+        .route("/delete-file", post(delete_file))
+        .route("/list-objects", post(list_objects))
+        .route("/storage-account-info", post(storage_account_info))
+        .route("/get-object-data", post(get_object_data))
+        .route("/:storage_account/*filename", get(get_object))
+        .layer(DefaultBodyLimit::disable())
+        .layer(middleware::from_fn_with_state(
+            Arc::clone(&shared),
+            record_request,
+        ))
+        .with_state(shared)
+        .into_make_service()
+}
+
+/// Counts every request, and answers it with an injected failure if one is
+/// pending for its path.
+async fn record_request<B>(
+    State(shared): State<Arc<Shared>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    match shared.record_request(request.uri().path()) {
+        Some(status) => {
+            ApiError(status, String::from("injected failure")).into_response()
+        }
+        None => next.run(request).await,
+    }
+}
+
+/// An error response with a JSON body, like the real server returns.
+struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn bad_request(message: impl ToString) -> Self {
+        Self(StatusCode::BAD_REQUEST, message.to_string())
+    }
+
+    fn not_found(message: impl ToString) -> Self {
+        Self(StatusCode::NOT_FOUND, message.to_string())
+    }
+}
+
+impl From<io::Error> for ApiError {
+    fn from(e: io::Error) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// Multipart fields of an upload or edit request.
+#[derive(Default)]
+struct Form {
+    files: Vec<(String, Bytes)>,
+    fields: HashMap<String, String>,
+}
+
+impl Form {
+    async fn read(mut multipart: Multipart) -> ApiResult<Form> {
+        let mut form = Form::default();
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(ApiError::bad_request)?
+        {
+            let name = field.name().unwrap_or_default().to_string();
+            if name == "file" {
+                let filename = field
+                    .file_name()
+                    .ok_or_else(|| {
+                        ApiError::bad_request("file part has no file name")
+                    })?
+                    .to_string();
+                let data =
+                    field.bytes().await.map_err(ApiError::bad_request)?;
+                form.files.push((filename, data));
+            } else {
+                let text = field.text().await.map_err(ApiError::bad_request)?;
+                form.fields.insert(name, text);
+            }
+        }
+        Ok(form)
+    }
+
+    fn field(&self, name: &str) -> ApiResult<&str> {
+        self.fields
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| ApiError::bad_request(format!("missing {name}")))
+    }
+}
+
+fn check_signature(
+    signer: &str,
+    signature: &str,
+    message: &str,
+) -> ApiResult<()> {
+    if verify(signer, signature, message) {
+        Ok(())
+    } else {
+        Err(ApiError(
+            StatusCode::UNAUTHORIZED,
+            String::from("invalid signature"),
+        ))
+    }
+}
+
+/// Fails unless `signer` owns the storage account. Accounts without a known
+/// owner can be modified by anyone.
+fn check_owner(
+    shared: &Shared,
+    storage_account: &str,
+    signer: &str,
+) -> ApiResult<()> {
+    match shared.owner(storage_account) {
+        Some(owner) if owner != signer => Err(ApiError(
+            StatusCode::UNAUTHORIZED,
+            format!("{signer} does not own storage account {storage_account}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn check_mutable(account: Option<&MockStorageAccount>) -> ApiResult<()> {
+    match account {
+        Some(account) if account.immutable => Err(ApiError(
+            StatusCode::FORBIDDEN,
+            String::from("storage account is immutable"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects storage account names that would escape the root directory.
+fn check_storage_account(storage_account: &str) -> ApiResult<()> {
+    if !is_valid_segment(storage_account) {
+        return Err(ApiError::bad_request(format!(
+            "invalid storage account {storage_account:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Rejects names that would escape the storage account's directory. Names
+/// may contain `/`, e.g. `images/logo.png`, and are stored in subdirectories.
+fn check_filename(filename: &str) -> ApiResult<()> {
+    if !filename.split('/').all(is_valid_segment) {
+        return Err(ApiError::bad_request(format!(
+            "invalid file name {filename:?}"
+        )));
+    }
+    Ok(())
+}
+
+fn is_valid_segment(segment: &str) -> bool {
+    !segment.is_empty()
+        && segment != "."
+        && segment != ".."
+        && !segment.contains('\\')
+}
+
+/// Splits an object URL into its storage account and file name, which may
+/// contain `/`.
+fn parse_location(location: &str) -> ApiResult<(&str, &str)> {
+    let invalid =
+        || ApiError::bad_request(format!("invalid location {location}"));
+    let (_, path) = location.split_once("://").ok_or_else(invalid)?;
+    let (_, path) = path.split_once('/').ok_or_else(invalid)?;
+    let (storage_account, filename) =
+        path.split_once('/').ok_or_else(invalid)?;
+    check_storage_account(storage_account)?;
+    check_filename(filename)?;
+    Ok((storage_account, filename))
+}
+
+/// Keys of the objects under `dir`, with `/` between directories.
+fn object_keys(dir: &Path) -> io::Result<Vec<String>> {
+    fn walk(
+        dir: &Path,
+        prefix: &str,
+        keys: &mut Vec<String>,
+    ) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let key =
+                format!("{prefix}{}", entry.file_name().to_string_lossy());
+            if entry.file_type()?.is_dir() {
+                walk(&entry.path(), &format!("{key}/"), keys)?;
+            } else {
+                keys.push(key);
+            }
+        }
+        Ok(())
+    }
+
+    let mut keys = vec![];
+    match walk(dir, "", &mut keys) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        result => result.map(|()| keys),
+    }
+}
+
+/// Removes the directories of `path` inside `dir` that a delete left empty.
+fn remove_empty_parents(dir: &Path, path: &Path) {
+    for parent in path.ancestors().skip(1) {
+        if parent == dir || fs::remove_dir(parent).is_err() {
+            break;
+        }
+    }
+}
+
+/// Total size of the objects in a storage account's directory.
+fn usage(dir: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    for key in object_keys(dir)? {
+        total += fs::metadata(dir.join(key))?.len();
+    }
+    Ok(total)
+}
+
+async fn upload(
+    State(shared): State<Arc<Shared>>,
+    multipart: Multipart,
+) -> ApiResult<Json<Value>> {
+    let form = Form::read(multipart).await?;
+    let storage_account = form.field("storage_account")?;
+    check_storage_account(storage_account)?;
+    let signer = form.field("signer")?;
+    let filenames = form.field("fileNames")?;
+    check_signature(
+        signer,
+        form.field("message")?,
+        &upload_message(storage_account, filenames),
+    )?;
+
+    let uploaded = form
+        .files
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+    if uploaded != filenames {
+        return Err(ApiError::bad_request(
+            "fileNames does not match the uploaded files",
+        ));
+    }
+
+    check_owner(&shared, storage_account, signer)?;
+    shared
+        .uploaders
+        .lock()
+        .unwrap()
+        .entry(storage_account.to_string())
+        .or_insert_with(|| signer.to_string());
+
+    let account = shared.account(storage_account);
+    let dir = shared.account_dir(storage_account);
+    fs::create_dir_all(&dir)?;
+    let mut current_usage = usage(&dir)?;
+
+    let mut finalized_locations = vec![];
+    let mut upload_errors = vec![];
+    for (filename, data) in &form.files {
+        let path = dir.join(filename);
+        let error = if check_filename(filename).is_err() {
+            Some("invalid file name")
+        } else if path.exists() {
+            Some("file already exists")
+        } else if account.as_ref().map_or(false, |account| {
+            current_usage + data.len() as u64 > account.reserved_bytes
+        }) {
+            Some("not enough storage")
+        } else if path
+            .parent()
+            .map_or(false, |parent| fs::create_dir_all(parent).is_err())
+        {
+            Some("a parent directory of the file is a file")
+        } else {
+            None
+        };
+
+        match error {
+            Some(error) => upload_errors.push(json!({
+                "file": filename,
+                "storage_account": storage_account,
+                "error": error,
+            })),
+            None => {
+                fs::write(&path, data)?;
+                current_usage += data.len() as u64;
+                finalized_locations
+                    .push(shared.object_url(storage_account, filename));
+            }
+        }
+    }
+
+    let message = if upload_errors.is_empty() {
+        "successfully uploaded files"
+    } else {
+        "some files failed to upload"
+    };
+    Ok(Json(json!({
+        "finalized_locations": finalized_locations,
+        "message": message,
+        "upload_errors": upload_errors,
+    })))
+}
+
+async fn edit(
+    State(shared): State<Arc<Shared>>,
+    multipart: Multipart,
+) -> ApiResult<Json<Value>> {
+    let form = Form::read(multipart).await?;
+    let storage_account = form.field("storage_account")?;
+    check_storage_account(storage_account)?;
+    let signer = form.field("signer")?;
+    let (filename, data) = match form.files.as_slice() {
+        [file] => file,
+        _ => return Err(ApiError::bad_request("expected exactly one file")),
+    };
+    check_filename(filename)?;
+    check_signature(
+        signer,
+        form.field("message")?,
+        &edit_message(storage_account, filename, data),
+    )?;
+
+    let url = shared.object_url(storage_account, filename);
+    if parse_location(form.field("url")?)? != (storage_account, filename) {
+        return Err(ApiError::bad_request("url does not match the file"));
+    }
+
+    check_owner(&shared, storage_account, signer)?;
+    let account = shared.account(storage_account);
+    check_mutable(account.as_ref())?;
+
+    let dir = shared.account_dir(storage_account);
+    let path = dir.join(filename);
+    let previous_len = match fs::metadata(&path) {
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => return Err(ApiError::not_found(format!("{url} does not exist"))),
+    };
+    if let Some(account) = account {
+        if usage(&dir)? - previous_len + data.len() as u64
+            > account.reserved_bytes
+        {
+            return Err(ApiError::bad_request("not enough storage"));
+        }
+    }
+
+    fs::write(&path, data)?;
+    Ok(Json(json!({ "finalized_location": url })))
+}
+
+#[derive(Deserialize)]
+struct DeleteFileRequest {
+    signer: String,
+    message: String,
+    location: String,
+}
+
+async fn delete_file(
+    State(shared): State<Arc<Shared>>,
+    Json(request): Json<DeleteFileRequest>,
+) -> ApiResult<Json<Value>> {
+    let (storage_account, filename) = parse_location(&request.location)?;
+    check_signature(
+        &request.signer,
+        &request.message,
+        &delete_message(storage_account, &request.location),
+    )?;
+    check_owner(&shared, storage_account, &request.signer)?;
+    check_mutable(shared.account(storage_account).as_ref())?;
+
+    let dir = shared.account_dir(storage_account);
+    let path = dir.join(filename);
+    match fs::remove_file(&path) {
+        Ok(()) => {
+            remove_empty_parents(&dir, &path);
+            Ok(Json(json!({ "message": "file deleted" })))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(
+            ApiError::not_found(format!("{} does not exist", request.location)),
+        ),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListObjectsRequest {
+    storage_account: String,
+    /// Only list keys starting with this
+    prefix: Option<String>,
+    /// Only list keys after this one
+    cursor: Option<String>,
+    /// Page size, `next_cursor` is set when there are more keys
+    limit: Option<usize>,
+    /// Also return size, last modification time and hash of each object
+    #[serde(default)]
+    detailed: bool,
+}
+
+async fn list_objects(
+    State(shared): State<Arc<Shared>>,
+    Json(request): Json<ListObjectsRequest>,
+) -> ApiResult<Json<Value>> {
+    check_storage_account(&request.storage_account)?;
+    let dir = shared.account_dir(&request.storage_account);
+    let mut keys = object_keys(&dir)?;
+    if shared.legacy_listing.load(Ordering::Relaxed) {
+        return Ok(Json(json!({ "keys": keys })));
+    }
+    keys.retain(|key| {
+        request
+            .prefix
+            .as_ref()
+            .map_or(true, |prefix| key.starts_with(prefix.as_str()))
+            && request.cursor.as_ref().map_or(true, |cursor| key > cursor)
+    });
+    keys.sort();
+
+    let mut next_cursor = None;
+    if let Some(limit) = request.limit {
+        if keys.len() > limit.max(1) {
+            keys.truncate(limit.max(1));
+            next_cursor = keys.last().cloned();
+        }
+    }
+
+    let mut response = json!({ "keys": keys, "next_cursor": next_cursor });
+    if request.detailed {
+        let objects = keys
+            .iter()
+            .map(|key| object_info(&dir, key))
+            .collect::<io::Result<Vec<Value>>>()?;
+        response["objects"] = Value::Array(objects);
+    }
+    Ok(Json(response))
+}
+
+fn object_info(dir: &Path, key: &str) -> io::Result<Value> {
+    let path = dir.join(key);
+    let metadata = fs::metadata(&path)?;
+    let last_modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    Ok(json!({
+        "key": key,
+        "size": metadata.len(),
+        "last_modified": last_modified,
+        "sha256": sha256_hex(&fs::read(&path)?),
+    }))
+}
+
+#[derive(Deserialize)]
+struct StorageAccountInfoRequest {
+    storage_account: String,
+}
+
+async fn storage_account_info(
+    State(shared): State<Arc<Shared>>,
+    Json(request): Json<StorageAccountInfoRequest>,
+) -> ApiResult<Json<Value>> {
+    check_storage_account(&request.storage_account)?;
+    let account =
+        shared.account(&request.storage_account).ok_or_else(|| {
+            ApiError::not_found(format!(
+                "storage account {} not found",
+                request.storage_account
+            ))
+        })?;
+    let current_usage = usage(&shared.account_dir(&account.storage_account))?;
+
+    // Same shape as the SDK's `StorageAcct::V2`
+    Ok(Json(json!({
+        "version": "V2",
+        "storage_account": account.storage_account,
+        "reserved_bytes": account.reserved_bytes,
+        "current_usage": current_usage,
+        "immutable": account.immutable,
+        "to_be_deleted": false,
+        "delete_request_epoch": 0,
+        "owner1": account.owner,
+        "account_counter_seed": 0,
+        "creation_time": 0,
+        "creation_epoch": 0,
+        "last_fee_epoch": 0,
+        "identifier": account.identifier,
+    })))
+}
+
+#[derive(Deserialize)]
+struct GetObjectDataRequest {
+    location: String,
+}
+
+async fn get_object_data(
+    State(shared): State<Arc<Shared>>,
+    Json(request): Json<GetObjectDataRequest>,
+) -> ApiResult<Json<Value>> {
+    let (storage_account, filename) = parse_location(&request.location)?;
+    if !shared.account_dir(storage_account).join(filename).is_file() {
+        return Err(ApiError::not_found(format!(
+            "{} does not exist",
+            request.location
+        )));
+    }
+
+    Ok(Json(json!({
+        "file_data": {
+            "owner-account-pubkey": shared.owner(storage_account).unwrap_or_default(),
+            "storage-account-pubkey": storage_account,
+        }
+    })))
+}
+
+/// Parses a single `bytes=` range into inclusive bounds within an object of
+/// `len` bytes.
+fn parse_range(range: &str, len: u64) -> Option<(u64, u64)> {
+    let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+    let last = len.checked_sub(1)?;
+    let (start, end) = if start.is_empty() {
+        // Suffix range, e.g. the last 500 bytes
+        let suffix: u64 = end.parse().ok()?;
+        (len.checked_sub(suffix.min(len))?, last)
+    } else if end.is_empty() {
+        (start.parse().ok()?, last)
+    } else {
+        (start.parse().ok()?, end.parse::<u64>().ok()?.min(last))
+    };
+    (start <= end).then_some((start, end))
+}
+
+async fn get_object(
+    State(shared): State<Arc<Shared>>,
+    UrlPath((storage_account, filename)): UrlPath<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    check_storage_account(&storage_account)?;
+    check_filename(&filename)?;
+    let path = shared.account_dir(&storage_account).join(&filename);
+    if !path.is_file() {
+        return Err(ApiError::not_found(format!(
+            "{storage_account}/{filename} not found"
+        )));
+    }
+    let data = fs::read(path)?;
+
+    let range = match headers.get(RANGE) {
+        Some(range) => range,
+        None => return Ok(data.into_response()),
+    };
+    let len = data.len() as u64;
+    match range
+        .to_str()
+        .ok()
+        .and_then(|range| parse_range(range, len))
+    {
+        Some((start, end)) => Ok((
+            StatusCode::PARTIAL_CONTENT,
+            [(CONTENT_RANGE, format!("bytes {start}-{end}/{len}"))],
+            data[start as usize..=end as usize].to_vec(),
+        )
+            .into_response()),
+        None => Ok((
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(CONTENT_RANGE, format!("bytes */{len}"))],
+        )
+            .into_response()),
+    }
+}
+
+#[test]
+fn test_parse_range() {
+    assert_eq!(parse_range("bytes=0-4", 10), Some((0, 4)));
+    assert_eq!(parse_range("bytes=5-", 10), Some((5, 9)));
+    assert_eq!(parse_range("bytes=-3", 10), Some((7, 9)));
+    assert_eq!(parse_range("bytes=8-100", 10), Some((8, 9)));
+    assert_eq!(parse_range("bytes=10-", 10), None);
+    assert_eq!(parse_range("bytes=0-1,4-5", 10), None);
+    assert_eq!(parse_range("bytes=0-0", 0), None);
+}
@@ -0,0 +1,61 @@
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use sha2::{Digest, Sha256};
+
+// The message formats must match the SDK byte for byte, including the
+// inconsistent spacing of the edit message.
+
+/// Message signed by the owner to upload files. `filenames` is the
+/// comma-separated list of uploaded file names.
+pub fn upload_message(storage_account: &str, filenames: &str) -> String {
+    format!(
+        "Shadow Drive Signed Message:\nStorage Account: {}\nUpload files with hash: {}",
+        storage_account,
+        sha256_hex(filenames.as_bytes())
+    )
+}
+
+/// Message signed by the owner to replace `filename` with `data`.
+pub fn edit_message(
+    storage_account: &str,
+    filename: &str,
+    data: &[u8],
+) -> String {
+    format!(
+        "Shadow Drive Signed Message:\n StorageAccount: {}\nFile to edit: {}\nNew file hash: {}",
+        storage_account,
+        filename,
+        sha256_hex(data)
+    )
+}
+
+/// Message signed by the owner to delete the object at `url`.
+pub fn delete_message(storage_account: &str, url: &str) -> String {
+    format!(
+        "Shadow Drive Signed Message:\nStorageAccount: {}\nFile to delete: {}",
+        storage_account, url
+    )
+}
+
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Checks a base58 ed25519 signature over `message` by the base58 `signer`,
+/// as produced by solana's `Signer::sign_message`.
+pub(crate) fn verify(signer: &str, signature: &str, message: &str) -> bool {
+    let public_key = bs58::decode(signer)
+        .into_vec()
+        .ok()
+        .and_then(|bytes| PublicKey::from_bytes(&bytes).ok());
+    let signature = bs58::decode(signature)
+        .into_vec()
+        .ok()
+        .and_then(|bytes| Signature::from_bytes(&bytes).ok());
+
+    match (public_key, signature) {
+        (Some(public_key), Some(signature)) => {
+            public_key.verify(message.as_bytes(), &signature).is_ok()
+        }
+        _ => false,
+    }
+}
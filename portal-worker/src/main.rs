@@ -0,0 +1,248 @@
+//! Uploads the objects summoned through the Shadow Portal program, taking the
+//! place of the modified Clockwork plugin.
+
+mod metrics;
+mod watch;
+mod worker;
+
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use clap::Parser;
+use log::{error, info, warn};
+use metrics::Metrics;
+use shadow_drive_cli::cluster::load_cluster;
+use shadow_drive_sdk::portal::{websocket_url, PortalClient};
+use solana_sdk::signature::{read_keypair_file, Signer};
+use tokio::{
+    sync::{mpsc, Semaphore},
+    task::JoinSet,
+};
+use tokio_util::sync::CancellationToken;
+use worker::Worker;
+
+/// How often the metrics are logged.
+const LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Watches the Shadow Portal program for summons and uploads their data.
+///
+/// The keypair pays for upload transactions and funds the clockwork threads
+/// that close summons afterwards, and receives each summon's extra lamports.
+#[derive(Debug, Parser)]
+#[clap(author, version, about)]
+struct Opts {
+    /// Path to the uploader's keypair, defaults to the Solana CLI's
+    #[clap(short, long)]
+    keypair: Option<PathBuf>,
+    /// RPC URL, defaults to the cluster's, or else the Solana CLI's
+    #[clap(short, long)]
+    url: Option<String>,
+    /// Websocket URL, derived from the RPC URL by default
+    #[clap(long)]
+    websocket_url: Option<String>,
+    /// Cluster profile selecting the portal program and object prefix, see
+    /// the CLI's --cluster
+    #[clap(long)]
+    cluster: Option<String>,
+    /// Cluster profiles to use instead of shadow-drive/clusters.yaml in the
+    /// user's config directory
+    #[clap(long)]
+    cluster_config: Option<PathBuf>,
+    /// Summons uploaded at the same time
+    #[clap(long, default_value_t = 8)]
+    concurrency: usize,
+    /// Retries for each summon before giving up on it until the next restart
+    #[clap(long, default_value_t = 5)]
+    max_retries: u32,
+    /// Seconds between scans for pending summons, which pick up summons
+    /// missed by the subscription
+    #[clap(
+        long,
+        default_value_t = 60,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    sweep_interval: u64,
+    /// Serve Prometheus metrics on this address, e.g. 127.0.0.1:9464
+    #[clap(long)]
+    metrics_addr: Option<SocketAddr>,
+    /// Seconds to let uploads in progress finish when shutting down
+    #[clap(long, default_value_t = 30)]
+    shutdown_timeout: u64,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or("info"),
+    )
+    .init();
+
+    if let Err(e) = run(Opts::parse()).await {
+        error!("{e:?}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(opts: Opts) -> anyhow::Result<()> {
+    // Default to the Solana CLI's keypair and URL, like the Shadow Drive CLI
+    let config = solana_cli_config::CONFIG_FILE
+        .as_ref()
+        .and_then(|path| solana_cli_config::Config::load(path).ok())
+        .unwrap_or_default();
+    let keypath = opts
+        .keypair
+        .unwrap_or_else(|| PathBuf::from(&config.keypair_path));
+    let keypair = read_keypair_file(&keypath)
+        .map_err(|e| anyhow!("failed to read keypair {keypath:?}: {e}"))?;
+
+    let cluster = opts
+        .cluster
+        .as_deref()
+        .map(|name| load_cluster(name, opts.cluster_config.as_deref()))
+        .transpose()?;
+    let url = opts
+        .url
+        .or_else(|| cluster.as_ref().map(|cluster| cluster.rpc_url.clone()))
+        .unwrap_or(config.json_rpc_url);
+    let websocket_url =
+        opts.websocket_url.unwrap_or_else(|| websocket_url(&url));
+    let cluster = cluster.unwrap_or_default();
+
+    let uploader = keypair.pubkey();
+    let portal = PortalClient::new(keypair, &url).with_cluster(&cluster);
+
+    // Every upload pays out into this account, so nothing can succeed without it
+    let payout_account = portal.payout_account();
+    portal
+        .rpc()
+        .get_account(&payout_account)
+        .await
+        .map_err(|e| {
+            anyhow!("payout account {payout_account} is not available: {e}")
+        })?;
+    let balance = portal.rpc().get_balance(&uploader).await?;
+    info!(
+        "uploading summons of {} as {uploader} ({balance} lamports) via {url}",
+        portal.program_id()
+    );
+    if balance == 0 {
+        warn!("{uploader} has no SOL to pay for uploads");
+    }
+
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            info!("shutting down");
+            shutdown.cancel();
+        }
+    });
+
+    let metrics = Arc::new(Metrics::default());
+    let worker = Arc::new(Worker::new(
+        portal,
+        Arc::clone(&metrics),
+        opts.max_retries,
+        shutdown.clone(),
+    ));
+
+    if let Some(addr) = opts.metrics_addr {
+        let metrics = Arc::clone(&metrics);
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(metrics, addr, shutdown).await {
+                error!("failed to serve metrics on {addr}: {e}");
+            }
+        });
+        info!("serving metrics on http://{addr}/metrics");
+    }
+    tokio::spawn({
+        let metrics = Arc::clone(&metrics);
+        let shutdown = shutdown.clone();
+        async move {
+            let mut ticks = tokio::time::interval(LOG_INTERVAL);
+            ticks.tick().await;
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => return,
+                    _ = ticks.tick() => info!("{}", metrics.summary()),
+                }
+            }
+        }
+    });
+
+    let (jobs_sender, mut jobs) = mpsc::unbounded_channel();
+    tokio::spawn(watch::subscribe(
+        Arc::clone(&worker),
+        websocket_url,
+        jobs_sender.clone(),
+        shutdown.clone(),
+    ));
+    tokio::spawn(watch::sweep(
+        Arc::clone(&worker),
+        Duration::from_secs(opts.sweep_interval),
+        jobs_sender,
+        shutdown.clone(),
+    ));
+
+    let permits = Arc::new(Semaphore::new(opts.concurrency.max(1)));
+    let mut uploads = JoinSet::new();
+    loop {
+        let metadata = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            Some(metadata) = jobs.recv() => metadata,
+            Some(_) = uploads.join_next(), if !uploads.is_empty() => continue,
+            else => break,
+        };
+        if !worker.claim(&metadata) {
+            continue;
+        }
+        let permit = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            permit = Arc::clone(&permits).acquire_owned() => {
+                permit.expect("the semaphore is never closed")
+            }
+        };
+        let worker = Arc::clone(&worker);
+        uploads.spawn(async move {
+            worker.process(metadata).await;
+            drop(permit);
+        });
+    }
+
+    // Let uploads that were already sent finish, as abandoning them could
+    // leave transactions unconfirmed
+    info!("waiting for {} uploads in progress", uploads.len());
+    let timeout = Duration::from_secs(opts.shutdown_timeout);
+    let drained = tokio::time::timeout(timeout, async {
+        while uploads.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        warn!("abandoning {} uploads in progress", uploads.len());
+        uploads.shutdown().await;
+    }
+    info!("{}", metrics.summary());
+
+    Ok(())
+}
+
+/// Completes on Ctrl-C, or SIGTERM on Unix.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate())
+            .expect("failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
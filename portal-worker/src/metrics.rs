@@ -0,0 +1,139 @@
+//! Counters describing what the worker has done, logged periodically and
+//! optionally served in the Prometheus text format.
+
+use std::{
+    fmt::Write,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use axum::{routing::get, Router};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Pending summons picked up from the subscription or a sweep
+    pub seen: AtomicU64,
+    pub uploaded: AtomicU64,
+    /// Summons uploaded or closed by someone else before this worker could
+    pub skipped: AtomicU64,
+    /// Summons that failed to upload, after running out of retries or with a
+    /// permanent error
+    pub failed: AtomicU64,
+    pub hash_mismatches: AtomicU64,
+    pub retries: AtomicU64,
+    pub reconnects: AtomicU64,
+    pub in_flight: AtomicU64,
+}
+
+impl Metrics {
+    pub fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn decrement(counter: &AtomicU64) {
+        counter.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn values(&self) -> [(&'static str, &'static str, &'static str, u64); 8] {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        [
+            (
+                "seen",
+                "counter",
+                "Pending summons picked up",
+                load(&self.seen),
+            ),
+            (
+                "uploaded",
+                "counter",
+                "Summons uploaded by this worker",
+                load(&self.uploaded),
+            ),
+            (
+                "skipped",
+                "counter",
+                "Summons uploaded or closed by someone else first",
+                load(&self.skipped),
+            ),
+            (
+                "failed",
+                "counter",
+                "Summons that failed to upload",
+                load(&self.failed),
+            ),
+            (
+                "hash_mismatches",
+                "counter",
+                "Summoned objects whose hash no longer matches",
+                load(&self.hash_mismatches),
+            ),
+            (
+                "retries",
+                "counter",
+                "Upload attempts that failed and were retried",
+                load(&self.retries),
+            ),
+            (
+                "reconnects",
+                "counter",
+                "Times the summon subscription was reconnected",
+                load(&self.reconnects),
+            ),
+            (
+                "in_flight",
+                "gauge",
+                "Summons being uploaded",
+                load(&self.in_flight),
+            ),
+        ]
+    }
+
+    /// One line for the log, e.g. `seen=3 uploaded=2 ...`.
+    pub fn summary(&self) -> String {
+        self.values()
+            .iter()
+            .map(|(name, _, _, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        for (name, kind, help, value) in self.values() {
+            let name = match kind {
+                "counter" => format!("portal_worker_{name}_total"),
+                _ => format!("portal_worker_{name}"),
+            };
+            let _ = writeln!(text, "# HELP {name} {help}");
+            let _ = writeln!(text, "# TYPE {name} {kind}");
+            let _ = writeln!(text, "{name} {value}");
+        }
+        text
+    }
+}
+
+/// Serves `/metrics` on `addr` until `shutdown` is cancelled.
+pub async fn serve(
+    metrics: Arc<Metrics>,
+    addr: SocketAddr,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let app = Router::new().route(
+        "/metrics",
+        get(move || {
+            let metrics = Arc::clone(&metrics);
+            async move { metrics.render() }
+        }),
+    );
+
+    axum::Server::try_bind(&addr)?
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(async move { shutdown.cancelled().await })
+        .await?;
+    Ok(())
+}
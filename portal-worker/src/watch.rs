@@ -0,0 +1,126 @@
+//! Finding pending summons: a `programSubscribe` subscription for new ones,
+//! and periodic sweeps for those that existed before the worker started or
+//! were missed while it was reconnecting.
+
+use std::{sync::Arc, time::Duration};
+
+use anchor_lang::AccountDeserialize;
+use futures::StreamExt;
+use log::{debug, info, warn};
+use shadow_drive_sdk::{models::ShadowDriveResult, portal::DataToBeSummoned};
+use solana_account_decoder::UiAccount;
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient, pubsub_client::PubsubClientError,
+    rpc_response::RpcKeyedAccount,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    metrics::Metrics,
+    worker::{Worker, INITIAL_BACKOFF, MAX_BACKOFF},
+};
+
+/// Sends the address of every summon that is created or changed while it has
+/// not been uploaded, reconnecting until `shutdown` is cancelled.
+pub async fn subscribe(
+    worker: Arc<Worker>,
+    websocket_url: String,
+    jobs: UnboundedSender<Pubkey>,
+    shutdown: CancellationToken,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        let result = tokio::select! {
+            _ = shutdown.cancelled() => return,
+            result = forward_updates(&worker, &websocket_url, &jobs, &mut backoff) => result,
+        };
+        if jobs.is_closed() {
+            return;
+        }
+        if let Err(e) = result {
+            warn!("summon subscription failed: {e}");
+        }
+
+        Metrics::increment(&worker.metrics().reconnects);
+        info!("reconnecting to {websocket_url} in {backoff:?}");
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            _ = tokio::time::sleep(backoff) => {}
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+async fn forward_updates(
+    worker: &Worker,
+    websocket_url: &str,
+    jobs: &UnboundedSender<Pubkey>,
+    backoff: &mut Duration,
+) -> ShadowDriveResult<()> {
+    let portal = worker.portal();
+    let pubsub = PubsubClient::new(websocket_url).await?;
+    let (mut updates, unsubscribe) = pubsub
+        .program_subscribe(
+            portal.program_id(),
+            Some(portal.summon_accounts_config()),
+        )
+        .await?;
+    info!("subscribed to summons of {}", portal.program_id());
+    *backoff = INITIAL_BACKOFF;
+
+    while let Some(update) = updates.next().await {
+        match decode(&update.value) {
+            Some((metadata, summon)) if !summon.uploaded => {
+                if jobs.send(metadata).is_err() {
+                    break;
+                }
+            }
+            Some(_) => {}
+            None => {
+                debug!("ignoring undecodable account {}", update.value.pubkey)
+            }
+        }
+    }
+    unsubscribe().await;
+
+    Err(PubsubClientError::ConnectionClosed(websocket_url.to_string()).into())
+}
+
+fn decode(keyed: &RpcKeyedAccount) -> Option<(Pubkey, DataToBeSummoned)> {
+    let metadata = keyed.pubkey.parse().ok()?;
+    let account = UiAccount::decode::<Account>(&keyed.account)?;
+    let summon =
+        DataToBeSummoned::try_deserialize(&mut account.data.as_slice()).ok()?;
+    Some((metadata, summon))
+}
+
+/// Sends the address of every pending summon each `interval`, starting
+/// immediately, until `shutdown` is cancelled.
+pub async fn sweep(
+    worker: Arc<Worker>,
+    interval: Duration,
+    jobs: UnboundedSender<Pubkey>,
+    shutdown: CancellationToken,
+) {
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            _ = ticks.tick() => {}
+        }
+
+        match worker.portal().list_pending_summons().await {
+            Ok(summons) => {
+                debug!("found {} pending summons", summons.len());
+                for (metadata, _) in summons {
+                    if jobs.send(metadata).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(e) => warn!("failed to list pending summons: {e}"),
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! Uploading a single summon: claiming it, fetching and verifying its object,
+//! and sending the `upload` instruction, with retries.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::{debug, info, warn};
+use shadow_drive_sdk::{
+    error::Error,
+    models::ShadowDriveResult,
+    portal::{DataToBeSummoned, PortalClient},
+};
+use solana_sdk::{
+    pubkey::Pubkey, signature::Keypair, transaction::TransactionError,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::metrics::Metrics;
+
+pub const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+pub const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+pub struct Worker {
+    portal: PortalClient<Keypair>,
+    metrics: Arc<Metrics>,
+    max_retries: u32,
+    shutdown: CancellationToken,
+    in_flight: Mutex<HashSet<Pubkey>>,
+    /// Summons that failed permanently, skipped until the worker restarts
+    given_up: Mutex<HashSet<Pubkey>>,
+}
+
+impl Worker {
+    pub fn new(
+        portal: PortalClient<Keypair>,
+        metrics: Arc<Metrics>,
+        max_retries: u32,
+        shutdown: CancellationToken,
+    ) -> Self {
+        Self {
+            portal,
+            metrics,
+            max_retries,
+            shutdown,
+            in_flight: Mutex::default(),
+            given_up: Mutex::default(),
+        }
+    }
+
+    pub fn portal(&self) -> &PortalClient<Keypair> {
+        &self.portal
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Marks a summon as being worked on. Returns `false` if it already is, or
+    /// was given up on, so the subscription and sweeps can report the same
+    /// summon more than once.
+    pub fn claim(&self, metadata: &Pubkey) -> bool {
+        if self.given_up.lock().unwrap().contains(metadata) {
+            return false;
+        }
+        let claimed = self.in_flight.lock().unwrap().insert(*metadata);
+        if claimed {
+            Metrics::increment(&self.metrics.seen);
+            Metrics::increment(&self.metrics.in_flight);
+        }
+        claimed
+    }
+
+    /// Uploads a claimed summon and releases it.
+    pub async fn process(&self, metadata: Pubkey) {
+        let result = self.upload_with_retries(&metadata).await;
+        self.release(metadata, result);
+    }
+
+    /// Records the outcome of processing a summon and releases it. Summons
+    /// that failed permanently are given up on, others are left for the next
+    /// sweep to retry.
+    fn release(
+        &self,
+        metadata: Pubkey,
+        result: ShadowDriveResult<Option<String>>,
+    ) {
+        match result {
+            Ok(Some(txid)) => {
+                Metrics::increment(&self.metrics.uploaded);
+                info!("uploaded {metadata}: {txid}");
+            }
+            Ok(None) => {
+                Metrics::increment(&self.metrics.skipped);
+                debug!("{metadata} was uploaded or closed by someone else");
+            }
+            Err(e) if is_retryable(&e) => {
+                Metrics::increment(&self.metrics.failed);
+                warn!("failed to upload {metadata}, retrying later: {e}");
+            }
+            Err(e) => {
+                Metrics::increment(&self.metrics.failed);
+                if let Error::ObjectHashMismatch { .. } = e {
+                    Metrics::increment(&self.metrics.hash_mismatches);
+                }
+                self.given_up.lock().unwrap().insert(metadata);
+                warn!("giving up on {metadata}: {e}");
+            }
+        }
+        self.in_flight.lock().unwrap().remove(&metadata);
+        Metrics::decrement(&self.metrics.in_flight);
+    }
+
+    async fn upload_with_retries(
+        &self,
+        metadata: &Pubkey,
+    ) -> ShadowDriveResult<Option<String>> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match self.try_upload(metadata).await {
+                Err(e) if attempt <= self.max_retries && is_retryable(&e) => {
+                    Metrics::increment(&self.metrics.retries);
+                    debug!(
+                        "attempt {attempt} for {metadata} failed, retrying in \
+                         {backoff:?}: {e}"
+                    );
+                    let delay = match &e {
+                        Error::RateLimited {
+                            retry_after: Some(retry_after),
+                        } => (*retry_after).max(backoff),
+                        _ => backoff,
+                    };
+                    tokio::select! {
+                        _ = self.shutdown.cancelled() => return Err(e),
+                        _ = tokio::time::sleep(delay) => {}
+                    }
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Returns the upload's signature, or `None` if the summon no longer needs
+    /// uploading.
+    async fn try_upload(
+        &self,
+        metadata: &Pubkey,
+    ) -> ShadowDriveResult<Option<String>> {
+        // The summon may have been handled since it was seen
+        let summon = match self.portal.get_summon(metadata).await? {
+            Some(summon) if !summon.uploaded => summon,
+            _ => return Ok(None),
+        };
+        let data = self.portal.fetch_summoned_data(&summon).await?;
+
+        match self.portal.upload(metadata, &summon, data.to_vec()).await {
+            Ok(response) => Ok(Some(response.txid)),
+            Err(e) => {
+                resolve_failed_upload(e, self.portal.get_summon(metadata).await)
+            }
+        }
+    }
+}
+
+/// Losing a race to another uploader is not a failure: an upload that failed
+/// because the summon, as read afterwards, was uploaded or closed meanwhile
+/// returns `None`.
+fn resolve_failed_upload(
+    error: Error,
+    summon: ShadowDriveResult<Option<DataToBeSummoned>>,
+) -> ShadowDriveResult<Option<String>> {
+    match summon {
+        Ok(Some(summon)) if !summon.uploaded => Err(error),
+        Ok(_) => Ok(None),
+        Err(_) => Err(error),
+    }
+}
+
+/// Network and RPC failures are retried, unless the program itself rejected
+/// the upload.
+fn is_retryable(error: &Error) -> bool {
+    match error {
+        Error::SolanaRpcError(e) => !matches!(
+            e.get_transaction_error(),
+            Some(TransactionError::InstructionError(..))
+        ),
+        error => error.is_retryable(),
+    }
+}
+
+#[cfg(test)]
+fn test_worker(max_retries: u32) -> Worker {
+    // Nothing listens on the RPC URL, so every RPC request fails to connect
+    let portal = PortalClient::new(Keypair::new(), "http://127.0.0.1:1");
+    Worker::new(
+        portal,
+        Arc::default(),
+        max_retries,
+        CancellationToken::new(),
+    )
+}
+
+#[cfg(test)]
+fn test_summon(
+    storage_account: Pubkey,
+    filename: &str,
+    data: &[u8],
+    uploaded: bool,
+) -> DataToBeSummoned {
+    use sha2::{Digest, Sha256};
+
+    DataToBeSummoned {
+        storage_account,
+        filename: filename.to_string(),
+        hash: Sha256::digest(data).into(),
+        time: 0,
+        uploader: Pubkey::default(),
+        summoner: Pubkey::new_unique(),
+        uploaded,
+        extra_lamports: 0,
+        unique_thread: None,
+        callback: None,
+        data: vec![],
+        payer: Pubkey::new_unique(),
+    }
+}
+
+#[test]
+fn test_is_retryable() {
+    use std::io;
+
+    use solana_client::client_error::ClientErrorKind;
+    use solana_sdk::instruction::InstructionError;
+
+    let rpc_error = |kind: ClientErrorKind| Error::SolanaRpcError(kind.into());
+    assert!(is_retryable(&Error::RateLimited { retry_after: None }));
+    assert!(is_retryable(&Error::ShadowDriveServerError {
+        status: 502,
+        message: "bad gateway".into(),
+    }));
+    assert!(is_retryable(&rpc_error(ClientErrorKind::Io(
+        io::Error::new(io::ErrorKind::ConnectionReset, "reset")
+    ))));
+    assert!(!is_retryable(&rpc_error(
+        ClientErrorKind::TransactionError(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(6000)
+        ))
+    )));
+    assert!(!is_retryable(&Error::ObjectHashMismatch {
+        expected: [0; 32],
+        actual: [1; 32],
+    }));
+}
+
+#[test]
+fn test_resolve_failed_upload() {
+    let storage_account = Pubkey::new_unique();
+    let error = || Error::RateLimited { retry_after: None };
+
+    // Someone else uploaded it, or it was closed
+    let uploaded = test_summon(storage_account, "a.txt", b"a", true);
+    assert_eq!(
+        resolve_failed_upload(error(), Ok(Some(uploaded))).unwrap(),
+        None
+    );
+    assert_eq!(resolve_failed_upload(error(), Ok(None)).unwrap(), None);
+
+    // Still pending, or unknown
+    let pending = test_summon(storage_account, "a.txt", b"a", false);
+    assert!(matches!(
+        resolve_failed_upload(error(), Ok(Some(pending))),
+        Err(Error::RateLimited { .. })
+    ));
+    assert!(matches!(
+        resolve_failed_upload(error(), Err(Error::UserInfoNotCreated)),
+        Err(Error::RateLimited { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_claim() {
+    use std::sync::atomic::Ordering;
+
+    let worker = test_worker(0);
+    let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    assert!(worker.claim(&a));
+    assert!(!worker.claim(&a), "claimed twice");
+    assert!(worker.claim(&b));
+    assert_eq!(worker.metrics.seen.load(Ordering::Relaxed), 2);
+    assert_eq!(worker.metrics.in_flight.load(Ordering::Relaxed), 2);
+
+    // The RPC is unreachable, which the next sweep retries
+    worker.process(a).await;
+    assert_eq!(worker.metrics.failed.load(Ordering::Relaxed), 1);
+    assert_eq!(worker.metrics.in_flight.load(Ordering::Relaxed), 1);
+    assert!(worker.claim(&a), "not released after a retryable failure");
+    assert_eq!(worker.metrics.seen.load(Ordering::Relaxed), 3);
+
+    // Permanent failures are given up on
+    worker.release(
+        a,
+        Err(Error::ObjectHashMismatch {
+            expected: [0; 32],
+            actual: [1; 32],
+        }),
+    );
+    assert_eq!(worker.metrics.failed.load(Ordering::Relaxed), 2);
+    assert_eq!(worker.metrics.hash_mismatches.load(Ordering::Relaxed), 1);
+    assert_eq!(worker.metrics.in_flight.load(Ordering::Relaxed), 1);
+    assert!(!worker.claim(&a), "claimed after giving up");
+    assert_eq!(worker.metrics.seen.load(Ordering::Relaxed), 3);
+}
+
+#[tokio::test]
+async fn test_fetch_summoned_data() {
+    use shadow_drive_mock::MockShadowDrive;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let mut worker = test_worker(0);
+    worker.portal = worker.portal.with_object_prefix(drive.object_prefix());
+    let storage_account = Pubkey::new_unique();
+    drive
+        .put_object(storage_account, "a.txt", b"summoned")
+        .unwrap();
+
+    let summon = test_summon(storage_account, "a.txt", b"summoned", false);
+    let data = worker.portal().fetch_summoned_data(&summon).await.unwrap();
+    assert_eq!(data.as_ref(), b"summoned");
+
+    // The object changed since it was summoned, which no retry can fix
+    drive
+        .put_object(storage_account, "a.txt", b"changed")
+        .unwrap();
+    let error = worker
+        .portal()
+        .fetch_summoned_data(&summon)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, Error::ObjectHashMismatch { .. }));
+    assert!(!is_retryable(&error));
+}
@@ -12,6 +12,12 @@ pub const INIT_FEE: u64 = 100;
 
 pub const TIME_DELAY_SECS: i64 = 0;
 
+/// Lamports the uploader funds the clockwork delete thread with, on top of
+/// the summon's `extra_lamports`.
+pub const SOL_TX_FEE: u64 = 5_000;
+pub const CW_TX_FEE: u64 = 1_000;
+pub const DELETE_TX_FEE: u64 = SOL_TX_FEE + CW_TX_FEE;
+
 pub mod shdw {
     #[cfg(feature = "mainnet")]
     anchor_lang::declare_id!("SHDWyBxihqiCj6YekG2GUr7wqKLeLAMK1gHZck9pL6y");
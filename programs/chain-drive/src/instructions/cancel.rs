@@ -0,0 +1,106 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use super::summon::DataToBeSummoned;
+use crate::{constants::shdw, PortalError};
+
+/// Refunds the SHDW held for a summon that has not been uploaded yet to the
+/// summoner, and closes its metadata and vault accounts, refunding their rent
+/// to whoever paid it.
+pub(crate) fn handler(ctx: Context<Cancel>) -> Result<()> {
+    let metadata_bump: u8 = *ctx.bumps.get("metadata").unwrap();
+    let metadata = &ctx.accounts.metadata;
+    let last_seed: Vec<u8> = metadata
+        .unique_thread
+        .map(|id| id.to_le_bytes().to_vec())
+        .unwrap_or_else(|| {
+            <str as AsRef<[u8]>>::as_ref(metadata.filename.as_ref()).to_vec()
+        });
+    let metadata_seeds: &[&[u8]] = &[
+        metadata.summoner.as_ref(),
+        metadata.storage_account.as_ref(),
+        last_seed.as_ref(),
+        &[metadata_bump],
+    ];
+    let signer_seeds: &[&[&[u8]]] = &[metadata_seeds];
+
+    #[cfg(feature = "verbose")]
+    msg!("refunding portal token pda to summoner");
+    anchor_spl::token::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: ctx.accounts.shdw_vault.to_account_info(),
+                to: ctx.accounts.summoner_token_account.to_account_info(),
+                authority: ctx.accounts.metadata.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        ctx.accounts.shdw_vault.amount,
+    )?;
+    #[cfg(feature = "verbose")]
+    msg!("closing portal token pda");
+    anchor_spl::token::close_account(CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        anchor_spl::token::CloseAccount {
+            account: ctx.accounts.shdw_vault.to_account_info(),
+            destination: ctx.accounts.payer.to_account_info(),
+            authority: ctx.accounts.metadata.to_account_info(),
+        },
+        signer_seeds,
+    ))?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Cancel<'info> {
+    #[account(mut)]
+    pub summoner: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = shdw_mint,
+        token::authority = summoner,
+    )]
+    pub summoner_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: the rent payer recorded by the summon, checked on `metadata`
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = summoner,
+        constraint = metadata.rent_payer() == payer.key()
+            @ anchor_lang::error::ErrorCode::ConstraintHasOne,
+        constraint = !metadata.uploaded @ PortalError::AlreadyUploaded,
+        seeds = [
+            metadata.summoner.key().as_ref(),
+            metadata.storage_account.as_ref(),
+            // this is embarassing
+            metadata.unique_thread.map(|id|
+                id.to_le_bytes().to_vec()
+            ).unwrap_or(<str as AsRef<[u8]>>::as_ref(metadata.filename.as_ref()).to_vec()).as_ref(),
+        ],
+        bump,
+    )]
+    pub metadata: Box<Account<'info, DataToBeSummoned>>,
+
+    #[account(
+        mut,
+        seeds = [
+            metadata.key().as_ref()
+        ],
+        bump,
+        token::mint = shdw_mint,
+        token::authority = metadata,
+    )]
+    pub shdw_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(address = shdw::ID)]
+    pub shdw_mint: Box<Account<'info, Mint>>,
+
+    pub token_program: Program<'info, Token>,
+}
@@ -1,5 +1,7 @@
+pub mod cancel;
 pub mod delete;
 pub mod init;
+pub mod registry;
 pub mod summon;
 pub mod update;
 pub mod upload;
@@ -0,0 +1,326 @@
+use anchor_lang::prelude::*;
+use runes::{ArchivedRunes, Runes};
+
+use crate::PortalError;
+
+pub const REGISTRY_SEED: &[u8] = b"rune-registry";
+
+pub(crate) fn create_handler(
+    ctx: Context<CreateRegistry>,
+    id: u64,
+) -> Result<()> {
+    ctx.accounts.registry.authority = ctx.accounts.authority.key();
+    ctx.accounts.registry.id = id;
+    ctx.accounts.registry.len = 0;
+
+    Ok(())
+}
+
+pub(crate) fn append_handler(
+    ctx: Context<AppendRunes>,
+    data: Vec<u8>,
+) -> Result<()> {
+    let offset = ctx.accounts.registry.len as usize;
+    RuneRegistry::write(&mut ctx.accounts.registry, offset, &data)
+}
+
+pub(crate) fn update_handler(
+    ctx: Context<UpdateRunes>,
+    offset: u32,
+    data: Vec<u8>,
+) -> Result<()> {
+    let offset = ctx.accounts.registry.update_offset(offset)?;
+    RuneRegistry::write(&mut ctx.accounts.registry, offset, &data)
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64, capacity: u32)]
+pub struct CreateRegistry<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = RuneRegistry::HEADER_LEN + capacity as usize,
+        seeds = [
+            REGISTRY_SEED,
+            authority.key().as_ref(),
+            id.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub registry: Account<'info, RuneRegistry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(data: Vec<u8>)]
+pub struct AppendRunes<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [
+            REGISTRY_SEED,
+            authority.key().as_ref(),
+            registry.id.to_le_bytes().as_ref(),
+        ],
+        bump,
+        realloc = RuneRegistry::space_for(
+            registry.to_account_info().data_len(),
+            registry.len as usize + data.len(),
+        ),
+        realloc::payer = authority,
+        realloc::zero = false,
+    )]
+    pub registry: Account<'info, RuneRegistry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(offset: u32, data: Vec<u8>)]
+pub struct UpdateRunes<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [
+            REGISTRY_SEED,
+            authority.key().as_ref(),
+            registry.id.to_le_bytes().as_ref(),
+        ],
+        bump,
+        realloc = RuneRegistry::space_for(
+            registry.to_account_info().data_len(),
+            offset as usize + data.len(),
+        ),
+        realloc::payer = authority,
+        realloc::zero = false,
+    )]
+    pub registry: Account<'info, RuneRegistry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Stores a serialized [`Runes`] archive on-chain, so that programs can
+/// summon data described by runes that were not compiled into them.
+///
+/// The archive is not part of the borsh serialized account. It is stored
+/// as raw bytes right after the fixed size header, at an 8 byte aligned
+/// offset, so that it can be read zero-copy with [`RuneRegistry::get_runes`].
+#[account]
+pub struct RuneRegistry {
+    /// The only key allowed to append to or update the archive
+    pub authority: Pubkey,
+    pub id: u64,
+    /// Length of the archive stored after the header
+    pub len: u32,
+    pub _padding: [u8; 4],
+}
+
+impl RuneRegistry {
+    /// discriminator + authority + id + len + padding
+    pub const HEADER_LEN: usize = 8 + 32 + 8 + 4 + 4;
+
+    /// Account size required to hold an archive of `archive_len` bytes.
+    /// Accounts never shrink, so this is at least `current_len`.
+    pub fn space_for(current_len: usize, archive_len: usize) -> usize {
+        current_len.max(Self::HEADER_LEN + archive_len)
+    }
+
+    /// Checks an `update_runes` offset, which may be anywhere up to the end
+    /// of the archive so that it stays contiguous.
+    fn update_offset(&self, offset: u32) -> Result<usize> {
+        if offset > self.len {
+            return Err(PortalError::InvalidRegistryOffset.into());
+        }
+        Ok(offset as usize)
+    }
+
+    /// Writes `data` at `offset` into the archive, discarding anything
+    /// stored after it. To replace the archive, write the first chunk at
+    /// offset 0 and append the rest.
+    fn write(
+        registry: &mut Account<RuneRegistry>,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<()> {
+        let start = Self::HEADER_LEN + offset;
+        let end = start + data.len();
+        {
+            let info = registry.to_account_info();
+            let mut account_data = info.try_borrow_mut_data()?;
+            account_data
+                .get_mut(start..end)
+                .ok_or(PortalError::InvalidRegistryOffset)?
+                .copy_from_slice(data);
+        }
+        registry.len = (offset + data.len()) as u32;
+
+        Ok(())
+    }
+
+    /// Returns the archive stored in the data of a registry account,
+    /// without any key id section or publisher signature trailer.
+    pub fn archive(data: &[u8]) -> Result<&[u8]> {
+        let header = RuneRegistry::try_deserialize(&mut &data[..])?;
+        let archive = data
+            .get(Self::HEADER_LEN..Self::HEADER_LEN + header.len as usize)
+            .ok_or(PortalError::InvalidRunes)?;
+        Ok(&archive[..runes::archive_len(archive)])
+    }
+
+    /// Validates and returns the runes stored in the data of a registry
+    /// account, e.g. `RuneRegistry::get_runes(&registry.try_borrow_data()?)`.
+    pub fn get_runes(data: &[u8]) -> Result<&ArchivedRunes> {
+        rkyv::check_archived_root::<Runes>(Self::archive(data)?)
+            .map_err(|_| PortalError::InvalidRunes.into())
+    }
+
+    /// Returns the runes stored in the data of a registry account without
+    /// validating the archive.
+    pub unsafe fn get_runes_unchecked(data: &[u8]) -> Result<&ArchivedRunes> {
+        Ok(rkyv::archived_root::<Runes>(Self::archive(data)?))
+    }
+}
+
+pub fn rune_registry(authority: &Pubkey, id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[REGISTRY_SEED, authority.as_ref(), id.to_le_bytes().as_ref()],
+        &crate::ID,
+    )
+    .0
+}
+
+#[test]
+fn test_registry_header_len() {
+    let header = RuneRegistry {
+        authority: Pubkey::default(),
+        id: 0,
+        len: 0,
+        _padding: [0; 4],
+    };
+    let mut data = vec![];
+    header.try_serialize(&mut data).unwrap();
+    assert_eq!(data.len(), RuneRegistry::HEADER_LEN);
+    assert_eq!(RuneRegistry::HEADER_LEN % 8, 0);
+}
+
+#[cfg(test)]
+fn with_test_registry(
+    capacity: usize,
+    f: impl FnOnce(&mut Account<RuneRegistry>),
+) {
+    let key = Pubkey::new_unique();
+    let mut lamports = 0;
+    // Aligned like account data, so archives can be read zero-copy
+    let mut data = rkyv::AlignedVec::new();
+    data.resize(RuneRegistry::HEADER_LEN + capacity, 0);
+    let header = RuneRegistry {
+        authority: Pubkey::new_unique(),
+        id: 0,
+        len: 0,
+        _padding: [0; 4],
+    };
+    header.try_serialize(&mut &mut data[..]).unwrap();
+
+    let info = AccountInfo::new(
+        &key,
+        false,
+        true,
+        &mut lamports,
+        &mut data,
+        &crate::ID,
+        false,
+        0,
+    );
+    let mut registry = Account::try_from(&info).unwrap();
+    f(&mut registry);
+}
+
+#[test]
+fn test_registry_append_and_update() {
+    with_test_registry(16, |registry| {
+        // Appends write at the end of the archive
+        let offset = registry.len as usize;
+        RuneRegistry::write(registry, offset, b"abc").unwrap();
+        let offset = registry.len as usize;
+        RuneRegistry::write(registry, offset, b"def").unwrap();
+        assert_eq!(registry.len, 6);
+
+        // Updates overwrite from the offset and truncate the rest
+        let offset = registry.update_offset(2).unwrap();
+        RuneRegistry::write(registry, offset, b"XY").unwrap();
+        assert_eq!(registry.len, 4);
+
+        let info = registry.to_account_info();
+        let data = info.try_borrow_data().unwrap();
+        let start = RuneRegistry::HEADER_LEN;
+        assert_eq!(&data[start..start + 6], b"abXYef");
+    });
+}
+
+#[test]
+fn test_registry_invalid_offset() {
+    with_test_registry(8, |registry| {
+        RuneRegistry::write(registry, 0, b"abcd").unwrap();
+
+        // Updates may start at the end of the archive, but not after it
+        assert_eq!(registry.update_offset(4).unwrap(), 4);
+        assert_eq!(
+            registry.update_offset(5).unwrap_err(),
+            Error::from(PortalError::InvalidRegistryOffset)
+        );
+
+        // Writes past the end of the account are rejected
+        assert_eq!(
+            RuneRegistry::write(registry, 4, b"too long").unwrap_err(),
+            Error::from(PortalError::InvalidRegistryOffset)
+        );
+        assert_eq!(registry.len, 4);
+    });
+}
+
+#[test]
+fn test_registry_space_for() {
+    let header = RuneRegistry::HEADER_LEN;
+    // Grows to fit the archive
+    assert_eq!(RuneRegistry::space_for(header, 100), header + 100);
+    assert_eq!(RuneRegistry::space_for(header + 10, 100), header + 100);
+    // Never shrinks, e.g. when an update truncates the archive
+    assert_eq!(RuneRegistry::space_for(header + 100, 10), header + 100);
+}
+
+#[test]
+fn test_registry_get_runes() {
+    let runes = Runes::new(
+        [7; 32],
+        vec!["a.txt".to_string(), "b.txt".to_string()],
+        &[b"a".to_vec(), b"bb".to_vec()],
+        vec![1, 2],
+    )
+    .unwrap();
+    let archive = runes.to_bytes().unwrap();
+
+    with_test_registry(archive.len(), |registry| {
+        // Written in chunks, like the CLI does
+        let (first, rest) = archive.split_at(archive.len() / 2);
+        RuneRegistry::write(registry, 0, first).unwrap();
+        let offset = registry.len as usize;
+        RuneRegistry::write(registry, offset, rest).unwrap();
+        registry.exit(&crate::ID).unwrap();
+
+        let info = registry.to_account_info();
+        let data = info.try_borrow_data().unwrap();
+        assert_eq!(RuneRegistry::archive(&data).unwrap(), &archive[..]);
+        let stored = RuneRegistry::get_runes(&data).unwrap();
+        assert_eq!(stored, &runes);
+    });
+}
@@ -23,6 +23,7 @@ pub(crate) fn handler(
     ctx.accounts.metadata.time = i64::MAX;
     ctx.accounts.metadata.uploader = Pubkey::default();
     ctx.accounts.metadata.summoner = ctx.accounts.summoner.key();
+    ctx.accounts.metadata.payer = ctx.accounts.payer.key();
     ctx.accounts.metadata.extra_lamports = extra_lamports;
     ctx.accounts.metadata.unique_thread = unique_thread;
     ctx.accounts.metadata.data = vec![];
@@ -135,9 +136,23 @@ pub struct DataToBeSummoned {
     pub unique_thread: Option<u64>,
     pub callback: Option<ClockworkInstructionData>,
     pub data: Vec<u8>,
+    /// Paid the rent of the metadata and vault accounts, which a cancel
+    /// refunds to it. Last, so accounts created before it was added still
+    /// deserialize: the spare bytes `space` reserves after `data` read as
+    /// `Pubkey::default()`. See [`DataToBeSummoned::rent_payer`].
+    pub payer: Pubkey,
 }
 
 impl DataToBeSummoned {
+    /// Where a cancel refunds the rent to: the payer, or the summoner for
+    /// summons created before the payer was recorded.
+    pub fn rent_payer(&self) -> Pubkey {
+        if self.payer == Pubkey::default() {
+            self.summoner
+        } else {
+            self.payer
+        }
+    }
     pub fn get_source(&self) -> String {
         Self::build_source(&self.storage_account, &self.filename)
     }
@@ -204,4 +219,33 @@ fn test_round_up_align() {
     assert_eq!(round_up_align(12, 8), 16);
     assert_eq!(round_up_align(16, 8), 16);
     assert_eq!(round_up_align(34, 8), 40);
-}
\ No newline at end of file
+}
+#[test]
+fn test_summon_without_payer() {
+    let summon = DataToBeSummoned {
+        storage_account: Pubkey::new_unique(),
+        filename: "file.txt".to_string(),
+        hash: [1; 32],
+        time: 0,
+        uploader: Pubkey::new_unique(),
+        summoner: Pubkey::new_unique(),
+        uploaded: false,
+        extra_lamports: 0,
+        unique_thread: None,
+        callback: None,
+        data: vec![],
+        payer: Pubkey::new_unique(),
+    };
+    let mut data = vec![0; DataToBeSummoned::space(0, &summon.filename, &None)];
+    summon.try_serialize(&mut &mut data[..]).unwrap();
+    let decoded = DataToBeSummoned::try_deserialize(&mut &data[..]).unwrap();
+    assert_eq!(decoded.payer, summon.payer);
+    assert_eq!(decoded.rent_payer(), summon.payer);
+
+    // Accounts written before the payer was recorded end after `data`
+    let len = 8 + summon.try_to_vec().unwrap().len();
+    data[len - 32..len].fill(0);
+    let decoded = DataToBeSummoned::try_deserialize(&mut &data[..]).unwrap();
+    assert_eq!(decoded.payer, Pubkey::default());
+    assert_eq!(decoded.rent_payer(), summon.summoner);
+}
@@ -1,11 +1,14 @@
 use std::str::FromStr;
 
+use anchor_lang::error::ERROR_CODE_OFFSET;
 use anchor_lang::prelude::*;
 use anchor_lang::InstructionData;
 use anchor_lang::{
     solana_program::instruction::Instruction, system_program::Transfer,
 };
 use clockwork_sdk::{cpi::ThreadCreate, state::Trigger};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
 use sha2::{Digest, Sha256};
 
 pub use clockwork_sdk::{
@@ -20,13 +23,16 @@ declare_id!("G6xPudzNNM8CwfLHC9ByzrF67LcwyiRe4t9vHg34eqpR");
 pub mod constants;
 pub mod instructions;
 pub use constants::*;
+use instructions::cancel::*;
 use instructions::delete::*;
 use instructions::init::*;
+use instructions::registry::*;
 use instructions::summon::*;
 use instructions::update::*;
 use instructions::upload::*;
 
 pub use instructions::init::{portal_config, PortalConfig};
+pub use instructions::registry::{rune_registry, RuneRegistry};
 
 #[program]
 pub mod chain_drive {
@@ -168,10 +174,6 @@ pub mod chain_drive {
 
         // let metadata_key = ctx.accounts.metadata.key().to_bytes().to_vec();
 
-        const SOL_TX_FEE: u64 = 5_000;
-        const CW_TX_FEE: u64 = 1_000;
-        const DELETE_TX_FEE: u64 = SOL_TX_FEE + CW_TX_FEE;
-
         #[cfg(feature = "verbose")]
         msg!("creating thread");
         clockwork_sdk::cpi::thread_create(
@@ -235,6 +237,12 @@ pub mod chain_drive {
         Ok(())
     }
 
+    /// Withdraws a summon that has not been uploaded yet, refunding its SHDW
+    /// and rent to the summoner.
+    pub fn cancel(ctx: Context<Cancel>) -> Result<()> {
+        instructions::cancel::handler(ctx)
+    }
+
     pub fn init(ctx: Context<Init>) -> Result<()> {
         msg!("Initializing portal program with {} as admin and with a {} shades per byte fee", ADMIN, INIT_FEE);
         ctx.accounts.config.admin = Pubkey::from_str(ADMIN).unwrap();
@@ -249,15 +257,83 @@ pub mod chain_drive {
 
         Ok(())
     }
+
+    /// Creates an on-chain registry for a runes archive, with room for
+    /// `capacity` bytes. The archive grows as needed when written to.
+    pub fn create_registry(
+        ctx: Context<CreateRegistry>,
+        id: u64,
+        capacity: u32,
+    ) -> Result<()> {
+        // Only sizes the account, in `CreateRegistry`
+        let _ = capacity;
+        instructions::registry::create_handler(ctx, id)
+    }
+
+    /// Appends a chunk of a serialized runes archive to a registry.
+    pub fn append_runes(
+        ctx: Context<AppendRunes>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::registry::append_handler(ctx, data)
+    }
+
+    /// Overwrites a registry's archive starting at `offset`, discarding
+    /// everything after the written chunk.
+    pub fn update_runes(
+        ctx: Context<UpdateRunes>,
+        offset: u32,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::registry::update_handler(ctx, offset, data)
+    }
 }
 
 #[error_code]
+#[derive(PartialEq, Eq, FromPrimitive)]
 pub enum PortalError {
     #[msg("you tried to delete the data too early")]
     EarlyDelete,
 
     #[msg("you tried to upload data with incorrect hash")]
     InvalidHash,
+
+    #[msg("registry writes must start within the stored archive")]
+    InvalidRegistryOffset,
+
+    #[msg("registry does not contain a valid runes archive")]
+    InvalidRunes,
+
+    #[msg("the summoned data has already been uploaded")]
+    AlreadyUploaded,
+}
+
+impl PortalError {
+    /// The error behind a custom program error code, if it is one of the
+    /// portal's.
+    pub fn from_code(code: u32) -> Option<Self> {
+        code.checked_sub(ERROR_CODE_OFFSET)
+            .and_then(Self::from_u32)
+    }
+}
+
+#[test]
+fn test_portal_error_from_code() {
+    assert_eq!(PortalError::from_code(6000), Some(PortalError::EarlyDelete));
+    assert_eq!(PortalError::from_code(0), None);
+    for error in [
+        PortalError::EarlyDelete,
+        PortalError::InvalidHash,
+        PortalError::InvalidRegistryOffset,
+        PortalError::InvalidRunes,
+        PortalError::AlreadyUploaded,
+    ] {
+        assert_eq!(PortalError::from_code(error.into()), Some(error));
+    }
+    assert_eq!(
+        PortalError::from_code(u32::from(PortalError::AlreadyUploaded) + 1),
+        None
+    );
 }
 
 #[test]
@@ -13,6 +13,7 @@ fn main() {
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 1, 2, 3, 4, 5, 6, 7, 8, 9,
                 10, 11, 12, 13, 14, 15, 16,
             ],
+            key_id: None,
         }],
     };
 
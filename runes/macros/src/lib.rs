@@ -0,0 +1,97 @@
+//! Compile time verification of signed rune archives. Used by `runes::inscribe_runes!`;
+//! depend on `runes` rather than using this crate directly.
+
+use std::path::PathBuf;
+
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, LitByteStr, LitStr, Token,
+};
+
+// Mirrors `runes::signed`. This crate can't depend on `runes`, since `runes` re-exports it.
+const SIGNED_RUNES_MAGIC: [u8; 8] = *b"RUNESIG1";
+const SIGNATURE_TRAILER_LEN: usize = 32 + 64 + SIGNED_RUNES_MAGIC.len();
+
+struct VerifyArgs {
+    path: LitStr,
+    publisher: LitStr,
+}
+
+impl Parse for VerifyArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let publisher = input.parse()?;
+        Ok(VerifyArgs { path, publisher })
+    }
+}
+
+/// Reads a signed rune archive at a path relative to `CARGO_MANIFEST_DIR`, and fails
+/// compilation unless it carries a valid signature by the given base58 publisher key.
+/// Expands to a byte string of exactly the bytes that were verified. The archive is also
+/// passed to `include_bytes!`, only so that edits to it trigger a rebuild.
+#[proc_macro]
+pub fn verify_signed_runes(input: TokenStream) -> TokenStream {
+    let VerifyArgs { path, publisher } = parse_macro_input!(input as VerifyArgs);
+
+    match verify(&path, &publisher) {
+        Ok((full_path, bytes)) => {
+            let bytes = LitByteStr::new(&bytes, path.span());
+            quote!({
+                const _: &[u8] = include_bytes!(#full_path);
+                #bytes
+            })
+            .into()
+        }
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn verify(path: &LitStr, publisher: &LitStr) -> syn::Result<(String, Vec<u8>)> {
+    // Decode the expected publisher
+    let expected: [u8; 32] = bs58::decode(publisher.value())
+        .into_vec()
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| syn::Error::new(publisher.span(), "invalid publisher pubkey"))?;
+
+    // Read archive
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| syn::Error::new(path.span(), "CARGO_MANIFEST_DIR is not set"))?;
+    let full_path = PathBuf::from(manifest_dir).join(path.value());
+    let bytes = std::fs::read(&full_path)
+        .map_err(|e| syn::Error::new(path.span(), format!("failed to read {full_path:?}: {e}")))?;
+
+    // Split off signature trailer
+    if bytes.len() < SIGNATURE_TRAILER_LEN || !bytes.ends_with(&SIGNED_RUNES_MAGIC) {
+        return Err(syn::Error::new(path.span(), "runes archive is not signed"));
+    }
+    let (archive, trailer) = bytes.split_at(bytes.len() - SIGNATURE_TRAILER_LEN);
+    if trailer[..32] != expected {
+        return Err(syn::Error::new(
+            publisher.span(),
+            format!(
+                "runes archive is signed by {}",
+                bs58::encode(&trailer[..32]).into_string()
+            ),
+        ));
+    }
+
+    // Verify signature
+    let public_key = PublicKey::from_bytes(&expected)
+        .map_err(|_| syn::Error::new(publisher.span(), "invalid publisher pubkey"))?;
+    let signature = Signature::from_bytes(&trailer[32..96])
+        .map_err(|_| syn::Error::new(path.span(), "malformed runes signature"))?;
+    public_key
+        .verify(archive, &signature)
+        .map_err(|_| syn::Error::new(path.span(), "invalid runes signature"))?;
+
+    let full_path = full_path
+        .to_str()
+        .map(str::to_string)
+        .ok_or_else(|| syn::Error::new(path.span(), "runes path is not valid utf-8"))?;
+    Ok((full_path, bytes))
+}
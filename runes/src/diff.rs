@@ -0,0 +1,83 @@
+use crate::{Rune, Runes};
+
+/// The difference between two [`Runes`] archives, keyed by rune name.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RunesDiff {
+    /// Runes present in `new` but not in `old`.
+    pub added: Vec<Rune>,
+    /// Runes present in `old` but not in `new`.
+    pub removed: Vec<Rune>,
+    /// Runes present in both whose length or hash changed, as `(old, new)`.
+    pub changed: Vec<(Rune, Rune)>,
+}
+
+impl RunesDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Applies this diff to `runes`, turning `old` into `new`.
+    pub fn apply(&self, runes: &mut Runes) {
+        for rune in &self.removed {
+            runes.remove_rune(&rune.name);
+        }
+        for (_, rune) in &self.changed {
+            runes.upsert_rune(rune.clone());
+        }
+        for rune in &self.added {
+            runes.upsert_rune(rune.clone());
+        }
+    }
+}
+
+/// Computes which runes were added, removed or changed going from `old` to `new`.
+pub fn diff(old: &Runes, new: &Runes) -> RunesDiff {
+    let mut diff = RunesDiff::default();
+
+    for new_rune in &new.runes {
+        match old.get_rune(&new_rune.name) {
+            None => diff.added.push(new_rune.clone()),
+            Some(old_rune) if old_rune != new_rune => {
+                diff.changed.push((old_rune.clone(), new_rune.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    for old_rune in &old.runes {
+        if new.get_rune(&old_rune.name).is_none() {
+            diff.removed.push(old_rune.clone());
+        }
+    }
+
+    diff
+}
+
+#[test]
+fn test_diff() {
+    let old = Runes::new(
+        [0; 32],
+        vec!["a".to_string(), "b".to_string(), "c".to_string()],
+        &[b"alpha".to_vec(), b"beta".to_vec(), b"gamma".to_vec()],
+        vec![5, 4, 5],
+    )
+    .unwrap();
+    let new = Runes::new(
+        [0; 32],
+        vec!["a".to_string(), "c".to_string(), "d".to_string()],
+        &[b"alpha".to_vec(), b"GAMMA".to_vec(), b"delta".to_vec()],
+        vec![5, 5, 5],
+    )
+    .unwrap();
+
+    let diff = diff(&old, &new);
+    assert_eq!(diff.added.len(), 1);
+    assert_eq!(diff.added[0].name, "d");
+    assert_eq!(diff.removed.len(), 1);
+    assert_eq!(diff.removed[0].name, "b");
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].1.name, "c");
+
+    let mut applied = old.clone();
+    diff.apply(&mut applied);
+    assert!(crate::diff(&applied, &new).is_empty());
+}
@@ -0,0 +1,82 @@
+use std::{fmt, io, path::PathBuf};
+
+#[derive(Debug)]
+pub enum RunesError {
+    /// The `.runes` file could not be created.
+    FailedToCreateRunesFile { path: PathBuf, source: io::Error },
+    /// The archive could not be written to the `.runes` file or writer.
+    FailedToSaveRunes(io::Error),
+    /// The `.runes` file could not be opened or read.
+    FailedToReadRunesFile { path: PathBuf, source: io::Error },
+    /// rkyv failed to serialize the runes.
+    SerializationFailed(String),
+    /// The bytes are not a valid runes archive.
+    InvalidRunesFile(String),
+    /// `filenames`, `filedata` and `sizes` passed to [`Runes::new`](crate::Runes::new)
+    /// have different lengths.
+    LengthMismatch {
+        filenames: usize,
+        filedata: usize,
+        sizes: usize,
+    },
+    /// A file is larger than a rune can describe.
+    RuneTooLarge { name: String, len: usize },
+    /// Tried to merge runes describing different storage accounts.
+    StorageAccountMismatch,
+    /// The archive does not carry a publisher signature.
+    MissingSignature,
+    /// The archive is signed, but not by the expected publisher.
+    UnexpectedPublisher,
+    /// The archive's signature does not match its contents.
+    InvalidSignature,
+}
+
+impl fmt::Display for RunesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunesError::FailedToCreateRunesFile { path, source } => {
+                write!(f, "failed to create runes file {path:?}: {source}")
+            }
+            RunesError::FailedToSaveRunes(source) => {
+                write!(f, "failed to save runes: {source}")
+            }
+            RunesError::FailedToReadRunesFile { path, source } => {
+                write!(f, "failed to read runes file {path:?}: {source}")
+            }
+            RunesError::SerializationFailed(e) => write!(f, "failed to serialize runes: {e}"),
+            RunesError::InvalidRunesFile(e) => write!(f, "invalid runes archive: {e}"),
+            RunesError::LengthMismatch {
+                filenames,
+                filedata,
+                sizes,
+            } => write!(
+                f,
+                "got {filenames} filenames, {filedata} files and {sizes} sizes"
+            ),
+            RunesError::RuneTooLarge { name, len } => write!(
+                f,
+                "{name} is {len} bytes, but runes can describe at most {} bytes",
+                u16::MAX
+            ),
+            RunesError::StorageAccountMismatch => {
+                write!(f, "runes belong to different storage accounts")
+            }
+            RunesError::MissingSignature => write!(f, "runes archive is not signed"),
+            RunesError::UnexpectedPublisher => {
+                write!(f, "runes archive is signed by an unexpected publisher")
+            }
+            RunesError::InvalidSignature => write!(f, "runes archive signature is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for RunesError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RunesError::FailedToCreateRunesFile { source, .. }
+            | RunesError::FailedToReadRunesFile { source, .. }
+            | RunesError::FailedToSaveRunes(source) => Some(source),
+            _ => None,
+        }
+    }
+}
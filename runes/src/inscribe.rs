@@ -1,6 +1,22 @@
+/// Embeds a `.runes` archive into the binary and generates `get_runes` / `get_runes_unchecked`,
+/// and `get_rune_key_id` for the [key ids](crate::keys) of encrypted objects.
+///
+/// `inscribe_runes!("path")` includes the file relative to the invoking source file, like
+/// `include_bytes!`.
+///
+/// `inscribe_runes!("path", publisher = "<base58 pubkey>")` requires a signed archive (see
+/// [`signed`](crate::signed)) and fails compilation unless it is validly signed by `publisher`.
+/// In this form the path is relative to the crate's `CARGO_MANIFEST_DIR`, since the verifying
+/// proc macro cannot resolve paths against the invoking file on stable Rust.
 #[macro_export]
 macro_rules! inscribe_runes {
     ($path:literal) => {
+        $crate::inscribe_runes!(@inscribe *include_bytes!($path));
+    };
+    ($path:literal, publisher = $publisher:literal) => {
+        $crate::inscribe_runes!(@inscribe *$crate::verify_signed_runes!($path, $publisher));
+    };
+    (@inscribe $bytes:expr) => {
         #[repr(C)] // guarantee 'bytes' comes after '_align'
         pub struct AlignedAsPrivate<Align, Bytes: ?Sized> {
             pub _align: [Align; 0],
@@ -9,26 +25,35 @@ macro_rules! inscribe_runes {
 
         #[macro_export]
         macro_rules! include_bytes_align_as {
-            ($align_ty:ty, $_path:literal) => {{
+            ($align_ty:ty) => {{
                 // const block expression to encapsulate the static
                 use crate::AlignedAsPrivate;
 
                 // this assignment is made possible by CoerceUnsized
                 static ALIGNED: &AlignedAsPrivate<$align_ty, [u8]> = &AlignedAsPrivate {
                     _align: [],
-                    bytes: *include_bytes!($path),
+                    bytes: $bytes,
                 };
 
                 &ALIGNED.bytes
             }};
         }
 
-        static __PRIVATE_INNER_RUNES_DATA: &'static [u8] = include_bytes_align_as!(u64, $path);
+        static __PRIVATE_INNER_RUNES_DATA: &'static [u8] = include_bytes_align_as!(u64);
+        // Key ids and signatures follow the serialized runes
+        fn __private_inner_runes_archive() -> &'static [u8] {
+            let data = __PRIVATE_INNER_RUNES_DATA;
+            &data[..runes::archive_len(data)]
+        }
+        pub fn get_rune_key_id(index: usize) -> Option<[u8; 8]> {
+            let data = __PRIVATE_INNER_RUNES_DATA;
+            runes::keys::key_id(&data[..runes::signed::archive_len(data)], index)
+        }
         pub unsafe fn get_runes_unchecked() -> &'static runes::ArchivedRunes {
-            rkyv::archived_root::<runes::Runes>(__PRIVATE_INNER_RUNES_DATA)
+            rkyv::archived_root::<runes::Runes>(__private_inner_runes_archive())
         }
         pub unsafe fn get_runes() -> Option<&'static runes::ArchivedRunes> {
-            rkyv::check_archived_root::<runes::Runes>(__PRIVATE_INNER_RUNES_DATA).ok()
+            rkyv::check_archived_root::<runes::Runes>(__private_inner_runes_archive()).ok()
         }
     };
 }
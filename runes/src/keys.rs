@@ -0,0 +1,210 @@
+//! Encryption key ids of runes.
+//!
+//! A rune of an encrypted object records the id of the key the object was encrypted with, so
+//! readers of the archive know which key decrypts it. Key ids are stored in a versioned section
+//! after the serialized runes:
+//!
+//! ```text
+//! [ rkyv serialized Runes ][ (rune index: u32, key id: 8) * count ][ count: u32 ][ RUNE_KEYS_MAGIC: 8 ]
+//! ```
+//!
+//! Integers are little endian. The section is only written if some rune has a key id, so
+//! archives without encrypted objects keep the original format, and the
+//! [`ArchivedRunes`](crate::ArchivedRunes) layout is the same either way. In signed archives the
+//! section comes before the signature trailer, so key ids are covered by the signature.
+
+use rkyv::AlignedVec;
+
+use crate::{Rune, RunesError};
+
+/// Marks the end of a key id section. The last byte is the section's format version.
+pub const RUNE_KEYS_MAGIC: [u8; 8] = *b"RUNEKEY1";
+
+/// Rune indices and the key ids of their objects.
+pub type KeyIds = Vec<(usize, [u8; 8])>;
+
+const ENTRY_LEN: usize = 4 + 8;
+const FOOTER_LEN: usize = 4 + RUNE_KEYS_MAGIC.len();
+
+/// Returns true if `bytes[..end]` ends with a key id section.
+pub const fn has_key_ids(bytes: &[u8], end: usize) -> bool {
+    if end < FOOTER_LEN || end > bytes.len() {
+        return false;
+    }
+    let offset = end - RUNE_KEYS_MAGIC.len();
+    let mut i = 0;
+    while i < RUNE_KEYS_MAGIC.len() {
+        if bytes[offset + i] != RUNE_KEYS_MAGIC[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Length of the serialized [`Runes`](crate::Runes) in `bytes[..end]`, which may end with a key
+/// id section. Use [`crate::archive_len`] for a whole `.runes` file.
+pub const fn archive_len(bytes: &[u8], end: usize) -> usize {
+    if !has_key_ids(bytes, end) {
+        return end;
+    }
+    let count = end - FOOTER_LEN;
+    let count = u32::from_le_bytes([
+        bytes[count],
+        bytes[count + 1],
+        bytes[count + 2],
+        bytes[count + 3],
+    ]) as usize;
+    match count.checked_mul(ENTRY_LEN) {
+        Some(entries) if entries + FOOTER_LEN <= end => end - FOOTER_LEN - entries,
+        // Leaves the section in place, so the archive fails validation
+        _ => end,
+    }
+}
+
+/// Returns the key id of the rune at `index` in `bytes`, an archive without signature trailer.
+/// Lets zero-copy readers of [`ArchivedRunes`](crate::ArchivedRunes) find the key of a rune.
+pub fn key_id(bytes: &[u8], index: usize) -> Option<[u8; 8]> {
+    let start = archive_len(bytes, bytes.len());
+    if start == bytes.len() {
+        return None;
+    }
+    let index = u32::try_from(index).ok()?.to_le_bytes();
+    bytes[start..bytes.len() - FOOTER_LEN]
+        .chunks_exact(ENTRY_LEN)
+        .find(|entry| entry[..4] == index)
+        .map(|entry| entry[4..].try_into().expect("entries have fixed length"))
+}
+
+/// Splits an archive without signature trailer into the serialized [`Runes`](crate::Runes) and
+/// the rune indices and key ids of its key id section.
+pub fn split_key_ids(bytes: &[u8]) -> Result<(&[u8], KeyIds), RunesError> {
+    if !has_key_ids(bytes, bytes.len()) {
+        return Ok((bytes, vec![]));
+    }
+    let start = archive_len(bytes, bytes.len());
+    if start == bytes.len() {
+        return Err(RunesError::InvalidRunesFile(
+            "key id section is longer than the archive".to_string(),
+        ));
+    }
+    let (archive, section) = bytes.split_at(start);
+    let key_ids = section[..section.len() - FOOTER_LEN]
+        .chunks_exact(ENTRY_LEN)
+        .map(|entry| {
+            let index = u32::from_le_bytes(entry[..4].try_into().expect("fixed length"));
+            let key_id = entry[4..].try_into().expect("entries have fixed length");
+            (index as usize, key_id)
+        })
+        .collect();
+    Ok((archive, key_ids))
+}
+
+/// Appends the key id section for `runes` to their serialized archive, if any rune has a key id.
+pub(crate) fn append_key_ids(bytes: &mut AlignedVec, runes: &[Rune]) -> Result<(), RunesError> {
+    let key_ids: Vec<_> = runes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, rune)| Some((index, rune.key_id?)))
+        .collect();
+    if key_ids.is_empty() {
+        return Ok(());
+    }
+    let too_many = |_| RunesError::SerializationFailed("too many runes for key ids".to_string());
+    for (index, key_id) in &key_ids {
+        bytes.extend_from_slice(&u32::try_from(*index).map_err(too_many)?.to_le_bytes());
+        bytes.extend_from_slice(key_id);
+    }
+    bytes.extend_from_slice(
+        &u32::try_from(key_ids.len())
+            .map_err(too_many)?
+            .to_le_bytes(),
+    );
+    bytes.extend_from_slice(&RUNE_KEYS_MAGIC);
+    Ok(())
+}
+
+#[test]
+fn test_key_ids() {
+    use crate::Runes;
+
+    let mut runes = Runes::new(
+        [5; 32],
+        vec!["plain".to_string(), "secret".to_string()],
+        &[b"alpha".to_vec(), b"ciphertext".to_vec()],
+        vec![5, 10],
+    )
+    .unwrap();
+
+    // Without key ids the archive keeps the original format
+    let plain = runes.to_bytes().unwrap();
+    assert_eq!(&plain[..], &rkyv::to_bytes::<_, 256>(&runes).unwrap()[..]);
+    assert!(!has_key_ids(&plain, plain.len()));
+    assert_eq!(crate::archive_len(&plain), plain.len());
+
+    // Key ids round trip, and the archive before them is unchanged
+    runes.runes[1] = runes.runes[1].clone().with_key_id([9; 8]);
+    let bytes = runes.to_bytes().unwrap();
+    assert_eq!(crate::archive_len(&bytes), plain.len());
+    assert_eq!(&bytes[..plain.len()], &plain[..]);
+    assert_eq!(key_id(&bytes, 0), None);
+    assert_eq!(key_id(&bytes, 1), Some([9; 8]));
+    assert_eq!(Runes::from_archive_bytes(&bytes).unwrap(), runes);
+
+    // Zero-copy readers see the same runes
+    let archived = rkyv::check_archived_root::<Runes>(&bytes[..crate::archive_len(&bytes)]);
+    assert_eq!(archived.unwrap(), &runes);
+
+    // Key ids of missing runes and truncated sections are rejected
+    let mut invalid = bytes.clone();
+    invalid[plain.len()] = 2;
+    assert!(matches!(
+        Runes::from_archive_bytes(&invalid),
+        Err(RunesError::InvalidRunesFile(_))
+    ));
+    let count = bytes.len() - FOOTER_LEN;
+    let mut invalid = bytes.clone();
+    invalid[count..count + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(crate::archive_len(&invalid), invalid.len());
+    assert_eq!(key_id(&invalid, 1), None);
+    assert!(matches!(
+        Runes::from_archive_bytes(&invalid),
+        Err(RunesError::InvalidRunesFile(_))
+    ));
+}
+
+#[cfg(feature = "signing")]
+#[test]
+fn test_signed_key_ids() {
+    use crate::{signed, Runes};
+    use signed::ed25519_dalek::{Keypair, SecretKey};
+
+    let secret = SecretKey::from_bytes(&[4; 32]).unwrap();
+    let publisher = Keypair {
+        public: (&secret).into(),
+        secret,
+    };
+    let runes = Runes {
+        storage_account: [0; 32],
+        runes: vec![Rune::from_data("a".to_string(), &b"alpha".to_vec())
+            .unwrap()
+            .with_key_id([1; 8])],
+    };
+    let target = std::env::temp_dir().join("test_signed_key_ids");
+    runes
+        .clone()
+        .save_signed(target.clone(), &publisher)
+        .unwrap();
+    let target = target.with_extension("runes");
+    let (loaded, _) = Runes::load_verified(&target, None).unwrap();
+    assert_eq!(loaded.runes[0].key_id, Some([1; 8]));
+
+    // Key ids are signed with the runes
+    let mut bytes = std::fs::read(&target).unwrap();
+    let section = crate::archive_len(&bytes);
+    bytes[section + 4] ^= 1;
+    assert!(matches!(
+        signed::verify(&bytes, None),
+        Err(RunesError::InvalidSignature)
+    ));
+}
@@ -1,19 +1,32 @@
-use std::{io::Write, path::PathBuf};
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
 
-use itertools::multizip;
 use rkyv::{Archive, CheckBytes, Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+pub mod diff;
+pub mod error;
 pub mod inscribe;
+pub mod keys;
+pub mod signed;
+
+pub use diff::{diff, RunesDiff};
+pub use error::RunesError;
+pub use runes_macros::verify_signed_runes;
 
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq, Clone, CheckBytes)]
-#[archive(compare(PartialEq))]
 #[archive_attr(derive(rkyv::CheckBytes, Debug))]
 #[repr(align(8))]
 pub struct Rune {
     pub name: String,
     pub len: u16,
     pub hash: [u8; 32],
+    /// Id of the key the object was encrypted with, if it is encrypted. Stored in the
+    /// archive's key id section (see [`keys`]) rather than in [`ArchivedRune`].
+    #[with(rkyv::with::Skip)]
+    pub key_id: Option<[u8; 8]>,
 }
 
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq, Clone)]
@@ -26,54 +39,228 @@ pub struct Runes {
 }
 
 impl Runes {
+    /// Creates runes for the given files. `filenames`, `filedata` and `sizes`
+    /// must have the same length, and each size must fit in a [`Rune`].
     pub fn new(
         storage_account: [u8; 32],
         filenames: Vec<String>,
         filedata: &[Vec<u8>],
         sizes: Vec<usize>,
-    ) -> Runes {
-        let hashes: Vec<[u8; 32]> = filedata.into_iter().map(sha256_hash).collect();
+    ) -> Result<Runes, RunesError> {
+        if filenames.len() != filedata.len() || filenames.len() != sizes.len() {
+            return Err(RunesError::LengthMismatch {
+                filenames: filenames.len(),
+                filedata: filedata.len(),
+                sizes: sizes.len(),
+            });
+        }
 
         // Create runes
         let mut runes = Vec::with_capacity(filenames.len());
-        for (filename, size, hash) in multizip((filenames, sizes, hashes)) {
+        for ((filename, data), size) in filenames.into_iter().zip(filedata).zip(sizes) {
+            let len = size.try_into().map_err(|_| RunesError::RuneTooLarge {
+                name: filename.clone(),
+                len: size,
+            })?;
             runes.push(Rune {
                 name: filename,
-                len: size as u16,
-                hash,
+                len,
+                hash: sha256_hash(data),
+                key_id: None,
             })
         }
-        Runes {
+        Ok(Runes {
             storage_account,
             runes,
-        }
+        })
+    }
+
+    /// Serializes the runes into an aligned rkyv archive, followed by the
+    /// [key id section](keys) if any rune has a key id.
+    pub fn to_bytes(&self) -> Result<rkyv::AlignedVec, RunesError> {
+        let mut bytes = rkyv::to_bytes::<_, 256>(self)
+            .map_err(|e| RunesError::SerializationFailed(e.to_string()))?;
+        keys::append_key_ids(&mut bytes, &self.runes)?;
+        Ok(bytes)
     }
 
-    pub fn save(self, mut target: PathBuf) -> Result<(), RunesError> {
+    /// Serializes the runes and writes the archive to `writer`.
+    pub fn save_to_writer<W: Write>(&self, mut writer: W) -> Result<(), RunesError> {
+        writer
+            .write_all(&self.to_bytes()?)
+            .map_err(RunesError::FailedToSaveRunes)
+    }
+
+    pub fn save(self, target: PathBuf) -> Result<(), RunesError> {
         // Serialize
-        let bytes = rkyv::to_bytes::<_, 256>(&self).unwrap();
+        let bytes = self.to_bytes()?;
 
         // Save to file
-        target.set_extension("runes");
-        let mut file =
-            std::fs::File::create(target).map_err(|_| RunesError::FailedToCreateRunesFile)?;
-        file.write_all(&bytes)
-            .map_err(|_| RunesError::FailedToSaveRunes)?;
+        write_runes_file(target, &bytes)
+    }
 
+    /// Serializes and signs the runes with the publisher's keypair,
+    /// returning a signed archive. See [`signed`] for the format.
+    #[cfg(feature = "signing")]
+    pub fn sign(&self, publisher: &signed::ed25519_dalek::Keypair) -> Result<Vec<u8>, RunesError> {
+        Ok(signed::sign(&self.to_bytes()?, publisher))
+    }
+
+    /// Saves a signed archive, embedding the publisher's signature.
+    #[cfg(feature = "signing")]
+    pub fn save_signed(
+        self,
+        target: PathBuf,
+        publisher: &signed::ed25519_dalek::Keypair,
+    ) -> Result<(), RunesError> {
+        write_runes_file(target, &self.sign(publisher)?)
+    }
+
+    /// Loads a `.runes` file previously written with [`Runes::save`].
+    /// Signed archives are accepted, but their signature is not checked;
+    /// see [`Runes::load_verified`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Runes, RunesError> {
+        let bytes = read_runes_file(path)?;
+        let (archive, _signature) = signed::split_signed(&bytes);
+        Runes::from_archive_bytes(archive)
+    }
+
+    /// Loads a signed `.runes` file and verifies its signature. If `publisher`
+    /// is provided, the archive must be signed by that key.
+    #[cfg(feature = "signing")]
+    pub fn load_verified(
+        path: impl AsRef<Path>,
+        publisher: Option<&[u8; 32]>,
+    ) -> Result<(Runes, signed::RunesSignature), RunesError> {
+        let bytes = read_runes_file(path)?;
+        let signature = signed::verify(&bytes, publisher)?;
+        let (archive, _) = signed::split_signed(&bytes);
+        Ok((Runes::from_archive_bytes(archive)?, signature))
+    }
+
+    fn from_archive_bytes(bytes: &[u8]) -> Result<Runes, RunesError> {
+        let (archive, key_ids) = keys::split_key_ids(bytes)?;
+
+        // Validate and deserialize. rkyv requires the buffer to be aligned.
+        let mut aligned = rkyv::AlignedVec::with_capacity(archive.len());
+        aligned.extend_from_slice(archive);
+        let mut runes = rkyv::from_bytes::<Runes>(&aligned)
+            .map_err(|e| RunesError::InvalidRunesFile(e.to_string()))?;
+
+        for (index, key_id) in key_ids {
+            let rune = runes.runes.get_mut(index).ok_or_else(|| {
+                RunesError::InvalidRunesFile(format!("key id for missing rune {index}"))
+            })?;
+            rune.key_id = Some(key_id);
+        }
+        Ok(runes)
+    }
+
+    pub fn get_rune(&self, name: &str) -> Option<&Rune> {
+        self.runes.iter().find(|rune| rune.name == name)
+    }
+
+    /// Inserts a rune, replacing any existing rune with the same name.
+    /// Returns the rune that was replaced, if any.
+    pub fn upsert_rune(&mut self, rune: Rune) -> Option<Rune> {
+        match self.runes.iter_mut().find(|r| r.name == rune.name) {
+            Some(existing) => Some(std::mem::replace(existing, rune)),
+            None => {
+                self.runes.push(rune);
+                None
+            }
+        }
+    }
+
+    /// Removes the rune with the given name, returning it if it existed.
+    pub fn remove_rune(&mut self, name: &str) -> Option<Rune> {
+        let index = self.runes.iter().position(|rune| rune.name == name)?;
+        Some(self.runes.remove(index))
+    }
+
+    /// Merges `other` into `self`. Runes in `other` take precedence over
+    /// runes in `self` with the same name. Both archives must describe the
+    /// same storage account.
+    pub fn merge(&mut self, other: Runes) -> Result<(), RunesError> {
+        if self.storage_account != other.storage_account {
+            return Err(RunesError::StorageAccountMismatch);
+        }
+        for rune in other.runes {
+            self.upsert_rune(rune);
+        }
         Ok(())
     }
 }
 
+impl Rune {
+    /// Creates a rune for a file by hashing its contents.
+    pub fn from_data(name: String, data: &Vec<u8>) -> Result<Rune, RunesError> {
+        let len = data
+            .len()
+            .try_into()
+            .map_err(|_| RunesError::RuneTooLarge {
+                name: name.clone(),
+                len: data.len(),
+            })?;
+        Ok(Rune {
+            name,
+            len,
+            hash: sha256_hash(data),
+            key_id: None,
+        })
+    }
+
+    /// Records that the object is encrypted with the key `key_id`. `data` given to
+    /// [`Rune::from_data`] must then be the ciphertext.
+    pub fn with_key_id(self, key_id: [u8; 8]) -> Rune {
+        Rune {
+            key_id: Some(key_id),
+            ..self
+        }
+    }
+}
+
+/// Compares everything but the key id, which archived runes do not carry.
+impl PartialEq<Rune> for ArchivedRune {
+    fn eq(&self, other: &Rune) -> bool {
+        self.name == other.name && self.len == other.len && self.hash == other.hash
+    }
+}
+
+/// Length of the serialized [`Runes`] at the start of a `.runes` file, i.e. without the
+/// [key id section](keys) and [signature trailer](signed) that may follow them.
+pub const fn archive_len(bytes: &[u8]) -> usize {
+    keys::archive_len(bytes, signed::archive_len(bytes))
+}
+
 impl ArchivedRunes {
     pub fn get_rune(&self, name: &str) -> Option<&ArchivedRune> {
         self.runes.iter().find(|rune| rune.name == name)
     }
 }
 
-#[derive(Debug)]
-pub enum RunesError {
-    FailedToCreateRunesFile,
-    FailedToSaveRunes,
+fn write_runes_file(mut target: PathBuf, bytes: &[u8]) -> Result<(), RunesError> {
+    target.set_extension("runes");
+    let mut file =
+        std::fs::File::create(&target).map_err(|source| RunesError::FailedToCreateRunesFile {
+            path: target,
+            source,
+        })?;
+    file.write_all(bytes)
+        .map_err(RunesError::FailedToSaveRunes)?;
+
+    Ok(())
+}
+
+fn read_runes_file(path: impl AsRef<Path>) -> Result<Vec<u8>, RunesError> {
+    let read_error = |source| RunesError::FailedToReadRunesFile {
+        path: path.as_ref().to_owned(),
+        source,
+    };
+    let mut file = std::fs::File::open(path.as_ref()).map_err(read_error)?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).map_err(read_error)?;
+    Ok(bytes)
 }
 
 fn sha256_hash(data: &Vec<u8>) -> [u8; 32] {
@@ -91,6 +278,7 @@ fn test_rune() {
         name: "test.txt".to_string(),
         len: 42,
         hash: (0..32).collect::<Vec<u8>>().try_into().unwrap(),
+        key_id: None,
     };
 
     let bytes = rkyv::to_bytes::<_, 256>(&rune).unwrap();
@@ -118,6 +306,7 @@ fn test_one_runes() {
         name: "test.txt".to_string(),
         len: 42,
         hash: (0..32).collect::<Vec<u8>>().try_into().unwrap(),
+        key_id: None,
     };
 
     let runes = Runes {
@@ -135,6 +324,7 @@ fn test_two_runes() {
         name: "test.txt".to_string(),
         len: 42,
         hash: (0..32).collect::<Vec<u8>>().try_into().unwrap(),
+        key_id: None,
     };
 
     let runes = Runes {
@@ -145,3 +335,180 @@ fn test_two_runes() {
     let archived = unsafe { rkyv::archived_root::<Runes>(&bytes[..]) };
     assert_eq!(archived, &runes);
 }
+
+#[test]
+fn test_save_and_load() {
+    let rune = Rune {
+        name: "test.txt".to_string(),
+        len: 42,
+        hash: (0..32).collect::<Vec<u8>>().try_into().unwrap(),
+        key_id: None,
+    };
+
+    let runes = Runes {
+        storage_account: [7; 32],
+        runes: vec![rune],
+    };
+    let target = std::env::temp_dir().join("test_save_and_load");
+    runes.clone().save(target.clone()).unwrap();
+
+    let loaded = Runes::load(target.with_extension("runes")).unwrap();
+    assert_eq!(loaded, runes);
+}
+
+#[test]
+fn test_upsert_and_remove_rune() {
+    let mut runes = Runes::new(
+        [0; 32],
+        vec!["a".to_string(), "b".to_string()],
+        &[b"alpha".to_vec(), b"beta".to_vec()],
+        vec![5, 4],
+    )
+    .unwrap();
+
+    // Replacing an existing rune returns the old one
+    let replaced = runes.upsert_rune(Rune::from_data("a".to_string(), &b"gamma".to_vec()).unwrap());
+    assert_eq!(replaced.unwrap().hash, sha256_hash(&b"alpha".to_vec()));
+    assert_eq!(runes.runes.len(), 2);
+    assert_eq!(
+        runes.get_rune("a").unwrap().hash,
+        sha256_hash(&b"gamma".to_vec())
+    );
+
+    // Inserting a new rune appends it
+    assert!(runes
+        .upsert_rune(Rune::from_data("c".to_string(), &b"delta".to_vec()).unwrap())
+        .is_none());
+    assert_eq!(runes.runes.len(), 3);
+
+    // Removing
+    assert_eq!(runes.remove_rune("b").unwrap().len, 4);
+    assert!(runes.remove_rune("b").is_none());
+    assert_eq!(runes.runes.len(), 2);
+}
+
+#[test]
+fn test_merge_runes() {
+    let mut runes = Runes::new(
+        [0; 32],
+        vec!["a".to_string(), "b".to_string()],
+        &[b"alpha".to_vec(), b"beta".to_vec()],
+        vec![5, 4],
+    )
+    .unwrap();
+    let other = Runes::new(
+        [0; 32],
+        vec!["b".to_string(), "c".to_string()],
+        &[b"BETA".to_vec(), b"gamma".to_vec()],
+        vec![4, 5],
+    )
+    .unwrap();
+    runes.merge(other).unwrap();
+
+    assert_eq!(runes.runes.len(), 3);
+    assert_eq!(
+        runes.get_rune("b").unwrap().hash,
+        sha256_hash(&b"BETA".to_vec())
+    );
+
+    let mismatched = Runes {
+        storage_account: [1; 32],
+        runes: vec![],
+    };
+    assert!(matches!(
+        runes.merge(mismatched),
+        Err(RunesError::StorageAccountMismatch)
+    ));
+}
+
+#[cfg(feature = "signing")]
+#[test]
+fn test_sign_and_verify() {
+    use signed::ed25519_dalek::{Keypair, SecretKey};
+
+    let secret = SecretKey::from_bytes(&[3; 32]).unwrap();
+    let publisher = Keypair {
+        public: (&secret).into(),
+        secret,
+    };
+    let runes = Runes::new(
+        [0; 32],
+        vec!["a".to_string()],
+        &[b"alpha".to_vec()],
+        vec![5],
+    )
+    .unwrap();
+    let target = std::env::temp_dir().join("test_sign_and_verify");
+    runes
+        .clone()
+        .save_signed(target.clone(), &publisher)
+        .unwrap();
+    let target = target.with_extension("runes");
+
+    // Verifies with and without an expected publisher
+    let (loaded, signature) = Runes::load_verified(&target, None).unwrap();
+    assert_eq!(loaded, runes);
+    assert_eq!(signature.publisher, publisher.public.to_bytes());
+    assert!(Runes::load_verified(&target, Some(&publisher.public.to_bytes())).is_ok());
+    assert!(matches!(
+        Runes::load_verified(&target, Some(&[9; 32])),
+        Err(RunesError::UnexpectedPublisher)
+    ));
+
+    // Signed archives still load without verification
+    assert_eq!(Runes::load(&target).unwrap(), runes);
+
+    // Tampering with the archive invalidates the signature
+    let mut bytes = runes.sign(&publisher).unwrap();
+    bytes[0] ^= 1;
+    assert!(matches!(
+        signed::verify(&bytes, None),
+        Err(RunesError::InvalidSignature)
+    ));
+
+    // Unsigned archives have no signature
+    let unsigned = runes.to_bytes().unwrap();
+    assert!(matches!(
+        signed::verify(&unsigned, None),
+        Err(RunesError::MissingSignature)
+    ));
+}
+
+#[test]
+fn test_new_length_mismatch() {
+    let result = Runes::new(
+        [0; 32],
+        vec!["a".to_string(), "b".to_string()],
+        &[b"alpha".to_vec()],
+        vec![5, 4],
+    );
+    assert!(matches!(
+        result,
+        Err(RunesError::LengthMismatch {
+            filenames: 2,
+            filedata: 1,
+            sizes: 2
+        })
+    ));
+
+    let result = Rune::from_data("big".to_string(), &vec![0; u16::MAX as usize + 1]);
+    assert!(matches!(result, Err(RunesError::RuneTooLarge { .. })));
+}
+
+#[test]
+fn test_save_to_writer() {
+    let runes = Runes::new(
+        [0; 32],
+        vec!["a".to_string()],
+        &[b"alpha".to_vec()],
+        vec![5],
+    )
+    .unwrap();
+    let mut buffer = vec![];
+    runes.save_to_writer(&mut buffer).unwrap();
+    assert_eq!(&buffer[..], &runes.to_bytes().unwrap()[..]);
+    assert!(matches!(
+        Runes::from_archive_bytes(&buffer[1..]),
+        Err(RunesError::InvalidRunesFile(_))
+    ));
+}
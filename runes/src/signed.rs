@@ -0,0 +1,134 @@
+//! Publisher signatures for rune archives.
+//!
+//! A signed `.runes` file is a regular archive followed by a fixed size trailer:
+//!
+//! ```text
+//! [ rkyv serialized Runes ][ key ids ][ publisher pubkey: 32 ][ ed25519 signature: 64 ][ SIGNED_RUNES_MAGIC: 8 ]
+//! ```
+//!
+//! The signature is over everything before the trailer, i.e. the serialized
+//! [`Runes`](crate::Runes) and their optional [key id section](crate::keys).
+//! [`archive_len`](crate::archive_len) strips both, leaving an archive that can be accessed
+//! through the usual [`ArchivedRunes`](crate::ArchivedRunes) API.
+
+/// Marks the end of a signed rune archive.
+pub const SIGNED_RUNES_MAGIC: [u8; 8] = *b"RUNESIG1";
+
+/// Length of the trailer appended to signed rune archives.
+pub const SIGNATURE_TRAILER_LEN: usize = 32 + 64 + SIGNED_RUNES_MAGIC.len();
+
+/// The publisher key and signature embedded in a signed rune archive.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RunesSignature {
+    pub publisher: [u8; 32],
+    pub signature: [u8; 64],
+}
+
+impl RunesSignature {
+    /// Serializes the signature into the trailer format.
+    pub fn to_trailer(&self) -> [u8; SIGNATURE_TRAILER_LEN] {
+        let mut trailer = [0; SIGNATURE_TRAILER_LEN];
+        trailer[..32].copy_from_slice(&self.publisher);
+        trailer[32..96].copy_from_slice(&self.signature);
+        trailer[96..].copy_from_slice(&SIGNED_RUNES_MAGIC);
+        trailer
+    }
+}
+
+/// Returns true if the bytes end with a signature trailer.
+pub const fn is_signed(bytes: &[u8]) -> bool {
+    if bytes.len() < SIGNATURE_TRAILER_LEN {
+        return false;
+    }
+    let offset = bytes.len() - SIGNED_RUNES_MAGIC.len();
+    let mut i = 0;
+    while i < SIGNED_RUNES_MAGIC.len() {
+        if bytes[offset + i] != SIGNED_RUNES_MAGIC[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Length of a possibly signed archive without its signature trailer.
+pub const fn archive_len(bytes: &[u8]) -> usize {
+    if is_signed(bytes) {
+        bytes.len() - SIGNATURE_TRAILER_LEN
+    } else {
+        bytes.len()
+    }
+}
+
+/// Splits a possibly signed archive into the signed bytes and the embedded
+/// signature, if any.
+pub fn split_signed(bytes: &[u8]) -> (&[u8], Option<RunesSignature>) {
+    if !is_signed(bytes) {
+        return (bytes, None);
+    }
+    let (archive, trailer) = bytes.split_at(bytes.len() - SIGNATURE_TRAILER_LEN);
+    let signature = RunesSignature {
+        publisher: trailer[..32].try_into().expect("trailer has fixed length"),
+        signature: trailer[32..96]
+            .try_into()
+            .expect("trailer has fixed length"),
+    };
+    (archive, Some(signature))
+}
+
+#[cfg(feature = "signing")]
+pub use ed25519_dalek;
+
+/// Signs an unsigned archive, as written by [`Runes::to_bytes`](crate::Runes::to_bytes),
+/// returning a signed archive.
+#[cfg(feature = "signing")]
+pub fn sign(archive: &[u8], publisher: &ed25519_dalek::Keypair) -> Vec<u8> {
+    use ed25519_dalek::Signer;
+
+    let signature = RunesSignature {
+        publisher: publisher.public.to_bytes(),
+        signature: publisher.sign(archive).to_bytes(),
+    };
+
+    let mut signed = Vec::with_capacity(archive.len() + SIGNATURE_TRAILER_LEN);
+    signed.extend_from_slice(archive);
+    signed.extend_from_slice(&signature.to_trailer());
+    signed
+}
+
+/// Verifies the signature of a signed archive. If `publisher` is provided, the
+/// archive must also be signed by that key.
+#[cfg(feature = "signing")]
+pub fn verify(
+    bytes: &[u8],
+    publisher: Option<&[u8; 32]>,
+) -> Result<RunesSignature, crate::RunesError> {
+    use crate::RunesError;
+    use ed25519_dalek::Verifier;
+
+    let (archive, signature) = split_signed(bytes);
+    let signature = signature.ok_or(RunesError::MissingSignature)?;
+    if let Some(publisher) = publisher {
+        if publisher != &signature.publisher {
+            return Err(RunesError::UnexpectedPublisher);
+        }
+    }
+
+    let public_key = ed25519_dalek::PublicKey::from_bytes(&signature.publisher)
+        .map_err(|_| RunesError::InvalidSignature)?;
+    let ed25519_signature = ed25519_dalek::Signature::from_bytes(&signature.signature)
+        .map_err(|_| RunesError::InvalidSignature)?;
+    public_key
+        .verify(archive, &ed25519_signature)
+        .map_err(|_| RunesError::InvalidSignature)?;
+
+    Ok(signature)
+}
+
+#[test]
+fn test_split_unsigned() {
+    let bytes = [1u8; 64];
+    assert!(!is_signed(&bytes));
+    assert_eq!(archive_len(&bytes), 64);
+    assert_eq!(split_signed(&bytes), (&bytes[..], None));
+}
@@ -0,0 +1,123 @@
+//! Bearer tokens for storage requests sent to an authenticated endpoint.
+//!
+//! Set an [`HttpAuth`] with
+//! [`ShadowDriveClientBuilder::http_auth`](crate::ShadowDriveClientBuilder::http_auth). Its
+//! token is only sent to its own origin, never to the public storage endpoint or CDN.
+
+use async_trait::async_trait;
+use reqwest::{
+    header::{HeaderValue, AUTHORIZATION},
+    Request, Url,
+};
+
+use crate::{error::Error, models::ShadowDriveResult};
+
+/// Supplies the bearer tokens for requests to one origin.
+#[async_trait]
+pub trait HttpAuth: Send + Sync {
+    /// URL of the authenticated endpoint. Requests get a token when their scheme, host and
+    /// port match it.
+    fn url(&self) -> &str;
+
+    /// The token to send. `rejected` is the token of a request that was rejected with 401,
+    /// in which case a new one should be returned.
+    async fn token(&self, rejected: Option<&str>) -> ShadowDriveResult<String>;
+}
+
+/// Whether `url` has the same origin as the endpoint of `auth`.
+pub(crate) fn authenticates(auth: &dyn HttpAuth, url: &Url) -> bool {
+    Url::parse(auth.url()).map_or(false, |endpoint| endpoint.origin() == url.origin())
+}
+
+pub(crate) fn with_bearer(mut request: Request, token: &str) -> ShadowDriveResult<Request> {
+    let value = HeaderValue::from_str(&format!("Bearer {}", token))
+        .map_err(|e| Error::AuthFailed(format!("invalid token: {}", e)))?;
+    request.headers_mut().insert(AUTHORIZATION, value);
+    Ok(request)
+}
+
+#[cfg(test)]
+struct TestAuth {
+    url: String,
+    rejected: std::sync::Mutex<Vec<Option<String>>>,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl HttpAuth for TestAuth {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    async fn token(&self, rejected: Option<&str>) -> ShadowDriveResult<String> {
+        let mut calls = self.rejected.lock().unwrap();
+        calls.push(rejected.map(str::to_string));
+        Ok(format!("token-{}", calls.len()))
+    }
+}
+
+#[test]
+fn test_authenticates() {
+    let auth = TestAuth {
+        url: "https://rpc.example.com/account".to_string(),
+        rejected: Default::default(),
+    };
+    for (url, expected) in [
+        ("https://rpc.example.com/upload", true),
+        ("https://rpc.example.com:443/upload", true),
+        ("http://rpc.example.com/upload", false),
+        ("https://rpc.example.com:8443/upload", false),
+        ("https://shdw-drive.genesysgo.net/upload", false),
+    ] {
+        assert_eq!(
+            authenticates(&auth, &Url::parse(url).unwrap()),
+            expected,
+            "{}",
+            url
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_http_auth() {
+    use std::sync::Arc;
+
+    use shadow_drive_mock::MockShadowDrive;
+    use solana_sdk::pubkey::Pubkey;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let storage_account = Pubkey::new_unique();
+
+    // Requests to the endpoint get a token, and a new one after a 401
+    let auth = Arc::new(TestAuth {
+        url: drive.endpoint(),
+        rejected: Default::default(),
+    });
+    let client = crate::client::test_client_builder(&drive)
+        .http_auth(auth.clone())
+        .build();
+    drive.fail_next("/list-objects", 1, 401);
+    assert!(client.list_objects(&storage_account).await.is_ok());
+    assert_eq!(drive.requests("/list-objects"), 2);
+    assert_eq!(
+        *auth.rejected.lock().unwrap(),
+        [None, Some("token-1".to_string())]
+    );
+
+    // Only once
+    drive.fail_next("/list-objects", 2, 401);
+    assert!(client.list_objects(&storage_account).await.is_err());
+    assert_eq!(drive.requests("/list-objects"), 4);
+
+    // Other origins never see the token
+    let auth = Arc::new(TestAuth {
+        url: "https://rpc.example.com".to_string(),
+        rejected: Default::default(),
+    });
+    let client = crate::client::test_client_builder(&drive)
+        .http_auth(auth.clone())
+        .build();
+    drive.fail_next("/list-objects", 1, 401);
+    assert!(client.list_objects(&storage_account).await.is_err());
+    assert!(auth.rejected.lock().unwrap().is_empty());
+}
@@ -0,0 +1,338 @@
+//! Blocking versions of [`ShadowDriveClient`](crate::ShadowDriveClient) and
+//! [`PortalClient`](crate::portal::PortalClient), for callers without an async runtime such as
+//! `anchor_client` test harnesses.
+//!
+//! Each client owns a single-threaded tokio runtime and blocks on the async client's futures.
+//! Like `reqwest::blocking`, these clients must not be used from within an async runtime.
+//!
+//! # Example
+//! ```
+//! # use shadow_drive_rust::{blocking::ShadowDriveClient, derived_addresses::storage_account};
+//! # use solana_sdk::signer::{keypair::read_keypair_file, Signer};
+//! #
+//! let keypair = read_keypair_file(KEYPAIR_PATH).expect("failed to load keypair at path");
+//! # let (storage_account_key, _) = storage_account(&keypair.pubkey(), 0);
+//! let shdw_drive_client = ShadowDriveClient::new(keypair, "https://ssc-dao.genesysgo.net");
+//! let files = shdw_drive_client.list_objects(&storage_account_key)?;
+//! ```
+
+use std::{pin::Pin, time::Duration};
+
+use byte_unit::Byte;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use runes::Rune;
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{
+    encryption::EncryptionKey,
+    estimate::Quote,
+    models::{storage_acct::StorageAcct, *},
+    portal::{self, DataToBeSummoned, PortalConfig, SummonOptions, SummonResponse},
+    GetObjectOptions, ListObjectsOptions, StorageAccountVersion, UploadOptions,
+};
+
+/// Generates a blocking method for each async method of the wrapped client.
+macro_rules! blocking_methods {
+    ($client:literal; $(fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty;)*) => {
+        $(
+            #[doc = concat!("Blocking version of [`", $client, "::", stringify!($name), "`](crate::", $client, "::", stringify!($name), ").")]
+            pub fn $name(&self $(, $arg: $ty)*) -> ShadowDriveResult<$ret> {
+                self.runtime.block_on(self.inner.$name($($arg),*))
+            }
+        )*
+    };
+}
+
+fn runtime() -> Runtime {
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to start tokio runtime")
+}
+
+/// A blocking [`ShadowDriveClient`](crate::ShadowDriveClient).
+pub struct ShadowDriveClient<T>
+where
+    T: Signer,
+{
+    inner: crate::ShadowDriveClient<T>,
+    runtime: Runtime,
+}
+
+impl<T> ShadowDriveClient<T>
+where
+    T: Signer,
+{
+    /// Creates a new blocking client, see [`ShadowDriveClient::new`](crate::ShadowDriveClient::new).
+    ///
+    /// # Panics
+    /// If the tokio runtime fails to start.
+    pub fn new<U: ToString>(wallet: T, rpc_url: U) -> Self {
+        Self::from_client(crate::ShadowDriveClient::new(wallet, rpc_url))
+    }
+
+    /// Wraps an async client, e.g. one made with
+    /// [`ShadowDriveClientBuilder`](crate::ShadowDriveClientBuilder).
+    ///
+    /// # Panics
+    /// If the tokio runtime fails to start.
+    pub fn from_client(inner: crate::ShadowDriveClient<T>) -> Self {
+        Self {
+            inner,
+            runtime: runtime(),
+        }
+    }
+
+    /// The wrapped async client, e.g. for its endpoint and address accessors.
+    pub fn inner(&self) -> &crate::ShadowDriveClient<T> {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> crate::ShadowDriveClient<T> {
+        self.inner
+    }
+
+    blocking_methods! {
+        "ShadowDriveClient";
+        fn get_object_data(&self, location: &str) -> FileDataResponse;
+        fn get_storage_price_and_min_account_size(&self) -> (u64, u64);
+        fn get_shdw_balance(&self) -> u64;
+        fn add_immutable_storage(&self, storage_account_key: &Pubkey, size: Byte) -> StorageResponse;
+        fn add_storage(&self, storage_account_key: &Pubkey, size: Byte) -> StorageResponse;
+        fn cancel_delete_storage_account(&self, storage_account_key: &Pubkey) -> ShdwDriveResponse;
+        fn claim_stake(&self, storage_account_key: &Pubkey) -> ShdwDriveResponse;
+        fn create_storage_account(
+            &self,
+            name: &str,
+            size: Byte,
+            version: StorageAccountVersion
+        ) -> CreateStorageAccountResponse;
+        fn delete_file(&self, storage_account_key: &Pubkey, url: String) -> DeleteFileResponse;
+        fn delete_storage_account(&self, storage_account_key: &Pubkey) -> ShdwDriveResponse;
+        fn edit_file(&self, storage_account_key: &Pubkey, data: ShadowFile) -> ShadowEditResponse;
+        fn get_object(&self, storage_account_key: &Pubkey, filename: &str) -> Bytes;
+        fn get_object_with_options(
+            &self,
+            storage_account_key: &Pubkey,
+            filename: &str,
+            options: GetObjectOptions
+        ) -> Bytes;
+        fn get_object_decrypted(
+            &self,
+            storage_account_key: &Pubkey,
+            filename: &str,
+            key: &EncryptionKey
+        ) -> Bytes;
+        fn get_storage_account(&self, key: &Pubkey) -> StorageAcct;
+        fn get_storage_accounts(&self, owner: &Pubkey) -> Vec<StorageAcct>;
+        fn get_storage_account_rpc(&self, key: &Pubkey) -> StorageAcct;
+        fn get_storage_accounts_rpc(&self, owner: &Pubkey) -> Vec<StorageAcct>;
+        fn get_file_accounts(&self, storage_account_key: &Pubkey) -> Vec<(Pubkey, FileAccount)>;
+        fn list_objects(&self, storage_account_key: &Pubkey) -> Vec<String>;
+        fn list_objects_detailed(
+            &self,
+            storage_account_key: &Pubkey,
+            options: ListObjectsOptions
+        ) -> ObjectListing;
+        fn make_storage_immutable(&self, storage_account_key: &Pubkey) -> StorageResponse;
+        fn migrate(&self, storage_account_key: &Pubkey) -> (ShdwDriveResponse, ShdwDriveResponse);
+        fn migrate_step_1(&self, storage_account_key: &Pubkey) -> ShdwDriveResponse;
+        fn migrate_step_2(&self, storage_account_key: &Pubkey) -> ShdwDriveResponse;
+        fn redeem_rent(
+            &self,
+            storage_account_key: &Pubkey,
+            file_account_key: &Pubkey
+        ) -> ShdwDriveResponse;
+        fn reduce_storage(&self, storage_account_key: &Pubkey, size: Byte) -> StorageResponse;
+        fn store_files(
+            &self,
+            storage_account_key: &Pubkey,
+            data: Vec<ShadowFile>
+        ) -> ShadowUploadResponse;
+        fn upload_files(
+            &self,
+            storage_account_key: &Pubkey,
+            files: Vec<ShadowFile>,
+            options: UploadOptions
+        ) -> Vec<ShadowBatchUploadResponse>;
+        fn estimate_create_storage_account(&self, size: Byte) -> Quote;
+        fn estimate_add_storage(&self, size: Byte) -> Quote;
+        fn estimate_store_files(&self, storage_account_key: &Pubkey, files: &[ShadowFile]) -> Quote;
+    }
+
+    /// Blocking version of [`ShadowDriveClient::get_object_stream`](crate::ShadowDriveClient::get_object_stream).
+    /// Each call to `next` blocks until the next chunk arrives.
+    pub fn get_object_stream(
+        &self,
+        storage_account_key: &Pubkey,
+        filename: &str,
+        options: GetObjectOptions,
+    ) -> ShadowDriveResult<impl Iterator<Item = ShadowDriveResult<Bytes>> + '_> {
+        let stream = self.runtime.block_on(self.inner.get_object_stream(
+            storage_account_key,
+            filename,
+            options,
+        ))?;
+
+        Ok(BlockingStream {
+            stream: Box::pin(stream),
+            runtime: &self.runtime,
+        })
+    }
+
+    /// Blocking version of [`ShadowDriveClient::list_objects_stream`](crate::ShadowDriveClient::list_objects_stream).
+    /// Each call to `next` blocks until the next object is listed.
+    pub fn list_objects_stream<'a>(
+        &'a self,
+        storage_account_key: &'a Pubkey,
+        options: ListObjectsOptions,
+    ) -> impl Iterator<Item = ShadowDriveResult<ObjectInfo>> + 'a {
+        BlockingStream {
+            stream: Box::pin(self.inner.list_objects_stream(storage_account_key, options)),
+            runtime: &self.runtime,
+        }
+    }
+
+    /// Blocking version of [`ShadowDriveClient::clear_object_cache`](crate::ShadowDriveClient::clear_object_cache).
+    pub fn clear_object_cache(&self) {
+        self.inner.clear_object_cache()
+    }
+}
+
+struct BlockingStream<'a, S> {
+    stream: Pin<Box<S>>,
+    runtime: &'a Runtime,
+}
+
+impl<S: Stream> Iterator for BlockingStream<'_, S> {
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+/// A blocking [`PortalClient`](crate::portal::PortalClient).
+pub struct PortalClient<T>
+where
+    T: Signer,
+{
+    inner: portal::PortalClient<T>,
+    runtime: Runtime,
+}
+
+impl<T> PortalClient<T>
+where
+    T: Signer,
+{
+    /// Creates a new blocking client, see [`PortalClient::new`](crate::portal::PortalClient::new).
+    ///
+    /// # Panics
+    /// If the tokio runtime fails to start.
+    pub fn new<U: ToString>(wallet: T, rpc_url: U) -> Self {
+        Self::from_client(portal::PortalClient::new(wallet, rpc_url))
+    }
+
+    /// Wraps an async client.
+    ///
+    /// # Panics
+    /// If the tokio runtime fails to start.
+    pub fn from_client(inner: portal::PortalClient<T>) -> Self {
+        Self {
+            inner,
+            runtime: runtime(),
+        }
+    }
+
+    /// The wrapped async client, e.g. for [`metadata_address`](portal::PortalClient::metadata_address).
+    pub fn inner(&self) -> &portal::PortalClient<T> {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> portal::PortalClient<T> {
+        self.inner
+    }
+
+    blocking_methods! {
+        "portal::PortalClient";
+        fn summon(
+            &self,
+            storage_account_key: &Pubkey,
+            filename: &str,
+            options: SummonOptions
+        ) -> SummonResponse;
+        fn summon_rune(
+            &self,
+            storage_account_key: &Pubkey,
+            rune: &Rune,
+            options: SummonOptions
+        ) -> SummonResponse;
+        fn get_summon(&self, metadata: &Pubkey) -> Option<DataToBeSummoned>;
+        fn wait_for_upload(&self, metadata: &Pubkey, timeout: Duration) -> DataToBeSummoned;
+        fn cancel(&self, metadata: &Pubkey) -> ShdwDriveResponse;
+        fn get_config(&self) -> PortalConfig;
+        fn list_summons(&self, summoner: &Pubkey) -> Vec<(Pubkey, DataToBeSummoned)>;
+        fn list_pending_summons(&self) -> Vec<(Pubkey, DataToBeSummoned)>;
+        fn fetch_summoned_data(&self, summon: &DataToBeSummoned) -> Bytes;
+        fn upload(
+            &self,
+            metadata: &Pubkey,
+            summon: &DataToBeSummoned,
+            data: Vec<u8>
+        ) -> ShdwDriveResponse;
+        fn init_config(&self) -> ShdwDriveResponse;
+        fn set_fee(&self, shades_per_byte: u64) -> ShdwDriveResponse;
+        fn estimate_summon(
+            &self,
+            storage_account_key: &Pubkey,
+            filename: &str,
+            options: &SummonOptions
+        ) -> Quote;
+    }
+}
+
+#[test]
+fn test_blocking_client() {
+    use shadow_drive_mock::MockShadowDrive;
+
+    // Without a runtime of the caller's, like in synchronous test harnesses
+    let drive = MockShadowDrive::start().unwrap();
+    let client = ShadowDriveClient::from_client(crate::client::test_client(&drive));
+    let storage_account = Pubkey::new_unique();
+
+    let files = vec![
+        ShadowFile::bytes("a.txt".to_string(), &b"alpha"[..]),
+        ShadowFile::bytes("b.txt".to_string(), &b"beta"[..]),
+    ];
+    let results = client
+        .upload_files(&storage_account, files, UploadOptions::default())
+        .unwrap();
+    assert!(results
+        .iter()
+        .all(|result| matches!(result.status, BatchUploadStatus::Uploaded)));
+
+    assert_eq!(
+        client.get_object(&storage_account, "a.txt").unwrap(),
+        &b"alpha"[..]
+    );
+    assert_eq!(
+        client.list_objects(&storage_account).unwrap(),
+        ["a.txt", "b.txt"]
+    );
+
+    // Streams block for each item
+    let chunks = client
+        .get_object_stream(&storage_account, "b.txt", GetObjectOptions::default())
+        .unwrap()
+        .collect::<ShadowDriveResult<Vec<_>>>()
+        .unwrap();
+    assert_eq!(chunks.concat(), b"beta");
+    let keys = client
+        .list_objects_stream(&storage_account, ListObjectsOptions::default())
+        .map(|object| object.map(|object| object.key))
+        .collect::<ShadowDriveResult<Vec<_>>>()
+        .unwrap();
+    assert_eq!(keys, ["a.txt", "b.txt"]);
+}
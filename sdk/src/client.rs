@@ -1,21 +1,24 @@
-use std::time::Duration;
+use std::sync::Arc;
 
 use anchor_lang::{prelude::Pubkey, AnchorDeserialize};
+use reqwest::{RequestBuilder, Response, StatusCode};
 use serde::de::DeserializeOwned;
-use serde_json::{json, Value};
+use serde_json::json;
 use shadow_drive_user_staking::instructions::initialize_config::StorageConfig;
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::{commitment_config::CommitmentConfig, signer::Signer, transaction::Transaction};
+use solana_sdk::{signer::Signer, transaction::Transaction};
 use spl_associated_token_account::get_associated_token_address;
 
 mod add_immutable_storage;
 mod add_storage;
+mod builder;
 mod cancel_delete_storage_account;
 mod claim_stake;
 mod create_storage_account;
 mod delete_file;
 mod delete_storage_account;
 mod edit_file;
+mod get_object;
 mod get_storage_account;
 mod list_objects;
 mod make_storage_immutable;
@@ -23,16 +26,18 @@ mod migrate;
 mod redeem_rent;
 mod reduce_storage;
 mod store_files;
-// mod upload_multiple_files;
+mod upload_files;
 
 pub use add_immutable_storage::*;
 pub use add_storage::*;
+pub use builder::*;
 pub use cancel_delete_storage_account::*;
 pub use claim_stake::*;
 pub use create_storage_account::*;
 pub use delete_file::*;
 pub use delete_storage_account::*;
 pub use edit_file::*;
+pub use get_object::*;
 pub use get_storage_account::*;
 pub use list_objects::*;
 pub use make_storage_immutable::*;
@@ -40,9 +45,13 @@ pub use migrate::*;
 pub use redeem_rent::*;
 pub use reduce_storage::*;
 pub use store_files::*;
+pub use upload_files::*;
+
+use list_objects::ObjectCache;
 
 use crate::{
-    constants::{SHDW_DRIVE_ENDPOINT, TOKEN_MINT},
+    auth::{authenticates, with_bearer, HttpAuth},
+    derived_addresses::DerivedAddresses,
     error::Error,
     models::{FileDataResponse, ShadowDriveResult},
 };
@@ -55,6 +64,15 @@ where
     wallet: T,
     rpc_client: RpcClient,
     http_client: reqwest::Client,
+    http_auth: Option<Arc<dyn HttpAuth>>,
+    storage_endpoint: String,
+    object_prefix: String,
+    program_id: Pubkey,
+    token_mint: Pubkey,
+    uploader: Pubkey,
+    emissions: Pubkey,
+    storage_config: Pubkey,
+    object_cache: Option<ObjectCache>,
 }
 
 impl<T> ShadowDriveClient<T>
@@ -70,6 +88,7 @@ where
     /// [cl]: https://docs.solana.com/developing/clients/jsonrpc-api#configuring-state-commitment
     ///
     /// To customize [`RpcClient`] settings see [`new_with_rpc`](Self::new_with_rpc).
+    /// To use a different Shadow Drive deployment see [`ShadowDriveClientBuilder`].
     ///
     /// # Example
     /// ```
@@ -79,16 +98,7 @@ where
     /// let shdw_drive = ShadowDriveClient::new(wallet, "https://ssc-dao.genesysgo.net");
     /// ```
     pub fn new<U: ToString>(wallet: T, rpc_url: U) -> Self {
-        let rpc_client = RpcClient::new_with_timeout_and_commitment(
-            rpc_url.to_string(),
-            Duration::from_secs(120),
-            CommitmentConfig::confirmed(),
-        );
-        Self {
-            wallet,
-            rpc_client,
-            http_client: reqwest::Client::new(),
-        }
+        ShadowDriveClientBuilder::new(wallet, rpc_url).build()
     }
 
     /// Creates a new [`ShadowDriveClient`] from the given [`Signer`] and [`RpcClient`].
@@ -108,27 +118,54 @@ where
     /// let shdw_drive = ShadowDriveClient::new_with_rpc(wallet, solana_rpc);
     /// ```
     pub fn new_with_rpc(wallet: T, rpc_client: RpcClient) -> Self {
-        Self {
-            wallet,
-            rpc_client,
-            http_client: reqwest::Client::new(),
+        ShadowDriveClientBuilder::new_with_rpc(wallet, rpc_client).build()
+    }
+
+    /// Sends a request made with `http_client`, with a token if it goes to the endpoint of
+    /// `http_auth`. A request rejected with 401 is sent once more with a new token, unless its
+    /// body is a stream, e.g. a multipart upload, which is returned as is.
+    pub(crate) async fn send_request(
+        &self,
+        request: RequestBuilder,
+    ) -> ShadowDriveResult<Response> {
+        let request = request.build()?;
+        let auth = match &self.http_auth {
+            Some(auth) if authenticates(auth.as_ref(), request.url()) => auth,
+            _ => return Ok(self.http_client.execute(request).await?),
+        };
+
+        let retry = request.try_clone();
+        let token = auth.token(None).await?;
+        let response = self
+            .http_client
+            .execute(with_bearer(request, &token)?)
+            .await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        // Fetch a new token either way, so that later requests use it
+        let token = auth.token(Some(&token)).await?;
+        match retry {
+            Some(request) => Ok(self
+                .http_client
+                .execute(with_bearer(request, &token)?)
+                .await?),
+            None => Ok(response),
         }
     }
 
     pub async fn get_object_data(&self, location: &str) -> ShadowDriveResult<FileDataResponse> {
         let response = self
-            .http_client
-            .post(format!("{}/get-object-data", SHDW_DRIVE_ENDPOINT))
-            .header("Content-Type", "application/json")
-            .json(&json!({ "location": location }))
-            .send()
+            .send_request(
+                self.http_client
+                    .post(format!("{}/get-object-data", self.storage_endpoint))
+                    .header("Content-Type", "application/json")
+                    .json(&json!({ "location": location })),
+            )
             .await?;
 
         if !response.status().is_success() {
-            return Err(Error::ShadowDriveServerError {
-                status: response.status().as_u16(),
-                message: response.json::<Value>().await?,
-            });
+            return Err(Error::from_response(response).await);
         }
 
         let response = response.json::<FileDataResponse>().await?;
@@ -148,18 +185,16 @@ where
         .map_err(Error::InvalidJson)?;
 
         let response = self
-            .http_client
-            .post(format!("{}/{}", SHDW_DRIVE_ENDPOINT, uri))
-            .header("Content-Type", "application/json")
-            .body(body)
-            .send()
+            .send_request(
+                self.http_client
+                    .post(format!("{}/{}", self.storage_endpoint, uri))
+                    .header("Content-Type", "application/json")
+                    .body(body),
+            )
             .await?;
 
         if !response.status().is_success() {
-            return Err(Error::ShadowDriveServerError {
-                status: response.status().as_u16(),
-                message: response.json::<Value>().await?,
-            });
+            return Err(Error::from_response(response).await);
         }
 
         let response = response.json::<K>().await?;
@@ -168,20 +203,19 @@ where
     }
 
     pub async fn get_storage_price_and_min_account_size(&self) -> ShadowDriveResult<(u64, u64)> {
-        let info_pubkey = Pubkey::find_program_address(
-            &["storage-config".as_bytes()],
-            &shadow_drive_user_staking::ID,
-        )
-        .0;
         let config = StorageConfig::deserialize(
-            &mut self.rpc_client.get_account_data(&info_pubkey).await?[8..].as_ref(),
+            &mut self
+                .rpc_client
+                .get_account_data(&self.storage_config)
+                .await?[8..]
+                .as_ref(),
         )?;
 
         Ok((config.shades_per_gib, config.min_account_size))
     }
 
     pub async fn get_shdw_balance(&self) -> ShadowDriveResult<u64> {
-        let shdw_pubkey = get_associated_token_address(&self.wallet.pubkey(), &TOKEN_MINT);
+        let shdw_pubkey = get_associated_token_address(&self.wallet.pubkey(), &self.token_mint);
         let balance: u64 = self
             .rpc_client
             .get_token_account_balance(&shdw_pubkey)
@@ -196,6 +230,48 @@ where
     pub fn rpc(&self) -> &RpcClient {
         &self.rpc_client
     }
+
+    pub(crate) fn wallet(&self) -> &T {
+        &self.wallet
+    }
+
+    /// Endpoint used for file uploads and fetching object data.
+    pub fn storage_endpoint(&self) -> &str {
+        &self.storage_endpoint
+    }
+
+    /// Prefix of the public URLs that stored objects are served from.
+    pub fn object_prefix(&self) -> &str {
+        &self.object_prefix
+    }
+
+    /// Address of the Shadow Drive program this client sends instructions to.
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    /// Address of the SHDW token mint used for storage payments.
+    pub fn token_mint(&self) -> &Pubkey {
+        &self.token_mint
+    }
+
+    /// Address of the upload authority for the Shadow Drive program.
+    pub fn uploader(&self) -> &Pubkey {
+        &self.uploader
+    }
+
+    /// Program derived addresses for the Shadow Drive program this client is configured with.
+    pub fn derived_addresses(&self) -> DerivedAddresses {
+        DerivedAddresses::new(self.program_id)
+    }
+
+    /// Returns the public URL of `filename` in the given storage account.
+    pub fn object_url(&self, storage_account_key: &Pubkey, filename: &str) -> String {
+        format!(
+            "{}/{}/{}",
+            self.object_prefix, storage_account_key, filename
+        )
+    }
 }
 
 pub(crate) fn serialize_and_encode(txn: &Transaction) -> ShadowDriveResult<String> {
@@ -203,3 +279,52 @@ pub(crate) fn serialize_and_encode(txn: &Transaction) -> ShadowDriveResult<Strin
         .map_err(|error| Error::TransactionSerializationFailed(format!("{:?}", error)))?;
     Ok(base64::encode(serialized))
 }
+
+/// A client builder for `drive` with a random wallet. Its RPC URL points at a
+/// closed port, so only storage API requests can succeed.
+#[cfg(test)]
+pub(crate) fn test_client_builder(
+    drive: &shadow_drive_mock::MockShadowDrive,
+) -> ShadowDriveClientBuilder<solana_sdk::signature::Keypair> {
+    ShadowDriveClientBuilder::new(solana_sdk::signature::Keypair::new(), "http://127.0.0.1:1")
+        .storage_endpoint(drive.endpoint())
+        .object_prefix(drive.object_prefix())
+}
+
+#[cfg(test)]
+pub(crate) fn test_client(
+    drive: &shadow_drive_mock::MockShadowDrive,
+) -> ShadowDriveClient<solana_sdk::signature::Keypair> {
+    test_client_builder(drive).build()
+}
+
+#[tokio::test]
+async fn test_get_object_data() {
+    use shadow_drive_mock::{MockShadowDrive, MockStorageAccount};
+
+    let drive = MockShadowDrive::start().unwrap();
+    let client = test_client(&drive);
+    let storage_account = Pubkey::new_unique();
+    let owner = client.wallet().pubkey();
+    drive.add_storage_account(MockStorageAccount::new(storage_account, owner));
+    drive.put_object(storage_account, "a.txt", b"a").unwrap();
+
+    let response = client
+        .get_object_data(&client.object_url(&storage_account, "a.txt"))
+        .await
+        .unwrap();
+    assert_eq!(response.file_data.owner_account_pubkey, owner.to_string());
+    assert_eq!(
+        response.file_data.storage_account_pubkey,
+        storage_account.to_string()
+    );
+
+    let error = client
+        .get_object_data(&client.object_url(&storage_account, "missing.txt"))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        Error::ShadowDriveServerError { status: 404, .. }
+    ));
+}
@@ -11,7 +11,6 @@ use spl_token::ID as TokenProgramID;
 
 use super::ShadowDriveClient;
 use crate::{
-    constants::{EMISSIONS, PROGRAM_ADDRESS, STORAGE_CONFIG_PDA, TOKEN_MINT, UPLOADER},
     error::Error,
     models::{
         storage_acct::{StorageAccount, StorageAccountV2, StorageAcct},
@@ -60,7 +59,7 @@ where
         let size_as_bytes: u64 = size
             .get_bytes()
             .try_into()
-            .map_err(|_| Error::InvalidStorage)?;
+            .map_err(|_| Error::InvalidStorage(size.get_bytes()))?;
 
         let selected_storage_acct = self.get_storage_account(storage_account_key).await?;
 
@@ -91,17 +90,17 @@ where
         size_as_bytes: u64,
     ) -> ShadowDriveResult<String> {
         let wallet_pubkey = &self.wallet.pubkey();
-        let owner_ata = get_associated_token_address(wallet_pubkey, &TOKEN_MINT);
-        let emissions_ata = get_associated_token_address(&EMISSIONS, &TOKEN_MINT);
+        let owner_ata = get_associated_token_address(wallet_pubkey, &self.token_mint);
+        let emissions_ata = get_associated_token_address(&self.emissions, &self.token_mint);
 
         let accounts = shdw_drive_accounts::IncreaseImmutableStorageV1 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             emissions_wallet: emissions_ata,
             owner: storage_account.owner_1,
             owner_ata,
-            uploader: UPLOADER,
-            token_mint: TOKEN_MINT,
+            uploader: self.uploader,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
             token_program: TokenProgramID,
         };
@@ -110,7 +109,7 @@ where
         };
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -134,17 +133,17 @@ where
         size_as_bytes: u64,
     ) -> ShadowDriveResult<String> {
         let wallet_pubkey = &self.wallet.pubkey();
-        let owner_ata = get_associated_token_address(wallet_pubkey, &TOKEN_MINT);
-        let emissions_ata = get_associated_token_address(&EMISSIONS, &TOKEN_MINT);
+        let owner_ata = get_associated_token_address(wallet_pubkey, &self.token_mint);
+        let emissions_ata = get_associated_token_address(&self.emissions, &self.token_mint);
 
         let accounts = shdw_drive_accounts::IncreaseImmutableStorageV2 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             emissions_wallet: emissions_ata,
             owner: storage_account.owner_1,
             owner_ata,
-            uploader: UPLOADER,
-            token_mint: TOKEN_MINT,
+            uploader: self.uploader,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
             token_program: TokenProgramID,
         };
@@ -154,7 +153,7 @@ where
         };
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
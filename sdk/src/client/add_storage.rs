@@ -15,8 +15,6 @@ use spl_token::ID as TokenProgramID;
 
 use super::ShadowDriveClient;
 use crate::{
-    constants::{PROGRAM_ADDRESS, STORAGE_CONFIG_PDA, TOKEN_MINT, UPLOADER},
-    derived_addresses,
     error::Error,
     models::{
         storage_acct::{StorageAccount, StorageAccountV2, StorageAcct},
@@ -66,10 +64,10 @@ where
         let size_as_bytes: u64 = size
             .get_bytes()
             .try_into()
-            .map_err(|_| Error::InvalidStorage)?;
+            .map_err(|_| Error::InvalidStorage(size.get_bytes()))?;
 
         let wallet_pubkey = self.wallet.pubkey();
-        let (user_info, _) = derived_addresses::user_info(&wallet_pubkey);
+        let (user_info, _) = self.derived_addresses().user_info(&wallet_pubkey);
 
         let user_info_acct = self.rpc_client.get_account(&user_info).await;
         match user_info_acct {
@@ -114,17 +112,17 @@ where
         size_as_bytes: u64,
     ) -> ShadowDriveResult<String> {
         let wallet_pubkey = &self.wallet.pubkey();
-        let owner_ata = get_associated_token_address(wallet_pubkey, &TOKEN_MINT);
-        let (stake_account, _) = derived_addresses::stake_account(storage_account_key);
+        let owner_ata = get_associated_token_address(wallet_pubkey, &self.token_mint);
+        let (stake_account, _) = self.derived_addresses().stake_account(storage_account_key);
 
         let accounts = shdw_drive_accounts::IncreaseStorageV1 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             owner: storage_account.owner_1,
             owner_ata,
             stake_account,
-            uploader: UPLOADER,
-            token_mint: TOKEN_MINT,
+            uploader: self.uploader,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
             token_program: TokenProgramID,
         };
@@ -133,7 +131,7 @@ where
         };
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -157,17 +155,17 @@ where
         size_as_bytes: u64,
     ) -> ShadowDriveResult<String> {
         let wallet_pubkey = &self.wallet.pubkey();
-        let owner_ata = get_associated_token_address(wallet_pubkey, &TOKEN_MINT);
-        let (stake_account, _) = derived_addresses::stake_account(storage_account_key);
+        let owner_ata = get_associated_token_address(wallet_pubkey, &self.token_mint);
+        let (stake_account, _) = self.derived_addresses().stake_account(storage_account_key);
 
         let accounts = shdw_drive_accounts::IncreaseStorageV2 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             owner: storage_account.owner_1,
             owner_ata,
             stake_account,
-            uploader: UPLOADER,
-            token_mint: TOKEN_MINT,
+            uploader: self.uploader,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
             token_program: TokenProgramID,
         };
@@ -177,7 +175,7 @@ where
         };
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -0,0 +1,174 @@
+use std::{sync::Arc, time::Duration};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signer::Signer};
+
+use super::{list_objects::ObjectCache, ShadowDriveClient};
+use crate::{
+    auth::HttpAuth,
+    cluster::Cluster,
+    constants::{
+        EMISSIONS, PROGRAM_ADDRESS, SHDW_DRIVE_ENDPOINT, SHDW_DRIVE_OBJECT_PREFIX, TOKEN_MINT,
+        UPLOADER,
+    },
+    derived_addresses::DerivedAddresses,
+};
+
+/// Builds a [`ShadowDriveClient`] that talks to a Shadow Drive deployment other than Mainnet,
+/// e.g. a devnet deployment, a staging server, or a local mock.
+///
+/// Every setting defaults to the Mainnet value in [`constants`](crate::constants).
+///
+/// # Example
+/// ```
+/// use shadow_drive_rust::{Pubkey, ShadowDriveClientBuilder};
+/// use solana_sdk::signer::keypair::Keypair;
+///
+/// let wallet = Keypair::generate();
+/// let shdw_drive = ShadowDriveClientBuilder::new(wallet, "http://localhost:8899")
+///     .storage_endpoint("http://localhost:8080")
+///     .object_prefix("http://localhost:8080/objects")
+///     .program_id(Pubkey::new_unique())
+///     .build();
+/// ```
+pub struct ShadowDriveClientBuilder<T>
+where
+    T: Signer,
+{
+    wallet: T,
+    rpc_client: RpcClient,
+    http_client: Option<reqwest::Client>,
+    http_auth: Option<Arc<dyn HttpAuth>>,
+    storage_endpoint: String,
+    object_prefix: String,
+    program_id: Pubkey,
+    token_mint: Pubkey,
+    uploader: Pubkey,
+    emissions: Pubkey,
+    object_cache_ttl: Option<Duration>,
+}
+
+impl<T> ShadowDriveClientBuilder<T>
+where
+    T: Signer,
+{
+    /// Starts building a client from the given [`Signer`] and RPC URL.
+    /// The RPC client is configured the same way as in [`ShadowDriveClient::new`].
+    pub fn new<U: ToString>(wallet: T, rpc_url: U) -> Self {
+        let rpc_client = RpcClient::new_with_timeout_and_commitment(
+            rpc_url.to_string(),
+            Duration::from_secs(120),
+            CommitmentConfig::confirmed(),
+        );
+        Self::new_with_rpc(wallet, rpc_client)
+    }
+
+    /// Starts building a client from the given [`Signer`] and [`RpcClient`].
+    pub fn new_with_rpc(wallet: T, rpc_client: RpcClient) -> Self {
+        Self {
+            wallet,
+            rpc_client,
+            http_client: None,
+            http_auth: None,
+            storage_endpoint: SHDW_DRIVE_ENDPOINT.to_string(),
+            object_prefix: SHDW_DRIVE_OBJECT_PREFIX.to_string(),
+            program_id: PROGRAM_ADDRESS,
+            token_mint: TOKEN_MINT,
+            uploader: UPLOADER,
+            emissions: EMISSIONS,
+            object_cache_ttl: None,
+        }
+    }
+
+    /// Endpoint used for file uploads and fetching object data.
+    /// Defaults to [`SHDW_DRIVE_ENDPOINT`].
+    pub fn storage_endpoint<U: ToString>(mut self, storage_endpoint: U) -> Self {
+        self.storage_endpoint = storage_endpoint
+            .to_string()
+            .trim_end_matches('/')
+            .to_string();
+        self
+    }
+
+    /// Prefix of the public URLs that stored objects are served from.
+    /// Defaults to [`SHDW_DRIVE_OBJECT_PREFIX`].
+    pub fn object_prefix<U: ToString>(mut self, object_prefix: U) -> Self {
+        self.object_prefix = object_prefix.to_string().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Address of the Shadow Drive program. Defaults to [`PROGRAM_ADDRESS`].
+    pub fn program_id(mut self, program_id: Pubkey) -> Self {
+        self.program_id = program_id;
+        self
+    }
+
+    /// Address of the SHDW token mint. Defaults to [`TOKEN_MINT`].
+    pub fn token_mint(mut self, token_mint: Pubkey) -> Self {
+        self.token_mint = token_mint;
+        self
+    }
+
+    /// Address of the upload authority. Defaults to [`UPLOADER`].
+    pub fn uploader(mut self, uploader: Pubkey) -> Self {
+        self.uploader = uploader;
+        self
+    }
+
+    /// Owner of the token account that receives fees for immutable storage.
+    /// Defaults to [`EMISSIONS`].
+    pub fn emissions(mut self, emissions: Pubkey) -> Self {
+        self.emissions = emissions;
+        self
+    }
+
+    /// Sets the storage endpoint, object prefix and addresses from a [`Cluster`] profile.
+    /// The RPC URL is the one the builder was started with.
+    pub fn cluster(self, cluster: &Cluster) -> Self {
+        self.storage_endpoint(&cluster.storage_endpoint)
+            .object_prefix(&cluster.object_prefix)
+            .program_id(cluster.program_id)
+            .token_mint(cluster.token_mint)
+            .uploader(cluster.uploader)
+            .emissions(cluster.emissions)
+    }
+
+    /// HTTP client used for requests to the storage endpoint, e.g. to configure timeouts,
+    /// proxies or default headers. Defaults to [`reqwest::Client::new`].
+    pub fn http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    /// Sends bearer tokens from `http_auth` with the requests that go to its endpoint, and
+    /// fetches a new token once when one is rejected with 401.
+    pub fn http_auth(mut self, http_auth: Arc<dyn HttpAuth>) -> Self {
+        self.http_auth = Some(http_auth);
+        self
+    }
+
+    /// Cache the listings fetched by
+    /// [`list_objects_detailed`](ShadowDriveClient::list_objects_detailed) for `ttl`.
+    /// Listings are not cached by default.
+    pub fn object_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.object_cache_ttl = Some(ttl);
+        self
+    }
+
+    pub fn build(self) -> ShadowDriveClient<T> {
+        ShadowDriveClient {
+            wallet: self.wallet,
+            rpc_client: self.rpc_client,
+            http_client: self.http_client.unwrap_or_default(),
+            http_auth: self.http_auth,
+            storage_endpoint: self.storage_endpoint,
+            object_prefix: self.object_prefix,
+            program_id: self.program_id,
+            token_mint: self.token_mint,
+            uploader: self.uploader,
+            emissions: self.emissions,
+            storage_config: DerivedAddresses::new(self.program_id).storage_config(),
+            object_cache: self.object_cache_ttl.map(ObjectCache::new),
+        }
+    }
+}
@@ -8,13 +8,9 @@ use solana_sdk::{
 };
 
 use super::ShadowDriveClient;
-use crate::{
-    constants::{PROGRAM_ADDRESS, STORAGE_CONFIG_PDA, TOKEN_MINT},
-    derived_addresses,
-    models::{
-        storage_acct::{StorageAccount, StorageAccountV2, StorageAcct},
-        *,
-    },
+use crate::models::{
+    storage_acct::{StorageAccount, StorageAccountV2, StorageAcct},
+    *,
 };
 
 impl<T> ShadowDriveClient<T>
@@ -74,21 +70,21 @@ where
         storage_account_key: &Pubkey,
         storage_account: StorageAccount,
     ) -> ShadowDriveResult<Transaction> {
-        let (stake_account, _) = derived_addresses::stake_account(storage_account_key);
+        let (stake_account, _) = self.derived_addresses().stake_account(storage_account_key);
 
         let accounts = shdw_drive_accounts::UnmarkDeleteAccountV1 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             stake_account,
             owner: storage_account.owner_1,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
         };
 
         let args = UnmarkDeleteAccount {};
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -108,21 +104,21 @@ where
         storage_account_key: &Pubkey,
         storage_account: StorageAccountV2,
     ) -> ShadowDriveResult<Transaction> {
-        let (stake_account, _) = derived_addresses::stake_account(storage_account_key);
+        let (stake_account, _) = self.derived_addresses().stake_account(storage_account_key);
 
         let accounts = shdw_drive_accounts::UnmarkDeleteAccountV2 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             stake_account,
             owner: storage_account.owner_1,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
         };
 
         let args = UnmarkDeleteAccount2 {};
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
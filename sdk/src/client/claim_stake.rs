@@ -9,13 +9,9 @@ use spl_associated_token_account::get_associated_token_address;
 use spl_token::ID as TokenProgramID;
 
 use super::ShadowDriveClient;
-use crate::{
-    constants::{PROGRAM_ADDRESS, STORAGE_CONFIG_PDA, TOKEN_MINT},
-    derived_addresses::*,
-    models::{
-        storage_acct::{StorageAccount, StorageAccountV2, StorageAcct},
-        *,
-    },
+use crate::models::{
+    storage_acct::{StorageAccount, StorageAccountV2, StorageAcct},
+    *,
 };
 
 impl<T> ShadowDriveClient<T>
@@ -76,18 +72,21 @@ where
         storage_account: StorageAccount,
     ) -> ShadowDriveResult<Transaction> {
         let wallet_pubkey = self.wallet.pubkey();
-        let unstake_account = unstake_account(storage_account_key).0;
-        let unstake_info_account = unstake_info(storage_account_key).0;
-        let owner_ata = get_associated_token_address(&wallet_pubkey, &TOKEN_MINT);
+        let unstake_account = self
+            .derived_addresses()
+            .unstake_account(storage_account_key)
+            .0;
+        let unstake_info_account = self.derived_addresses().unstake_info(storage_account_key).0;
+        let owner_ata = get_associated_token_address(&wallet_pubkey, &self.token_mint);
 
         let accounts = shdw_drive_accounts::ClaimStakeV1 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             unstake_info: unstake_info_account,
             unstake_account,
             owner: storage_account.owner_1,
             owner_ata,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
             token_program: TokenProgramID,
         };
@@ -95,7 +94,7 @@ where
         let args = shdw_drive_instructions::ClaimStake {};
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -116,18 +115,21 @@ where
         storage_account: StorageAccountV2,
     ) -> ShadowDriveResult<Transaction> {
         let wallet_pubkey = self.wallet.pubkey();
-        let unstake_account = unstake_account(storage_account_key).0;
-        let unstake_info_account = unstake_info(storage_account_key).0;
-        let owner_ata = get_associated_token_address(&wallet_pubkey, &TOKEN_MINT);
+        let unstake_account = self
+            .derived_addresses()
+            .unstake_account(storage_account_key)
+            .0;
+        let unstake_info_account = self.derived_addresses().unstake_info(storage_account_key).0;
+        let owner_ata = get_associated_token_address(&wallet_pubkey, &self.token_mint);
 
         let accounts = shdw_drive_accounts::ClaimStakeV2 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             unstake_info: unstake_info_account,
             unstake_account,
             owner: storage_account.owner_1,
             owner_ata,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
             token_program: TokenProgramID,
         };
@@ -135,7 +137,7 @@ where
         let args = shdw_drive_instructions::ClaimStake2 {};
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
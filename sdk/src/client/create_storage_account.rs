@@ -1,6 +1,6 @@
 use anchor_lang::{system_program, AccountDeserialize, InstructionData, ToAccountMetas};
 use byte_unit::Byte;
-use serde_json::{json, Value};
+use serde_json::json;
 use shadow_drive_user_staking::{
     accounts as shdw_drive_accounts,
     instruction::{InitializeAccount, InitializeAccount2},
@@ -18,13 +18,7 @@ use spl_associated_token_account::get_associated_token_address;
 use spl_token::ID as TokenProgram;
 
 use super::ShadowDriveClient;
-use crate::{
-    constants::{PROGRAM_ADDRESS, SHDW_DRIVE_ENDPOINT, STORAGE_CONFIG_PDA, TOKEN_MINT, UPLOADER},
-    derived_addresses,
-    error::Error,
-    models::*,
-    serialize_and_encode,
-};
+use crate::{error::Error, models::*, serialize_and_encode};
 
 pub enum StorageAccountVersion {
     V1 { owner_2: Option<Pubkey> },
@@ -67,7 +61,7 @@ where
 
         let rpc_client = &self.rpc_client;
 
-        let (user_info, _) = derived_addresses::user_info(&wallet_pubkey);
+        let (user_info, _) = self.derived_addresses().user_info(&wallet_pubkey);
 
         // If userInfo hasn't been initialized, default to 0 for account seed
         let user_info_acct = rpc_client.get_account(&user_info).await;
@@ -95,7 +89,7 @@ where
         let storage_requested: u64 = size
             .get_bytes()
             .try_into()
-            .map_err(|_| Error::InvalidStorage)?;
+            .map_err(|_| Error::InvalidStorage(size.get_bytes()))?;
 
         let txn_encoded = match version {
             StorageAccountVersion::V1 { owner_2 } => {
@@ -111,18 +105,16 @@ where
         let body = serde_json::to_string(&json!({ "transaction": txn_encoded })).unwrap();
 
         let response = self
-            .http_client
-            .post(format!("{}/storage-account", SHDW_DRIVE_ENDPOINT))
-            .header("Content-Type", "application/json")
-            .body(body)
-            .send()
+            .send_request(
+                self.http_client
+                    .post(format!("{}/storage-account", self.storage_endpoint))
+                    .header("Content-Type", "application/json")
+                    .body(body),
+            )
             .await?;
 
         if !response.status().is_success() {
-            return Err(Error::ShadowDriveServerError {
-                status: response.status().as_u16(),
-                message: response.json::<Value>().await?,
-            });
+            return Err(Error::from_response(response).await);
         }
 
         let response = response.json::<CreateStorageAccountResponse>().await?;
@@ -140,20 +132,22 @@ where
     ) -> ShadowDriveResult<String> {
         let wallet_pubkey = self.wallet.pubkey();
 
-        let (storage_account, _) = derived_addresses::storage_account(&wallet_pubkey, account_seed);
+        let (storage_account, _) = self
+            .derived_addresses()
+            .storage_account(&wallet_pubkey, account_seed);
 
-        let (stake_account, _) = derived_addresses::stake_account(&storage_account);
+        let (stake_account, _) = self.derived_addresses().stake_account(&storage_account);
 
-        let owner_ata = get_associated_token_address(&wallet_pubkey, &TOKEN_MINT);
+        let owner_ata = get_associated_token_address(&wallet_pubkey, &self.token_mint);
 
         let accounts = shdw_drive_accounts::InitializeStorageAccountV1 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             user_info,
             storage_account,
             stake_account,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             owner_1: wallet_pubkey,
-            uploader: UPLOADER,
+            uploader: self.uploader,
             owner_1_token_account: owner_ata,
             system_program: system_program::ID,
             token_program: TokenProgram,
@@ -167,7 +161,7 @@ where
         };
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -193,20 +187,22 @@ where
     ) -> ShadowDriveResult<String> {
         let wallet_pubkey = self.wallet.pubkey();
 
-        let (storage_account, _) = derived_addresses::storage_account(&wallet_pubkey, account_seed);
+        let (storage_account, _) = self
+            .derived_addresses()
+            .storage_account(&wallet_pubkey, account_seed);
 
-        let (stake_account, _) = derived_addresses::stake_account(&storage_account);
+        let (stake_account, _) = self.derived_addresses().stake_account(&storage_account);
 
-        let owner_ata = get_associated_token_address(&wallet_pubkey, &TOKEN_MINT);
+        let owner_ata = get_associated_token_address(&wallet_pubkey, &self.token_mint);
 
         let accounts = shdw_drive_accounts::InitializeStorageAccountV2 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             user_info,
             storage_account,
             stake_account,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             owner_1: wallet_pubkey,
-            uploader: UPLOADER,
+            uploader: self.uploader,
             owner_1_token_account: owner_ata,
             system_program: system_program::ID,
             token_program: TokenProgram,
@@ -219,7 +215,7 @@ where
         };
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -1,8 +1,8 @@
-use serde_json::{json, Value};
+use serde_json::json;
 use solana_sdk::{pubkey::Pubkey, signer::Signer};
 
 use super::ShadowDriveClient;
-use crate::{constants::SHDW_DRIVE_ENDPOINT, error::Error, models::*};
+use crate::{error::Error, models::*};
 
 impl<T> ShadowDriveClient<T>
 where
@@ -56,17 +56,16 @@ where
         });
 
         let response = self
-            .http_client
-            .post(format!("{}/delete-file", SHDW_DRIVE_ENDPOINT))
-            .json(&body)
-            .send()
+            .send_request(
+                self.http_client
+                    .post(format!("{}/delete-file", self.storage_endpoint))
+                    .json(&body),
+            )
             .await?;
+        self.invalidate_object_cache(storage_account_key);
 
         if !response.status().is_success() {
-            return Err(Error::ShadowDriveServerError {
-                status: response.status().as_u16(),
-                message: response.json::<Value>().await?,
-            });
+            return Err(Error::from_response(response).await);
         }
 
         let response = response.json::<DeleteFileResponse>().await?;
@@ -81,3 +80,29 @@ fn delete_file_message(storage_account_key: &Pubkey, url: &str) -> String {
         storage_account_key, url
     )
 }
+
+#[tokio::test]
+async fn test_delete_file() {
+    use shadow_drive_mock::{MockShadowDrive, MockStorageAccount};
+
+    let drive = MockShadowDrive::start().unwrap();
+    let client = super::test_client(&drive);
+    let storage_account = Pubkey::new_unique();
+    drive.add_storage_account(MockStorageAccount::new(
+        storage_account,
+        client.wallet.pubkey(),
+    ));
+    drive.put_object(storage_account, "a.txt", b"a").unwrap();
+    let url = client.object_url(&storage_account, "a.txt");
+
+    // Only the owner may delete
+    let other = super::test_client(&drive);
+    let error = other
+        .delete_file(&storage_account, url.clone())
+        .await
+        .unwrap_err();
+    assert!(matches!(error, Error::NotFileOwner), "{error:?}");
+
+    client.delete_file(&storage_account, url).await.unwrap();
+    assert!(drive.get_object(storage_account, "a.txt").is_err());
+}
@@ -7,12 +7,9 @@ use solana_sdk::{
 };
 
 use super::ShadowDriveClient;
-use crate::{
-    constants::{PROGRAM_ADDRESS, STORAGE_CONFIG_PDA, TOKEN_MINT},
-    models::{
-        storage_acct::{StorageAccount, StorageAccountV2, StorageAcct},
-        ShadowDriveResult, ShdwDriveResponse,
-    },
+use crate::models::{
+    storage_acct::{StorageAccount, StorageAccountV2, StorageAcct},
+    ShadowDriveResult, ShdwDriveResponse,
 };
 
 impl<T> ShadowDriveClient<T>
@@ -77,16 +74,16 @@ where
         storage_account: StorageAccount,
     ) -> ShadowDriveResult<Transaction> {
         let accounts = shdw_drive_accounts::RequestDeleteAccountV1 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             owner: storage_account.owner_1,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
         };
         let args = shdw_drive_instructions::RequestDeleteAccount {};
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -107,17 +104,17 @@ where
         storage_account: StorageAccountV2,
     ) -> ShadowDriveResult<Transaction> {
         let accounts = shdw_drive_accounts::RequestDeleteAccountV2 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             owner: storage_account.owner_1,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
         };
 
         let args = shdw_drive_instructions::RequestDeleteAccount2 {};
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -1,13 +1,8 @@
 use reqwest::multipart::{Form, Part};
-use serde_json::Value;
 use solana_sdk::{pubkey::Pubkey, signer::Signer};
 
 use super::ShadowDriveClient;
-use crate::{
-    constants::{SHDW_DRIVE_ENDPOINT, SHDW_DRIVE_OBJECT_PREFIX},
-    error::Error,
-    models::*,
-};
+use crate::{error::Error, models::*};
 
 impl<T> ShadowDriveClient<T>
 where
@@ -54,12 +49,7 @@ where
             .sign_message(message_to_sign.as_bytes())
             .to_string();
 
-        let url = format!(
-            "{}/{}/{}",
-            SHDW_DRIVE_OBJECT_PREFIX,
-            storage_account_key,
-            data.name()
-        );
+        let url = self.object_url(storage_account_key, data.name());
 
         let form = Form::new()
             .part("file", data.into_form_part().await?)
@@ -72,17 +62,16 @@ where
             .part("url", Part::text(url));
 
         let response = self
-            .http_client
-            .post(format!("{}/edit", SHDW_DRIVE_ENDPOINT))
-            .multipart(form)
-            .send()
+            .send_request(
+                self.http_client
+                    .post(format!("{}/edit", self.storage_endpoint))
+                    .multipart(form),
+            )
             .await?;
+        self.invalidate_object_cache(storage_account_key);
 
         if !response.status().is_success() {
-            return Err(Error::ShadowDriveServerError {
-                status: response.status().as_u16(),
-                message: response.json::<Value>().await?,
-            });
+            return Err(Error::from_response(response).await);
         }
 
         let response = response.json::<ShadowEditResponse>().await?;
@@ -97,3 +86,36 @@ fn edit_message(storage_account_key: &Pubkey, filename: &str, new_hash: &str) ->
         storage_account_key, filename, new_hash
     )
 }
+
+#[tokio::test]
+async fn test_edit_file() {
+    use shadow_drive_mock::{MockShadowDrive, MockStorageAccount};
+
+    let drive = MockShadowDrive::start().unwrap();
+    let client = super::test_client(&drive);
+    let storage_account = Pubkey::new_unique();
+    drive.add_storage_account(MockStorageAccount::new(
+        storage_account,
+        client.wallet.pubkey(),
+    ));
+    drive.put_object(storage_account, "a.txt", b"a").unwrap();
+
+    let file = ShadowFile::bytes(String::from("a.txt"), &b"edited"[..]);
+    let response = client.edit_file(&storage_account, file).await.unwrap();
+    assert_eq!(
+        response.finalized_location,
+        client.object_url(&storage_account, "a.txt")
+    );
+    assert_eq!(
+        drive.get_object(storage_account, "a.txt").unwrap(),
+        b"edited"
+    );
+
+    // Only existing files can be edited
+    let file = ShadowFile::bytes(String::from("b.txt"), &b"b"[..]);
+    let error = client.edit_file(&storage_account, file).await.unwrap_err();
+    assert!(
+        matches!(error, Error::ShadowDriveServerError { status: 404, .. }),
+        "{error:?}"
+    );
+}
@@ -0,0 +1,420 @@
+use std::ops::{Bound, RangeBounds};
+
+use bytes::Bytes;
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
+use reqwest::{header::RANGE, Response, StatusCode};
+use sha2::{Digest, Sha256};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+
+use super::ShadowDriveClient;
+use crate::{encryption::EncryptionKey, error::Error, models::*};
+
+/// Options for [`get_object_with_options`](ShadowDriveClient::get_object_with_options)
+/// and [`get_object_stream`](ShadowDriveClient::get_object_stream).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GetObjectOptions {
+    start: u64,
+    /// Exclusive
+    end: Option<u64>,
+    sha256: Option<[u8; 32]>,
+}
+
+impl GetObjectOptions {
+    /// Only fetch the given byte range of the object, e.g. `0..1024` or `1024..`.
+    pub fn range<R: RangeBounds<u64>>(mut self, range: R) -> Self {
+        self.start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        self.end = match range.end_bound() {
+            Bound::Included(end) => Some(end + 1),
+            Bound::Excluded(end) => Some(*end),
+            Bound::Unbounded => None,
+        };
+        self
+    }
+
+    /// Fail unless the fetched bytes hash to `sha256`, e.g. the hash stored in a rune.
+    /// When a range is set, only the bytes in the range are hashed.
+    pub fn sha256(mut self, sha256: [u8; 32]) -> Self {
+        self.sha256 = Some(sha256);
+        self
+    }
+
+    fn is_ranged(&self) -> bool {
+        self.start > 0 || self.end.is_some()
+    }
+
+    /// Value of the HTTP `Range` header, if a range is set.
+    fn range_header(&self) -> ShadowDriveResult<Option<String>> {
+        match self.end {
+            Some(end) if end <= self.start => Err(Error::InvalidRange {
+                start: self.start,
+                end,
+            }),
+            Some(end) => Ok(Some(format!("bytes={}-{}", self.start, end - 1))),
+            None if self.start > 0 => Ok(Some(format!("bytes={}-", self.start))),
+            None => Ok(None),
+        }
+    }
+
+    /// Cuts the range out of `chunk`, which starts at `offset` in the object.
+    fn slice(&self, chunk: Bytes, offset: u64) -> Bytes {
+        let len = chunk.len() as u64;
+        let from = self.start.saturating_sub(offset).min(len);
+        let to = self
+            .end
+            .map_or(len, |end| end.saturating_sub(offset).min(len))
+            .max(from);
+        chunk.slice(from as usize..to as usize)
+    }
+}
+
+fn verify_sha256(expected: [u8; 32], hasher: Sha256) -> ShadowDriveResult<()> {
+    let actual: [u8; 32] = hasher.finalize().into();
+    if actual != expected {
+        return Err(Error::ObjectHashMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+impl<T> ShadowDriveClient<T>
+where
+    T: Signer,
+{
+    /// Downloads an object from the Shadow Drive.
+    /// * `storage_account_key` - The public key of the [`StorageAccount`](crate::models::StorageAccount) that contains the file.
+    /// * `filename` - The name of the file.
+    ///
+    /// To fetch part of the object or verify its hash see [`get_object_with_options`](Self::get_object_with_options).
+    /// # Example
+    ///
+    /// ```
+    /// # use shadow_drive_rust::{ShadowDriveClient, derived_addresses::storage_account};
+    /// # use solana_client::rpc_client::RpcClient;
+    /// # use solana_sdk::{
+    /// # pubkey::Pubkey,
+    /// # signature::Keypair,
+    /// # signer::{keypair::read_keypair_file, Signer},
+    /// # };
+    /// #
+    /// # let keypair = read_keypair_file(KEYPAIR_PATH).expect("failed to load keypair at path");
+    /// # let user_pubkey = keypair.pubkey();
+    /// # let rpc_client = RpcClient::new("https://ssc-dao.genesysgo.net");
+    /// # let shdw_drive_client = ShadowDriveClient::new(keypair, rpc_client);
+    /// # let (storage_account_key, _) = storage_account(&user_pubkey, 0);
+    /// #
+    /// let data = shdw_drive_client
+    ///     .get_object(&storage_account_key, "example.png")
+    ///     .await?;
+    /// ```
+    pub async fn get_object(
+        &self,
+        storage_account_key: &Pubkey,
+        filename: &str,
+    ) -> ShadowDriveResult<Bytes> {
+        self.get_object_with_options(storage_account_key, filename, GetObjectOptions::default())
+            .await
+    }
+
+    /// Downloads an object, or a byte range of it, from the Shadow Drive and optionally checks its SHA-256 hash.
+    /// * `storage_account_key` - The public key of the [`StorageAccount`](crate::models::StorageAccount) that contains the file.
+    /// * `filename` - The name of the file.
+    /// * `options` - The byte range to fetch and the expected hash.
+    /// # Example
+    ///
+    /// ```
+    /// # use shadow_drive_rust::{ShadowDriveClient, GetObjectOptions, derived_addresses::storage_account};
+    /// # use solana_client::rpc_client::RpcClient;
+    /// # use solana_sdk::{
+    /// # pubkey::Pubkey,
+    /// # signature::Keypair,
+    /// # signer::{keypair::read_keypair_file, Signer},
+    /// # };
+    /// #
+    /// # let keypair = read_keypair_file(KEYPAIR_PATH).expect("failed to load keypair at path");
+    /// # let user_pubkey = keypair.pubkey();
+    /// # let rpc_client = RpcClient::new("https://ssc-dao.genesysgo.net");
+    /// # let shdw_drive_client = ShadowDriveClient::new(keypair, rpc_client);
+    /// # let (storage_account_key, _) = storage_account(&user_pubkey, 0);
+    /// # let rune_hash = [0u8; 32];
+    /// #
+    /// let data = shdw_drive_client
+    ///     .get_object_with_options(
+    ///         &storage_account_key,
+    ///         "example.png",
+    ///         GetObjectOptions::default().sha256(rune_hash),
+    ///     )
+    ///     .await?;
+    /// ```
+    pub async fn get_object_with_options(
+        &self,
+        storage_account_key: &Pubkey,
+        filename: &str,
+        options: GetObjectOptions,
+    ) -> ShadowDriveResult<Bytes> {
+        let response = self
+            .request_object(storage_account_key, filename, &options)
+            .await?;
+        let partial = response.status() == StatusCode::PARTIAL_CONTENT;
+        let mut data = response.bytes().await?;
+
+        // The server ignored the range and sent the whole object
+        if options.is_ranged() && !partial {
+            data = options.slice(data, 0);
+        }
+
+        if let Some(expected) = options.sha256 {
+            let mut hasher = Sha256::new();
+            hasher.update(&data);
+            verify_sha256(expected, hasher)?;
+        }
+
+        Ok(data)
+    }
+
+    /// Downloads an object stored with [`ShadowFile::encrypted`] and decrypts it with `key`.
+    /// * `storage_account_key` - The public key of the [`StorageAccount`](crate::models::StorageAccount) that contains the file.
+    /// * `filename` - The name of the file.
+    /// * `key` - The key the file was encrypted with.
+    ///
+    /// Fails with [`Error::WrongEncryptionKey`] if the file was encrypted with another key.
+    pub async fn get_object_decrypted(
+        &self,
+        storage_account_key: &Pubkey,
+        filename: &str,
+        key: &EncryptionKey,
+    ) -> ShadowDriveResult<Bytes> {
+        let data = self.get_object(storage_account_key, filename).await?;
+        key.decrypt(filename, &data).map(Bytes::from)
+    }
+
+    /// Streams an object, or a byte range of it, from the Shadow Drive without buffering it in memory.
+    /// * `storage_account_key` - The public key of the [`StorageAccount`](crate::models::StorageAccount) that contains the file.
+    /// * `filename` - The name of the file.
+    /// * `options` - The byte range to fetch and the expected hash.
+    ///
+    /// If a hash is expected and does not match, the last item of the stream is an [`Error::ObjectHashMismatch`].
+    /// Data yielded before it must not be trusted until the stream ends.
+    /// # Example
+    ///
+    /// ```
+    /// # use futures::StreamExt;
+    /// # use shadow_drive_rust::{ShadowDriveClient, GetObjectOptions, derived_addresses::storage_account};
+    /// # use solana_client::rpc_client::RpcClient;
+    /// # use solana_sdk::{
+    /// # pubkey::Pubkey,
+    /// # signature::Keypair,
+    /// # signer::{keypair::read_keypair_file, Signer},
+    /// # };
+    /// #
+    /// # let keypair = read_keypair_file(KEYPAIR_PATH).expect("failed to load keypair at path");
+    /// # let user_pubkey = keypair.pubkey();
+    /// # let rpc_client = RpcClient::new("https://ssc-dao.genesysgo.net");
+    /// # let shdw_drive_client = ShadowDriveClient::new(keypair, rpc_client);
+    /// # let (storage_account_key, _) = storage_account(&user_pubkey, 0);
+    /// #
+    /// let mut stream = shdw_drive_client
+    ///     .get_object_stream(
+    ///         &storage_account_key,
+    ///         "video.mp4",
+    ///         GetObjectOptions::default().range(0..1_048_576),
+    ///     )
+    ///     .await?;
+    /// while let Some(chunk) = stream.next().await {
+    ///     let chunk = chunk?;
+    /// }
+    /// ```
+    pub async fn get_object_stream(
+        &self,
+        storage_account_key: &Pubkey,
+        filename: &str,
+        options: GetObjectOptions,
+    ) -> ShadowDriveResult<impl Stream<Item = ShadowDriveResult<Bytes>>> {
+        let response = self
+            .request_object(storage_account_key, filename, &options)
+            .await?;
+        let partial = response.status() == StatusCode::PARTIAL_CONTENT;
+        let trim = options.is_ranged() && !partial;
+        let end = options.end.filter(|_| trim);
+        let expected = options.sha256;
+
+        // The server ignored the range and sent the whole object, so cut the
+        // range out of it while streaming
+        let chunks = response
+            .bytes_stream()
+            .map_err(Error::from)
+            .scan(0u64, move |offset, chunk| {
+                if end.map_or(false, |end| *offset >= end) {
+                    return future::ready(None);
+                }
+                let chunk = chunk.map(|chunk| {
+                    let chunk_offset = *offset;
+                    *offset += chunk.len() as u64;
+                    if trim {
+                        options.slice(chunk, chunk_offset)
+                    } else {
+                        chunk
+                    }
+                });
+                future::ready(Some(chunk))
+            })
+            .try_filter(|chunk| future::ready(!chunk.is_empty()))
+            .fuse();
+
+        let hasher = expected.map(|expected| (expected, Sha256::new()));
+        Ok(stream::unfold(
+            (Box::pin(chunks), hasher),
+            |(mut chunks, mut hasher)| async move {
+                match chunks.next().await {
+                    Some(Ok(chunk)) => {
+                        if let Some((_, hasher)) = hasher.as_mut() {
+                            hasher.update(&chunk);
+                        }
+                        Some((Ok(chunk), (chunks, hasher)))
+                    }
+                    // Skip verification, the caller already has an error
+                    Some(Err(e)) => Some((Err(e), (chunks, None))),
+                    None => {
+                        let (expected, hasher) = hasher?;
+                        verify_sha256(expected, hasher)
+                            .err()
+                            .map(|e| (Err(e), (chunks, None)))
+                    }
+                }
+            },
+        ))
+    }
+
+    async fn request_object(
+        &self,
+        storage_account_key: &Pubkey,
+        filename: &str,
+        options: &GetObjectOptions,
+    ) -> ShadowDriveResult<Response> {
+        let mut request = self
+            .http_client
+            .get(self.object_url(storage_account_key, filename));
+        if let Some(range) = options.range_header()? {
+            request = request.header(RANGE, range);
+        }
+
+        let response = self.send_request(request).await?;
+
+        if !response.status().is_success() {
+            return Err(Error::from_response(response).await);
+        }
+
+        Ok(response)
+    }
+}
+
+#[test]
+fn test_range_slice() {
+    let options = GetObjectOptions::default().range(2..5);
+    assert_eq!(options.range_header().unwrap().unwrap(), "bytes=2-4");
+    // A range split across chunks, as when the server ignores `Range`
+    assert_eq!(options.slice(Bytes::from_static(b"012"), 0), &b"2"[..]);
+    assert_eq!(options.slice(Bytes::from_static(b"345"), 3), &b"34"[..]);
+    assert!(options.slice(Bytes::from_static(b"678"), 6).is_empty());
+
+    let error = GetObjectOptions::default()
+        .range(5..5)
+        .range_header()
+        .unwrap_err();
+    assert!(
+        matches!(error, Error::InvalidRange { start: 5, end: 5 }),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_get_object() {
+    use shadow_drive_mock::MockShadowDrive;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let client = super::test_client(&drive);
+    let storage_account = Pubkey::new_unique();
+    drive
+        .put_object(storage_account, "a.txt", b"0123456789")
+        .unwrap();
+    let hash: [u8; 32] = Sha256::digest(b"2345").into();
+
+    let data = client.get_object(&storage_account, "a.txt").await.unwrap();
+    assert_eq!(data, &b"0123456789"[..]);
+
+    let options = GetObjectOptions::default().range(2..6).sha256(hash);
+    let data = client
+        .get_object_with_options(&storage_account, "a.txt", options)
+        .await
+        .unwrap();
+    assert_eq!(data, &b"2345"[..]);
+
+    // The hash covers only the range
+    let options = GetObjectOptions::default().sha256(hash);
+    let error = client
+        .get_object_with_options(&storage_account, "a.txt", options)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, Error::ObjectHashMismatch { expected, .. } if expected == hash),
+        "{error:?}"
+    );
+
+    let options = GetObjectOptions::default().range(6..2);
+    let error = client
+        .get_object_with_options(&storage_account, "a.txt", options)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, Error::InvalidRange { start: 6, end: 2 }),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_get_object_stream() {
+    use shadow_drive_mock::MockShadowDrive;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let client = super::test_client(&drive);
+    let storage_account = Pubkey::new_unique();
+    let data = vec![7u8; 1 << 20];
+    drive.put_object(storage_account, "a.bin", &data).unwrap();
+    let hash: [u8; 32] = Sha256::digest(&data[1024..]).into();
+
+    let options = GetObjectOptions::default().range(1024..).sha256(hash);
+    let chunks: Vec<_> = client
+        .get_object_stream(&storage_account, "a.bin", options)
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    let streamed = chunks
+        .into_iter()
+        .collect::<ShadowDriveResult<Vec<_>>>()
+        .unwrap()
+        .concat();
+    assert_eq!(streamed, &data[1024..]);
+
+    // A mismatch is reported as the last item, after all of the data
+    let options = GetObjectOptions::default().sha256(hash);
+    let mut chunks: Vec<_> = client
+        .get_object_stream(&storage_account, "a.bin", options)
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    let error = chunks.pop().unwrap().unwrap_err();
+    assert!(
+        matches!(error, Error::ObjectHashMismatch { .. }),
+        "{error:?}"
+    );
+    let streamed = chunks
+        .into_iter()
+        .collect::<ShadowDriveResult<Vec<_>>>()
+        .unwrap()
+        .concat();
+    assert_eq!(streamed, data);
+}
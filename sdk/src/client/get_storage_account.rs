@@ -1,15 +1,20 @@
 use anchor_lang::AccountDeserialize;
 use futures::future::join_all;
 use serde_json::json;
-use solana_sdk::{pubkey::Pubkey, signer::Signer};
+use shadow_drive_user_staking::instructions::initialize_account::{
+    StorageAccount, StorageAccountV2,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey, signer::Signer};
 
 use super::ShadowDriveClient;
 use crate::{
-    constants::SHDW_DRIVE_ENDPOINT,
-    derived_addresses,
+    error::Error,
     models::{storage_acct::StorageAcct, *},
 };
 
+/// Most accounts `getMultipleAccounts` returns per request.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
 impl<T> ShadowDriveClient<T>
 where
     T: Signer,
@@ -41,12 +46,13 @@ where
     /// ```
     pub async fn get_storage_account(&self, key: &Pubkey) -> ShadowDriveResult<StorageAcct> {
         let response = self
-            .http_client
-            .post(format!("{}/storage-account-info", SHDW_DRIVE_ENDPOINT))
-            .json(&json!({
-                "storage_account": key.to_string()
-            }))
-            .send()
+            .send_request(
+                self.http_client
+                    .post(format!("{}/storage-account-info", self.storage_endpoint))
+                    .json(&json!({
+                        "storage_account": key.to_string()
+                    })),
+            )
             .await?
             .json()
             .await?;
@@ -82,16 +88,13 @@ where
         &self,
         owner: &Pubkey,
     ) -> ShadowDriveResult<Vec<StorageAcct>> {
-        let (user_info_key, _) = derived_addresses::user_info(owner);
-        let user_info = self.rpc_client.get_account_data(&user_info_key).await?;
-        let user_info = UserInfo::try_deserialize(&mut user_info.as_slice())?;
+        let accounts_to_fetch = self.storage_account_keys(owner).await?;
 
-        let accounts_to_fetch = (0..user_info.account_counter)
-            .map(|account_seed| derived_addresses::storage_account(owner, account_seed).0);
-
-        let accounts = accounts_to_fetch.map(|storage_account_key| async move {
-            self.get_storage_account(&storage_account_key).await
-        });
+        let accounts = accounts_to_fetch
+            .into_iter()
+            .map(|storage_account_key| async move {
+                self.get_storage_account(&storage_account_key).await
+            });
 
         let (accounts, errors): (
             Vec<ShadowDriveResult<StorageAcct>>,
@@ -106,4 +109,213 @@ where
         //unwrap is safe due do the abve partition
         Ok(accounts.into_iter().map(Result::unwrap).collect())
     }
+
+    /// Returns the [`StorageAccount`](crate::models::StorageAccount) at `key`, read from chain
+    /// data without the Shadow Drive HTTP API, e.g. when it is down or against a local validator.
+    ///
+    /// V2 accounts do not record their usage on chain, so their `current_usage` is 0; use
+    /// [`get_storage_account`](Self::get_storage_account) where usage matters. Fails with
+    /// [`Error::AccountOwnerMismatch`] if the account is not owned by the Shadow Drive program.
+    pub async fn get_storage_account_rpc(&self, key: &Pubkey) -> ShadowDriveResult<StorageAcct> {
+        let account = self.rpc_client.get_account(key).await?;
+        self.storage_account_from_account(key, &account)
+    }
+
+    /// Returns all [`StorageAccount`]s owned by `owner`, read from chain data without the
+    /// Shadow Drive HTTP API. As with [`get_storage_account_rpc`](Self::get_storage_account_rpc),
+    /// the `current_usage` of V2 accounts is 0.
+    ///
+    /// Deleted accounts are skipped, as are accounts that are not owned by the Shadow Drive
+    /// program or fail to deserialize, which are logged.
+    pub async fn get_storage_accounts_rpc(
+        &self,
+        owner: &Pubkey,
+    ) -> ShadowDriveResult<Vec<StorageAcct>> {
+        let keys = self.storage_account_keys(owner).await?;
+
+        let mut accounts = Vec::with_capacity(keys.len());
+        for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let datas = self.rpc_client.get_multiple_accounts(chunk).await?;
+            for (key, account) in chunk.iter().zip(datas) {
+                let account = match account {
+                    Some(account) => account,
+                    None => continue,
+                };
+                match self.storage_account_from_account(key, &account) {
+                    Ok(account) => accounts.push(account),
+                    Err(error) => {
+                        tracing::warn!(%key, %error, "skipping invalid storage account")
+                    }
+                }
+            }
+        }
+
+        Ok(accounts)
+    }
+
+    fn storage_account_from_account(
+        &self,
+        key: &Pubkey,
+        account: &Account,
+    ) -> ShadowDriveResult<StorageAcct> {
+        if account.owner != self.program_id {
+            return Err(Error::AccountOwnerMismatch {
+                account: *key,
+                owner: account.owner,
+            });
+        }
+        StorageAcct::from_account_data(*key, &account.data)
+    }
+
+    /// Returns the [`FileAccount`]s of a V1 storage account with their addresses, read from chain
+    /// data. V2 storage accounts keep no file accounts, so none are returned for them.
+    pub async fn get_file_accounts(
+        &self,
+        storage_account_key: &Pubkey,
+    ) -> ShadowDriveResult<Vec<(Pubkey, FileAccount)>> {
+        let data = self
+            .rpc_client
+            .get_account_data(storage_account_key)
+            .await?;
+        let storage_account = match StorageAccount::try_deserialize(&mut data.as_slice()) {
+            Ok(storage_account) => storage_account,
+            Err(_) if StorageAccountV2::try_deserialize(&mut data.as_slice()).is_ok() => {
+                return Ok(Vec::new())
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let keys: Vec<Pubkey> = (0..storage_account.init_counter)
+            .map(|file_seed| {
+                self.derived_addresses()
+                    .file_account(storage_account_key, file_seed)
+                    .0
+            })
+            .collect();
+
+        let mut files = Vec::with_capacity(keys.len());
+        for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let datas = self.rpc_client.get_multiple_accounts(chunk).await?;
+            for (key, account) in chunk.iter().zip(datas) {
+                // deleted files leave gaps in the seeds
+                if let Some(account) = account {
+                    files.push((
+                        *key,
+                        FileAccount::try_deserialize(&mut account.data.as_slice())?,
+                    ));
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Addresses of every storage account `owner` has created.
+    async fn storage_account_keys(&self, owner: &Pubkey) -> ShadowDriveResult<Vec<Pubkey>> {
+        let (user_info_key, _) = self.derived_addresses().user_info(owner);
+        let user_info = self.rpc_client.get_account_data(&user_info_key).await?;
+        let user_info = UserInfo::try_deserialize(&mut user_info.as_slice())?;
+
+        Ok((0..user_info.account_counter)
+            .map(|account_seed| {
+                self.derived_addresses()
+                    .storage_account(owner, account_seed)
+                    .0
+            })
+            .collect())
+    }
+}
+
+/// A client whose RPC requests are answered with `mocks`, once each.
+#[cfg(test)]
+fn test_rpc_client(
+    mocks: Vec<(solana_client::rpc_request::RpcRequest, serde_json::Value)>,
+) -> ShadowDriveClient<solana_sdk::signature::Keypair> {
+    use solana_client::nonblocking::rpc_client::RpcClient;
+
+    let rpc_client =
+        RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks.into_iter().collect());
+    crate::ShadowDriveClientBuilder::new_with_rpc(solana_sdk::signature::Keypair::new(), rpc_client)
+        .build()
+}
+
+/// `getAccountInfo` and `getMultipleAccounts` JSON of an account with `data`.
+#[cfg(test)]
+fn test_ui_account(owner: &Pubkey, data: &[u8]) -> serde_json::Value {
+    json!({
+        "lamports": 1_000_000,
+        "data": [base64::encode(data), "base64"],
+        "owner": owner.to_string(),
+        "executable": false,
+        "rentEpoch": 0,
+    })
+}
+
+#[tokio::test]
+async fn test_get_storage_account_rpc() {
+    use solana_client::rpc_request::RpcRequest;
+
+    use crate::{constants::PROGRAM_ADDRESS, models::storage_acct::test_account_data};
+
+    let key = Pubkey::new_unique();
+    let data = test_account_data::<StorageAccountV2>(|account| account.storage = 1000);
+    let response =
+        |owner| json!({ "context": { "slot": 1 }, "value": test_ui_account(owner, &data) });
+
+    let client = test_rpc_client(vec![(
+        RpcRequest::GetAccountInfo,
+        response(&PROGRAM_ADDRESS),
+    )]);
+    let account = client.get_storage_account_rpc(&key).await.unwrap();
+    assert_eq!(account.storage(), 1000);
+
+    // Accounts of other programs are not trusted, even if their data would deserialize
+    let other = Pubkey::new_unique();
+    let client = test_rpc_client(vec![(RpcRequest::GetAccountInfo, response(&other))]);
+    match client.get_storage_account_rpc(&key).await {
+        Err(Error::AccountOwnerMismatch { account, owner }) => {
+            assert_eq!(account, key);
+            assert_eq!(owner, other);
+        }
+        result => panic!("expected an owner mismatch, got {:?}", result),
+    }
+}
+
+#[tokio::test]
+async fn test_get_storage_accounts_rpc() {
+    use solana_client::rpc_request::RpcRequest;
+
+    use crate::{constants::PROGRAM_ADDRESS, models::storage_acct::test_account_data};
+
+    let user_info = test_account_data::<UserInfo>(|user_info| user_info.account_counter = 5);
+    let v1 = test_account_data::<StorageAccount>(|account| account.identifier = "v1".to_string());
+    let v2 = test_account_data::<StorageAccountV2>(|account| account.identifier = "v2".to_string());
+    let client = test_rpc_client(vec![
+        (
+            RpcRequest::GetAccountInfo,
+            json!({ "context": { "slot": 1 }, "value": test_ui_account(&PROGRAM_ADDRESS, &user_info) }),
+        ),
+        (
+            RpcRequest::GetMultipleAccounts,
+            json!({
+                "context": { "slot": 1 },
+                "value": [
+                    test_ui_account(&PROGRAM_ADDRESS, &v1),
+                    // Deleted
+                    null,
+                    test_ui_account(&Pubkey::new_unique(), &v2),
+                    test_ui_account(&PROGRAM_ADDRESS, b"not a storage account"),
+                    test_ui_account(&PROGRAM_ADDRESS, &v2),
+                ],
+            }),
+        ),
+    ]);
+
+    // Only the valid accounts are returned
+    let accounts = client
+        .get_storage_accounts_rpc(&Pubkey::new_unique())
+        .await
+        .unwrap();
+    let identifiers: Vec<_> = accounts.iter().map(StorageAcct::identifier).collect();
+    assert_eq!(identifiers, ["v1", "v2"]);
 }
@@ -1,13 +1,164 @@
-use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures::{stream, Stream, TryStreamExt};
+use serde::Deserialize;
+use serde_json::json;
 use solana_sdk::{pubkey::Pubkey, signer::Signer};
 
 use super::ShadowDriveClient;
 use crate::{
-    constants::SHDW_DRIVE_ENDPOINT,
     error::Error,
-    models::{ListObjectsResponse, ShadowDriveResult},
+    models::{ListObjectsResponse, ObjectInfo, ObjectListing, ShadowDriveResult},
 };
 
+/// Options for [`list_objects_detailed`](ShadowDriveClient::list_objects_detailed)
+/// and [`list_objects_stream`](ShadowDriveClient::list_objects_stream).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ListObjectsOptions {
+    prefix: Option<String>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+impl ListObjectsOptions {
+    /// Only list objects whose keys start with `prefix`.
+    pub fn prefix<P: ToString>(mut self, prefix: P) -> Self {
+        self.prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Only list objects whose keys sort after `cursor`, e.g. the
+    /// [`next_cursor`](ObjectListing::next_cursor) of the previous page.
+    pub fn cursor<C: ToString>(mut self, cursor: C) -> Self {
+        self.cursor = Some(cursor.to_string());
+        self
+    }
+
+    /// List at most `limit` objects per page. Pages are unbounded by default.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit.max(1));
+        self
+    }
+
+    fn matches(&self, key: &str) -> bool {
+        self.prefix
+            .as_ref()
+            .map_or(true, |prefix| key.starts_with(prefix.as_str()))
+            && self
+                .cursor
+                .as_ref()
+                .map_or(true, |cursor| key > cursor.as_str())
+    }
+
+    /// Filters and pages a listing that the server did not page itself.
+    fn page(&self, mut objects: Vec<ObjectInfo>) -> ObjectListing {
+        objects.retain(|object| self.matches(&object.key));
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        let next_cursor = match self.limit {
+            Some(limit) if objects.len() > limit => {
+                objects.truncate(limit);
+                objects.last().map(|object| object.key.clone())
+            }
+            _ => None,
+        };
+        ObjectListing {
+            objects,
+            next_cursor,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ListObjectsDetailedResponse {
+    /// Missing from servers that only return `objects`
+    #[serde(default)]
+    keys: Vec<String>,
+    /// Missing from servers that only list keys
+    #[serde(default)]
+    objects: Option<Vec<ObjectInfoResponse>>,
+    /// Only set by servers that page the listing themselves
+    #[serde(default)]
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ObjectInfoResponse {
+    key: String,
+    size: Option<u64>,
+    content_type: Option<String>,
+    last_modified: Option<u64>,
+    /// Hex encoded
+    sha256: Option<String>,
+}
+
+impl From<ObjectInfoResponse> for ObjectInfo {
+    fn from(object: ObjectInfoResponse) -> Self {
+        Self {
+            key: object.key,
+            size: object.size,
+            content_type: object.content_type,
+            last_modified: object.last_modified,
+            sha256: object
+                .sha256
+                .and_then(|sha256| hex::decode(sha256).ok())
+                .and_then(|sha256| sha256.try_into().ok()),
+        }
+    }
+}
+
+/// A page of a listing as returned by the server, or the complete rest of it.
+enum ObjectsPage {
+    /// Paged by the server, which will return more objects after `next_cursor`
+    Partial {
+        objects: Vec<ObjectInfo>,
+        next_cursor: String,
+    },
+    /// Every object after the requested cursor, not necessarily filtered or sorted
+    Rest(Vec<ObjectInfo>),
+}
+
+/// Complete listings of storage accounts, each kept for `ttl`.
+pub(crate) struct ObjectCache {
+    ttl: Duration,
+    listings: Mutex<HashMap<Pubkey, (Instant, Vec<ObjectInfo>)>>,
+}
+
+impl ObjectCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            listings: Mutex::default(),
+        }
+    }
+
+    fn get(&self, storage_account_key: &Pubkey) -> Option<Vec<ObjectInfo>> {
+        let listings = self.listings.lock().unwrap();
+        listings
+            .get(storage_account_key)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < self.ttl)
+            .map(|(_, objects)| objects.clone())
+    }
+
+    fn insert(&self, storage_account_key: Pubkey, objects: Vec<ObjectInfo>) {
+        self.listings
+            .lock()
+            .unwrap()
+            .insert(storage_account_key, (Instant::now(), objects));
+    }
+
+    pub(crate) fn remove(&self, storage_account_key: &Pubkey) {
+        self.listings.lock().unwrap().remove(storage_account_key);
+    }
+
+    fn clear(&self) {
+        self.listings.lock().unwrap().clear();
+    }
+}
+
 impl<T> ShadowDriveClient<T>
 where
     T: Signer,
@@ -42,19 +193,17 @@ where
         storage_account_key: &Pubkey,
     ) -> ShadowDriveResult<Vec<String>> {
         let response = self
-            .http_client
-            .post(format!("{}/list-objects", SHDW_DRIVE_ENDPOINT))
-            .json(&json!({
-              "storageAccount": storage_account_key.to_string()
-            }))
-            .send()
+            .send_request(
+                self.http_client
+                    .post(format!("{}/list-objects", self.storage_endpoint))
+                    .json(&json!({
+                      "storageAccount": storage_account_key.to_string()
+                    })),
+            )
             .await?;
 
         if !response.status().is_success() {
-            return Err(Error::ShadowDriveServerError {
-                status: response.status().as_u16(),
-                message: response.json::<Value>().await?,
-            });
+            return Err(Error::from_response(response).await);
         }
         response
             .json::<ListObjectsResponse>()
@@ -62,4 +211,372 @@ where
             .map(|response| response.keys)
             .map_err(Error::from)
     }
+
+    /// Lists the objects in a storage account with their size, content type, last modification
+    /// time and SHA-256 hash where the server provides them, in a single request per page.
+    /// * `storage_account_key` - The public key of the [`StorageAccount`](crate::models::StorageAccount) that owns the files.
+    /// * `options` - Prefix filter and paging, see [`ListObjectsOptions`].
+    ///
+    /// With [`ShadowDriveClientBuilder::object_cache_ttl`](crate::ShadowDriveClientBuilder::object_cache_ttl)
+    /// set, the complete listing of the account is fetched once and pages are served from it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use shadow_drive_rust::{ShadowDriveClient, ListObjectsOptions, derived_addresses::storage_account};
+    /// # use solana_sdk::signer::{keypair::read_keypair_file, Signer};
+    /// #
+    /// # let keypair = read_keypair_file(KEYPAIR_PATH).expect("failed to load keypair at path");
+    /// # let user_pubkey = keypair.pubkey();
+    /// # let shdw_drive_client = ShadowDriveClient::new(keypair, "https://ssc-dao.genesysgo.net");
+    /// # let (storage_account_key, _) = storage_account(&user_pubkey, 0);
+    /// #
+    /// let page = shdw_drive_client
+    ///     .list_objects_detailed(
+    ///         &storage_account_key,
+    ///         ListObjectsOptions::default().prefix("images/").limit(100),
+    ///     )
+    ///     .await?;
+    /// ```
+    pub async fn list_objects_detailed(
+        &self,
+        storage_account_key: &Pubkey,
+        options: ListObjectsOptions,
+    ) -> ShadowDriveResult<ObjectListing> {
+        match self
+            .list_objects_page(storage_account_key, &options)
+            .await?
+        {
+            ObjectsPage::Partial {
+                objects,
+                next_cursor,
+            } => Ok(ObjectListing {
+                objects,
+                next_cursor: Some(next_cursor),
+            }),
+            ObjectsPage::Rest(objects) => Ok(options.page(objects)),
+        }
+    }
+
+    /// Streams every object matching `options`, fetching further pages as needed.
+    /// Useful for storage accounts too large to list in one response, together with
+    /// [`ListObjectsOptions::limit`].
+    ///
+    /// Listings that are cached, or come from servers that do not page them, are fetched once
+    /// and streamed without further requests.
+    pub fn list_objects_stream<'a>(
+        &'a self,
+        storage_account_key: &'a Pubkey,
+        options: ListObjectsOptions,
+    ) -> impl Stream<Item = ShadowDriveResult<ObjectInfo>> + 'a {
+        stream::try_unfold(Some(options), move |options| async move {
+            let options = match options {
+                Some(options) => options,
+                None => return Ok::<_, Error>(None),
+            };
+            let (objects, next) = match self
+                .list_objects_page(storage_account_key, &options)
+                .await?
+            {
+                ObjectsPage::Partial {
+                    objects,
+                    next_cursor,
+                } => (objects, Some(options.cursor(next_cursor))),
+                // Paging the complete listing again for every page would be quadratic
+                ObjectsPage::Rest(objects) => {
+                    let options = ListObjectsOptions {
+                        limit: None,
+                        ..options
+                    };
+                    (options.page(objects).objects, None)
+                }
+            };
+            let objects = stream::iter(objects.into_iter().map(Ok::<_, Error>));
+            Ok(Some((objects, next)))
+        })
+        .try_flatten()
+    }
+
+    /// Drops every listing cached by [`list_objects_detailed`](Self::list_objects_detailed).
+    /// Uploads, edits and deletes made through this client drop the affected listing already.
+    pub fn clear_object_cache(&self) {
+        if let Some(cache) = &self.object_cache {
+            cache.clear();
+        }
+    }
+
+    pub(crate) fn invalidate_object_cache(&self, storage_account_key: &Pubkey) {
+        if let Some(cache) = &self.object_cache {
+            cache.remove(storage_account_key);
+        }
+    }
+
+    /// The cached listing, if caching is enabled, or the page of `options` from the server.
+    async fn list_objects_page(
+        &self,
+        storage_account_key: &Pubkey,
+        options: &ListObjectsOptions,
+    ) -> ShadowDriveResult<ObjectsPage> {
+        let cache = match &self.object_cache {
+            Some(cache) => cache,
+            None => return self.fetch_objects_page(storage_account_key, options).await,
+        };
+
+        if let Some(objects) = cache.get(storage_account_key) {
+            return Ok(ObjectsPage::Rest(objects));
+        }
+        let objects = self.fetch_all_objects(storage_account_key).await?;
+        cache.insert(*storage_account_key, objects.clone());
+        Ok(ObjectsPage::Rest(objects))
+    }
+
+    async fn fetch_objects_page(
+        &self,
+        storage_account_key: &Pubkey,
+        options: &ListObjectsOptions,
+    ) -> ShadowDriveResult<ObjectsPage> {
+        let response = self
+            .send_request(
+                self.http_client
+                    .post(format!("{}/list-objects", self.storage_endpoint))
+                    .json(&json!({
+                      "storageAccount": storage_account_key.to_string(),
+                      "detailed": true,
+                      "prefix": options.prefix,
+                      "cursor": options.cursor,
+                      "limit": options.limit,
+                    })),
+            )
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(Error::from_response(response).await);
+        }
+        let response = response.json::<ListObjectsDetailedResponse>().await?;
+
+        let objects: Vec<ObjectInfo> = match response.objects {
+            Some(objects) => objects.into_iter().map(ObjectInfo::from).collect(),
+            None => response
+                .keys
+                .into_iter()
+                .map(ObjectInfo::from_key)
+                .collect(),
+        };
+        match response.next_cursor {
+            Some(next_cursor) => Ok(ObjectsPage::Partial {
+                objects,
+                next_cursor,
+            }),
+            // Either the last page, or the whole listing from an older server that ignores the
+            // prefix and paging, so the caller applies them
+            None => Ok(ObjectsPage::Rest(objects)),
+        }
+    }
+
+    async fn fetch_all_objects(
+        &self,
+        storage_account_key: &Pubkey,
+    ) -> ShadowDriveResult<Vec<ObjectInfo>> {
+        let mut options = ListObjectsOptions::default();
+        let mut objects = Vec::new();
+        loop {
+            match self
+                .fetch_objects_page(storage_account_key, &options)
+                .await?
+            {
+                ObjectsPage::Partial {
+                    objects: page,
+                    next_cursor,
+                } => {
+                    objects.extend(page);
+                    options = options.cursor(next_cursor);
+                }
+                ObjectsPage::Rest(rest) => {
+                    objects.extend(rest);
+                    return Ok(objects);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_keys(objects: &[ObjectInfo]) -> Vec<&str> {
+    objects.iter().map(|object| object.key.as_str()).collect()
+}
+
+#[cfg(test)]
+async fn test_list<T: Signer>(
+    client: &ShadowDriveClient<T>,
+    storage_account_key: &Pubkey,
+) -> Vec<String> {
+    let page = client
+        .list_objects_detailed(storage_account_key, ListObjectsOptions::default())
+        .await
+        .unwrap();
+    page.objects.into_iter().map(|object| object.key).collect()
+}
+
+#[test]
+fn test_detailed_response_without_keys() {
+    let response: ListObjectsDetailedResponse = serde_json::from_value(json!({
+        "objects": [{ "key": "a.txt", "size": 1 }],
+    }))
+    .unwrap();
+    assert!(response.keys.is_empty());
+    assert_eq!(response.objects.unwrap()[0].key, "a.txt");
+    assert!(response.next_cursor.is_none());
+}
+
+#[tokio::test]
+async fn test_list_objects_pages() {
+    use futures::TryStreamExt;
+    use shadow_drive_mock::MockShadowDrive;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let client = super::test_client(&drive);
+    let storage_account = Pubkey::new_unique();
+    for name in ["a.txt", "b.json", "b.txt", "c.txt", "d.txt"] {
+        drive
+            .put_object(storage_account, name, name.as_bytes())
+            .unwrap();
+    }
+
+    let mut keys = client.list_objects(&storage_account).await.unwrap();
+    keys.sort();
+    assert_eq!(keys, ["a.txt", "b.json", "b.txt", "c.txt", "d.txt"]);
+
+    let page = client
+        .list_objects_detailed(&storage_account, ListObjectsOptions::default().limit(2))
+        .await
+        .unwrap();
+    assert_eq!(test_keys(&page.objects), ["a.txt", "b.json"]);
+    assert_eq!(page.objects[0].size, Some(5));
+    assert_eq!(page.next_cursor.as_deref(), Some("b.json"));
+
+    let options = ListObjectsOptions::default().cursor("b.json").limit(2);
+    let page = client
+        .list_objects_detailed(&storage_account, options)
+        .await
+        .unwrap();
+    assert_eq!(test_keys(&page.objects), ["b.txt", "c.txt"]);
+
+    let options = ListObjectsOptions::default().prefix("b.");
+    let page = client
+        .list_objects_detailed(&storage_account, options)
+        .await
+        .unwrap();
+    assert_eq!(test_keys(&page.objects), ["b.json", "b.txt"]);
+    assert_eq!(page.next_cursor, None);
+
+    // One request per page
+    let requests = drive.requests("/list-objects");
+    let objects: Vec<ObjectInfo> = client
+        .list_objects_stream(&storage_account, ListObjectsOptions::default().limit(2))
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(
+        test_keys(&objects),
+        ["a.txt", "b.json", "b.txt", "c.txt", "d.txt"]
+    );
+    assert_eq!(drive.requests("/list-objects"), requests + 3);
+}
+
+#[tokio::test]
+async fn test_list_objects_unpaged() {
+    use futures::TryStreamExt;
+    use shadow_drive_mock::MockShadowDrive;
+
+    let drive = MockShadowDrive::start().unwrap();
+    drive.set_legacy_listing(true);
+    let client = super::test_client(&drive);
+    let storage_account = Pubkey::new_unique();
+    for name in ["d.txt", "c.txt", "b.txt", "b.json", "a.txt"] {
+        drive
+            .put_object(storage_account, name, name.as_bytes())
+            .unwrap();
+    }
+
+    // Prefix, cursor and limit are applied locally
+    let options = ListObjectsOptions::default().prefix("b.").limit(1);
+    let page = client
+        .list_objects_detailed(&storage_account, options)
+        .await
+        .unwrap();
+    assert_eq!(test_keys(&page.objects), ["b.json"]);
+    assert_eq!(page.objects[0].size, None);
+    assert_eq!(page.next_cursor.as_deref(), Some("b.json"));
+
+    let options = ListObjectsOptions::default().prefix("b.").cursor("b.json");
+    let page = client
+        .list_objects_detailed(&storage_account, options)
+        .await
+        .unwrap();
+    assert_eq!(test_keys(&page.objects), ["b.txt"]);
+    assert_eq!(page.next_cursor, None);
+
+    // The listing is fetched once, not once per page
+    let requests = drive.requests("/list-objects");
+    let objects: Vec<ObjectInfo> = client
+        .list_objects_stream(&storage_account, ListObjectsOptions::default().limit(2))
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(
+        test_keys(&objects),
+        ["a.txt", "b.json", "b.txt", "c.txt", "d.txt"]
+    );
+    assert_eq!(drive.requests("/list-objects"), requests + 1);
+}
+
+#[tokio::test]
+async fn test_object_cache() {
+    use futures::TryStreamExt;
+    use shadow_drive_mock::MockShadowDrive;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let client = super::test_client_builder(&drive)
+        .object_cache_ttl(Duration::from_millis(200))
+        .build();
+    let storage_account = Pubkey::new_unique();
+    drive.put_object(storage_account, "a.txt", b"a").unwrap();
+
+    assert_eq!(test_list(&client, &storage_account).await, ["a.txt"]);
+    assert_eq!(drive.requests("/list-objects"), 1);
+
+    // Served from the cache, even though the account changed behind its back
+    drive.put_object(storage_account, "b.txt", b"b").unwrap();
+    assert_eq!(test_list(&client, &storage_account).await, ["a.txt"]);
+    let objects: Vec<ObjectInfo> = client
+        .list_objects_stream(&storage_account, ListObjectsOptions::default().limit(1))
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(test_keys(&objects), ["a.txt"]);
+    assert_eq!(drive.requests("/list-objects"), 1);
+
+    // Refetched once the listing expires
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(
+        test_list(&client, &storage_account).await,
+        ["a.txt", "b.txt"]
+    );
+    assert_eq!(drive.requests("/list-objects"), 2);
+
+    // Changes made through the client drop the listing
+    let file = crate::models::ShadowFile::bytes(String::from("c.txt"), &b"c"[..]);
+    client
+        .store_files(&storage_account, vec![file])
+        .await
+        .unwrap();
+    assert_eq!(
+        test_list(&client, &storage_account).await,
+        ["a.txt", "b.txt", "c.txt"]
+    );
+    assert_eq!(drive.requests("/list-objects"), 3);
+
+    client.clear_object_cache();
+    test_list(&client, &storage_account).await;
+    assert_eq!(drive.requests("/list-objects"), 4);
 }
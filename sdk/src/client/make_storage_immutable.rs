@@ -11,8 +11,6 @@ use spl_token::ID as TokenProgramID;
 
 use super::ShadowDriveClient;
 use crate::{
-    constants::{EMISSIONS, PROGRAM_ADDRESS, STORAGE_CONFIG_PDA, TOKEN_MINT, UPLOADER},
-    derived_addresses,
     models::{
         storage_acct::{StorageAccount, StorageAccountV2, StorageAcct},
         ShadowDriveResult, StorageResponse,
@@ -76,19 +74,19 @@ where
         storage_account: StorageAccount,
     ) -> ShadowDriveResult<String> {
         let wallet_pubkey = self.wallet.pubkey();
-        let owner_ata = get_associated_token_address(&wallet_pubkey, &TOKEN_MINT);
-        let emissions_ata = get_associated_token_address(&EMISSIONS, &TOKEN_MINT);
-        let (stake_account, _) = derived_addresses::stake_account(storage_account_key);
+        let owner_ata = get_associated_token_address(&wallet_pubkey, &self.token_mint);
+        let emissions_ata = get_associated_token_address(&self.emissions, &self.token_mint);
+        let (stake_account, _) = self.derived_addresses().stake_account(storage_account_key);
 
         let accounts = shdw_drive_accounts::MakeAccountImmutableV1 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             owner: storage_account.owner_1,
-            uploader: UPLOADER,
+            uploader: self.uploader,
             owner_ata,
             stake_account,
             emissions_wallet: emissions_ata,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
             token_program: TokenProgramID,
             associated_token_program: spl_associated_token_account::ID,
@@ -98,7 +96,7 @@ where
         let args = shdw_drive_instructions::MakeAccountImmutable {};
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -121,19 +119,19 @@ where
         storage_account: StorageAccountV2,
     ) -> ShadowDriveResult<String> {
         let wallet_pubkey = self.wallet.pubkey();
-        let owner_ata = get_associated_token_address(&wallet_pubkey, &TOKEN_MINT);
-        let emissions_ata = get_associated_token_address(&EMISSIONS, &TOKEN_MINT);
-        let (stake_account, _) = derived_addresses::stake_account(storage_account_key);
+        let owner_ata = get_associated_token_address(&wallet_pubkey, &self.token_mint);
+        let emissions_ata = get_associated_token_address(&self.emissions, &self.token_mint);
+        let (stake_account, _) = self.derived_addresses().stake_account(storage_account_key);
 
         let accounts = shdw_drive_accounts::MakeAccountImmutableV2 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             owner: storage_account.owner_1,
-            uploader: UPLOADER,
+            uploader: self.uploader,
             owner_ata,
             stake_account,
             emissions_wallet: emissions_ata,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
             token_program: TokenProgramID,
             associated_token_program: spl_associated_token_account::ID,
@@ -142,7 +140,7 @@ where
         let args = shdw_drive_instructions::MakeAccountImmutable2 {};
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
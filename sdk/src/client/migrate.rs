@@ -7,7 +7,7 @@ use solana_sdk::{
 };
 
 use super::ShadowDriveClient;
-use crate::{constants::PROGRAM_ADDRESS, derived_addresses, models::*};
+use crate::models::*;
 
 impl<T> ShadowDriveClient<T>
 where
@@ -57,7 +57,9 @@ where
         storage_account_key: &Pubkey,
     ) -> ShadowDriveResult<ShdwDriveResponse> {
         let wallet_pubkey = self.wallet.pubkey();
-        let (migration, _) = derived_addresses::migration_helper(storage_account_key);
+        let (migration, _) = self
+            .derived_addresses()
+            .migration_helper(storage_account_key);
 
         let accounts = shdw_drive_accounts::MigrateStep1 {
             storage_account: *storage_account_key,
@@ -69,7 +71,7 @@ where
         let args = shdw_drive_instructions::MigrateStep1 {};
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -92,7 +94,9 @@ where
         storage_account_key: &Pubkey,
     ) -> ShadowDriveResult<ShdwDriveResponse> {
         let wallet_pubkey = self.wallet.pubkey();
-        let (migration, _) = derived_addresses::migration_helper(storage_account_key);
+        let (migration, _) = self
+            .derived_addresses()
+            .migration_helper(storage_account_key);
 
         let accounts = shdw_drive_accounts::MigrateStep2 {
             storage_account: *storage_account_key,
@@ -104,7 +108,7 @@ where
         let args = shdw_drive_instructions::MigrateStep2 {};
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
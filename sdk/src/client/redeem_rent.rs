@@ -7,7 +7,7 @@ use solana_sdk::{
 };
 
 use super::ShadowDriveClient;
-use crate::{constants::PROGRAM_ADDRESS, models::*};
+use crate::models::*;
 
 impl<T> ShadowDriveClient<T>
 where
@@ -57,7 +57,7 @@ where
         let args = shdw_drive_instructions::RedeemRent {};
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -12,8 +12,6 @@ use spl_token::ID as TokenProgramID;
 
 use super::ShadowDriveClient;
 use crate::{
-    constants::{EMISSIONS, PROGRAM_ADDRESS, STORAGE_CONFIG_PDA, TOKEN_MINT, UPLOADER},
-    derived_addresses,
     error::Error,
     models::{
         storage_acct::{StorageAccount, StorageAccountV2, StorageAcct},
@@ -64,7 +62,7 @@ where
         let size_as_bytes: u64 = size
             .get_bytes()
             .try_into()
-            .map_err(|_| Error::InvalidStorage)?;
+            .map_err(|_| Error::InvalidStorage(size.get_bytes()))?;
 
         let selected_storage_acct = self.get_storage_account(storage_account_key).await?;
 
@@ -89,25 +87,27 @@ where
         size_as_bytes: u64,
     ) -> ShadowDriveResult<String> {
         let wallet_pubkey = self.wallet.pubkey();
-        let (unstake_account, _) = derived_addresses::unstake_account(storage_account_key);
-        let (unstake_info, _) = derived_addresses::unstake_info(storage_account_key);
+        let (unstake_account, _) = self
+            .derived_addresses()
+            .unstake_account(storage_account_key);
+        let (unstake_info, _) = self.derived_addresses().unstake_info(storage_account_key);
 
-        let owner_ata = get_associated_token_address(&wallet_pubkey, &TOKEN_MINT);
-        let (stake_account, _) = derived_addresses::stake_account(storage_account_key);
+        let owner_ata = get_associated_token_address(&wallet_pubkey, &self.token_mint);
+        let (stake_account, _) = self.derived_addresses().stake_account(storage_account_key);
 
-        let emeissions_ata = get_associated_token_address(&EMISSIONS, &TOKEN_MINT);
+        let emeissions_ata = get_associated_token_address(&self.emissions, &self.token_mint);
 
         let accounts = shdw_drive_accounts::DecreaseStorageV1 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             unstake_info,
             unstake_account,
             owner: storage_account.owner_1,
             owner_ata,
             stake_account,
-            uploader: UPLOADER,
+            uploader: self.uploader,
             emissions_wallet: emeissions_ata,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
             token_program: TokenProgramID,
             rent: rent::ID,
@@ -117,7 +117,7 @@ where
         };
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
@@ -140,25 +140,27 @@ where
         size_as_bytes: u64,
     ) -> ShadowDriveResult<String> {
         let wallet_pubkey = self.wallet.pubkey();
-        let (unstake_account, _) = derived_addresses::unstake_account(storage_account_key);
-        let (unstake_info, _) = derived_addresses::unstake_info(storage_account_key);
+        let (unstake_account, _) = self
+            .derived_addresses()
+            .unstake_account(storage_account_key);
+        let (unstake_info, _) = self.derived_addresses().unstake_info(storage_account_key);
 
-        let owner_ata = get_associated_token_address(&wallet_pubkey, &TOKEN_MINT);
-        let (stake_account, _) = derived_addresses::stake_account(storage_account_key);
+        let owner_ata = get_associated_token_address(&wallet_pubkey, &self.token_mint);
+        let (stake_account, _) = self.derived_addresses().stake_account(storage_account_key);
 
-        let emeissions_ata = get_associated_token_address(&EMISSIONS, &TOKEN_MINT);
+        let emeissions_ata = get_associated_token_address(&self.emissions, &self.token_mint);
 
         let accounts = shdw_drive_accounts::DecreaseStorageV2 {
-            storage_config: *STORAGE_CONFIG_PDA,
+            storage_config: self.storage_config,
             storage_account: *storage_account_key,
             unstake_info,
             unstake_account,
             owner: storage_account.owner_1,
             owner_ata,
             stake_account,
-            uploader: UPLOADER,
+            uploader: self.uploader,
             emissions_wallet: emeissions_ata,
-            token_mint: TOKEN_MINT,
+            token_mint: self.token_mint,
             system_program: system_program::ID,
             token_program: TokenProgramID,
             rent: rent::ID,
@@ -168,7 +170,7 @@ where
         };
 
         let instruction = Instruction {
-            program_id: PROGRAM_ADDRESS,
+            program_id: self.program_id,
             accounts: accounts.to_account_metas(None),
             data: args.data(),
         };
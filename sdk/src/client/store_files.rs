@@ -1,11 +1,10 @@
 use itertools::Itertools;
 use reqwest::multipart::{Form, Part};
-use serde_json::Value;
 use sha2::{Digest, Sha256};
 use solana_sdk::{pubkey::Pubkey, signer::Signer};
 
 use super::ShadowDriveClient;
-use crate::{constants::SHDW_DRIVE_ENDPOINT, error::Error, models::*};
+use crate::{error::Error, models::*};
 
 fn upload_message(storage_account_key: &Pubkey, filename_hash: &str) -> String {
     format!(
@@ -52,17 +51,16 @@ where
             .part("fileNames", Part::text(filenames));
 
         let response = self
-            .http_client
-            .post(format!("{}/upload", SHDW_DRIVE_ENDPOINT))
-            .multipart(form)
-            .send()
+            .send_request(
+                self.http_client
+                    .post(format!("{}/upload", self.storage_endpoint))
+                    .multipart(form),
+            )
             .await?;
+        self.invalidate_object_cache(storage_account_key);
 
         if !response.status().is_success() {
-            return Err(Error::ShadowDriveServerError {
-                status: response.status().as_u16(),
-                message: response.json::<Value>().await.unwrap_or(Value::Null),
-            });
+            return Err(Error::from_response(response).await);
         }
 
         let response = response.json::<ShadowUploadResponse>().await?;
@@ -70,3 +68,46 @@ where
         Ok(response)
     }
 }
+
+#[tokio::test]
+async fn test_store_files() {
+    use shadow_drive_mock::{MockShadowDrive, MockStorageAccount};
+
+    let drive = MockShadowDrive::start().unwrap();
+    let client = super::test_client(&drive);
+    let storage_account = Pubkey::new_unique();
+    drive.add_storage_account(MockStorageAccount::new(
+        storage_account,
+        client.wallet.pubkey(),
+    ));
+
+    let files = vec![
+        ShadowFile::bytes(String::from("a.txt"), &b"a"[..]),
+        ShadowFile::bytes(String::from("b.txt"), &b"bb"[..]),
+    ];
+    let response = client.store_files(&storage_account, files).await.unwrap();
+    assert_eq!(
+        response.finalized_locations,
+        vec![
+            client.object_url(&storage_account, "a.txt"),
+            client.object_url(&storage_account, "b.txt"),
+        ]
+    );
+    assert!(response.upload_errors.is_empty());
+    assert_eq!(drive.get_object(storage_account, "b.txt").unwrap(), b"bb");
+
+    // Existing files are reported per file
+    let files = vec![ShadowFile::bytes(String::from("a.txt"), &b"a"[..])];
+    let response = client.store_files(&storage_account, files).await.unwrap();
+    assert!(response.finalized_locations.is_empty());
+    assert_eq!(response.upload_errors[0].file, "a.txt");
+
+    // Only the owner may upload
+    let other = super::test_client(&drive);
+    let files = vec![ShadowFile::bytes(String::from("c.txt"), &b"c"[..])];
+    let error = other
+        .store_files(&storage_account, files)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, Error::NotFileOwner), "{error:?}");
+}
@@ -0,0 +1,619 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::{stream, Future, StreamExt};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+
+use super::ShadowDriveClient;
+use crate::{error::Error, models::*};
+
+/// Per-file progress reported by [`upload_files`](ShadowDriveClient::upload_files).
+#[derive(Clone, Debug)]
+pub enum UploadProgress {
+    /// A file with the same name is already in the storage account, or the manifest records
+    /// the same contents, so the file will not be uploaded.
+    Skipped {
+        file_name: String,
+    },
+    /// An attempt to upload the file started. `attempt` starts at 1.
+    Started {
+        file_name: String,
+        attempt: u32,
+    },
+    Uploaded {
+        file_name: String,
+        location: String,
+    },
+    Failed {
+        file_name: String,
+        error: String,
+    },
+}
+
+pub type UploadProgressCallback = Arc<dyn Fn(&UploadProgress) + Send + Sync>;
+
+/// Options for [`upload_files`](ShadowDriveClient::upload_files).
+#[derive(Clone)]
+pub struct UploadOptions {
+    concurrency: usize,
+    batch_size: usize,
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    manifest: Option<PathBuf>,
+    on_progress: Option<UploadProgressCallback>,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            batch_size: 5,
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            manifest: None,
+            on_progress: None,
+        }
+    }
+}
+
+impl fmt::Debug for UploadOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UploadOptions")
+            .field("concurrency", &self.concurrency)
+            .field("batch_size", &self.batch_size)
+            .field("max_retries", &self.max_retries)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("manifest", &self.manifest)
+            .field("on_progress", &self.on_progress.is_some())
+            .finish()
+    }
+}
+
+impl UploadOptions {
+    /// Maximum number of upload requests in flight at once. Defaults to 4.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Number of files sent in each upload request. Defaults to 5.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// How many times a request that failed with a [retryable](Error::is_retryable) error is
+    /// retried. Defaults to 5.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Delay before the first retry, which doubles after every retry up to `max`.
+    /// Defaults to 500ms, up to 30s.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Path of an [`UploadManifest`] recording finished uploads. It is created if missing,
+    /// and updated after every request, so running the same upload again resumes it.
+    ///
+    /// Uploads resume per file, not within a file: the storage API takes each file in a
+    /// single request, so a file whose upload was interrupted is sent again in full.
+    pub fn manifest<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.manifest = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Called with the progress of every file. Calls may come from concurrent uploads.
+    pub fn on_progress<F>(mut self, on_progress: F) -> Self
+    where
+        F: Fn(&UploadProgress) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+
+    fn report(&self, progress: UploadProgress) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(&progress);
+        }
+    }
+
+    /// Sends `request` until it succeeds, fails with an error that is not
+    /// [retryable](Error::is_retryable), or runs out of retries. `request` is given the
+    /// attempt number, starting at 1.
+    async fn retry<R, F, Fut>(&self, mut request: F) -> ShadowDriveResult<R>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = ShadowDriveResult<R>>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match request(attempt).await {
+                Err(e) if attempt <= self.max_retries && e.is_retryable() => {
+                    tracing::debug!(attempt, error = ?e, ?backoff, "request failed, retrying");
+                    let delay = match e {
+                        Error::RateLimited {
+                            retry_after: Some(retry_after),
+                        } => retry_after.max(backoff),
+                        _ => backoff,
+                    };
+                    tokio::time::sleep(delay).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A file recorded in an [`UploadManifest`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Hex encoded SHA-256 of the uploaded contents
+    pub sha256: String,
+    pub location: String,
+}
+
+/// Files that were uploaded to a storage account, kept on disk so that an interrupted
+/// [`upload_files`](ShadowDriveClient::upload_files) does not start over.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadManifest {
+    pub storage_account: String,
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+impl UploadManifest {
+    pub fn new(storage_account_key: &Pubkey) -> Self {
+        Self {
+            storage_account: storage_account_key.to_string(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the manifest at `path`, or returns an empty one if it doesn't exist.
+    /// Fails if the manifest belongs to a different storage account.
+    pub async fn load<P: AsRef<Path>>(
+        path: P,
+        storage_account_key: &Pubkey,
+    ) -> ShadowDriveResult<Self> {
+        let data = match tokio::fs::read(path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::new(storage_account_key))
+            }
+            Err(e) => return Err(Error::FileSystemError(e)),
+        };
+        let manifest: Self = serde_json::from_slice(&data).map_err(Error::InvalidJson)?;
+        if manifest.storage_account != storage_account_key.to_string() {
+            return Err(Error::UploadManifestMismatch {
+                manifest: manifest.storage_account,
+                storage_account: storage_account_key.to_string(),
+            });
+        }
+        Ok(manifest)
+    }
+
+    /// Writes the manifest to a temporary file next to `path` and moves it into place,
+    /// so an interrupted save never leaves a truncated manifest.
+    pub async fn save<P: AsRef<Path>>(&self, path: P) -> ShadowDriveResult<()> {
+        let path = path.as_ref();
+        let data = serde_json::to_vec_pretty(self).map_err(Error::InvalidJson)?;
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, data).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+}
+
+/// A file to upload, with its hash if it is recorded in a manifest.
+type PendingFile = (ShadowFile, Option<String>);
+
+impl<T> ShadowDriveClient<T>
+where
+    T: Signer,
+{
+    /// Uploads any number of files, sending [`batch_size`](UploadOptions::batch_size) files per
+    /// request with up to [`concurrency`](UploadOptions::concurrency) requests in flight.
+    /// * `storage_account_key` - The public key of the [`StorageAccount`](crate::models::StorageAccount) that will hold the files.
+    /// * `files` - The [`ShadowFile`](crate::models::ShadowFile)s to upload.
+    /// * `options` - Concurrency, retries, progress reporting and the resume manifest.
+    ///
+    /// Files that already exist in the storage account are skipped. They are matched by name
+    /// only, without comparing contents, so use [`edit_file`](Self::edit_file) to replace a file.
+    /// Requests that fail with a [retryable](Error::is_retryable) error, including the initial
+    /// listing of the storage account, are retried with exponential backoff. A failed upload request
+    /// does not stop the other uploads; its files are reported with [`BatchUploadStatus::Error`].
+    ///
+    /// If a manifest is set, files it records with the same contents are skipped without asking
+    /// the server, and files recorded with different contents are reported as errors.
+    /// # Example
+    ///
+    /// ```
+    /// # use shadow_drive_rust::{ShadowDriveClient, UploadOptions, UploadProgress, derived_addresses::storage_account};
+    /// # use solana_client::rpc_client::RpcClient;
+    /// # use solana_sdk::{
+    /// # pubkey::Pubkey,
+    /// # signature::Keypair,
+    /// # signer::{keypair::read_keypair_file, Signer},
+    /// # };
+    /// #
+    /// # let keypair = read_keypair_file(KEYPAIR_PATH).expect("failed to load keypair at path");
+    /// # let user_pubkey = keypair.pubkey();
+    /// # let rpc_client = RpcClient::new("https://ssc-dao.genesysgo.net");
+    /// # let shdw_drive_client = ShadowDriveClient::new(keypair, rpc_client);
+    /// # let (storage_account_key, _) = storage_account(&user_pubkey, 0);
+    /// # let files = vec![];
+    /// #
+    /// let options = UploadOptions::default()
+    ///     .concurrency(8)
+    ///     .manifest("upload-manifest.json")
+    ///     .on_progress(|progress| {
+    ///         if let UploadProgress::Uploaded { location, .. } = progress {
+    ///             println!("uploaded {location}");
+    ///         }
+    ///     });
+    /// let upload_results = shdw_drive_client
+    ///     .upload_files(&storage_account_key, files, options)
+    ///     .await?;
+    /// ```
+    pub async fn upload_files(
+        &self,
+        storage_account_key: &Pubkey,
+        files: Vec<ShadowFile>,
+        options: UploadOptions,
+    ) -> ShadowDriveResult<Vec<ShadowBatchUploadResponse>> {
+        let mut manifest = match &options.manifest {
+            Some(path) => UploadManifest::load(path, storage_account_key).await?,
+            None => UploadManifest::new(storage_account_key),
+        };
+        let existing: HashSet<String> = options
+            .retry(|_| self.list_objects(storage_account_key))
+            .await?
+            .into_iter()
+            .collect();
+
+        let mut results = Vec::with_capacity(files.len());
+        let mut pending: Vec<PendingFile> = Vec::with_capacity(files.len());
+        for file in files {
+            let sha256 = match &options.manifest {
+                Some(_) => Some(file.sha256().await?),
+                None => None,
+            };
+            let file_name = file.name().to_string();
+
+            let status = match (manifest.files.get(&file_name), &sha256) {
+                (Some(entry), Some(sha256)) if &entry.sha256 != sha256 => Some((
+                    BatchUploadStatus::Error(String::from("file changed since it was uploaded")),
+                    None,
+                )),
+                (Some(entry), _) => Some((
+                    BatchUploadStatus::AlreadyExists,
+                    Some(entry.location.clone()),
+                )),
+                (None, _) if existing.contains(&file_name) => Some((
+                    BatchUploadStatus::AlreadyExists,
+                    Some(self.object_url(storage_account_key, &file_name)),
+                )),
+                (None, _) => None,
+            };
+
+            match status {
+                Some((status, location)) => {
+                    match &status {
+                        BatchUploadStatus::Error(error) => options.report(UploadProgress::Failed {
+                            file_name: file_name.clone(),
+                            error: error.clone(),
+                        }),
+                        _ => options.report(UploadProgress::Skipped {
+                            file_name: file_name.clone(),
+                        }),
+                    }
+                    results.push(ShadowBatchUploadResponse {
+                        file_name,
+                        status,
+                        location,
+                        transaction_signature: None,
+                    });
+                }
+                None => pending.push((file, sha256)),
+            }
+        }
+
+        if !results.is_empty() {
+            tracing::debug!(
+                skipped = results.len(),
+                "skipping files that were already uploaded"
+            );
+        }
+
+        let batches: Vec<Vec<PendingFile>> = pending
+            .into_iter()
+            .chunks(options.batch_size)
+            .into_iter()
+            .map(Iterator::collect)
+            .collect();
+
+        let mut uploads = stream::iter(batches)
+            .map(|batch| self.upload_batch(storage_account_key, batch, &options))
+            .buffer_unordered(options.concurrency);
+
+        while let Some((batch, response)) = uploads.next().await {
+            let response = match response {
+                Ok(response) => response,
+                Err(e) => {
+                    let error = format!("{:?}", e);
+                    for (file, _) in batch {
+                        options.report(UploadProgress::Failed {
+                            file_name: file.name.clone(),
+                            error: error.clone(),
+                        });
+                        results.push(ShadowBatchUploadResponse {
+                            file_name: file.name,
+                            status: BatchUploadStatus::Error(error.clone()),
+                            location: None,
+                            transaction_signature: None,
+                        });
+                    }
+                    continue;
+                }
+            };
+
+            let upload_errors: HashMap<&str, &str> = response
+                .upload_errors
+                .iter()
+                .map(|e| (e.file.as_str(), e.error.as_str()))
+                .collect();
+            for (file, sha256) in batch {
+                if let Some(error) = upload_errors.get(file.name()) {
+                    options.report(UploadProgress::Failed {
+                        file_name: file.name.clone(),
+                        error: error.to_string(),
+                    });
+                    results.push(ShadowBatchUploadResponse {
+                        file_name: file.name,
+                        status: BatchUploadStatus::Error(error.to_string()),
+                        location: None,
+                        transaction_signature: None,
+                    });
+                    continue;
+                }
+
+                let suffix = format!("/{}", file.name());
+                let location = response
+                    .finalized_locations
+                    .iter()
+                    .find(|location| location.ends_with(&suffix))
+                    .cloned()
+                    .unwrap_or_else(|| self.object_url(storage_account_key, file.name()));
+                options.report(UploadProgress::Uploaded {
+                    file_name: file.name.clone(),
+                    location: location.clone(),
+                });
+                if let Some(sha256) = sha256 {
+                    manifest.files.insert(
+                        file.name.clone(),
+                        ManifestEntry {
+                            sha256,
+                            location: location.clone(),
+                        },
+                    );
+                }
+                results.push(ShadowBatchUploadResponse {
+                    file_name: file.name,
+                    status: BatchUploadStatus::Uploaded,
+                    location: Some(location),
+                    transaction_signature: None,
+                });
+            }
+
+            if let Some(path) = &options.manifest {
+                manifest.save(path).await?;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Sends one [`store_files`](Self::store_files) request, retrying transient failures.
+    /// Returns the batch along with the result so the caller can attribute it to files.
+    async fn upload_batch(
+        &self,
+        storage_account_key: &Pubkey,
+        batch: Vec<PendingFile>,
+        options: &UploadOptions,
+    ) -> (Vec<PendingFile>, ShadowDriveResult<ShadowUploadResponse>) {
+        let result = options
+            .retry(|attempt| {
+                for (file, _) in &batch {
+                    options.report(UploadProgress::Started {
+                        file_name: file.name.clone(),
+                        attempt,
+                    });
+                }
+                let files = batch.iter().map(|(file, _)| file.clone()).collect();
+                self.store_files(storage_account_key, files)
+            })
+            .await;
+        (batch, result)
+    }
+}
+
+#[cfg(test)]
+fn test_file(name: &str, data: &'static [u8]) -> ShadowFile {
+    ShadowFile::bytes(name.to_string(), data)
+}
+
+#[cfg(test)]
+fn test_status<'a>(
+    results: &'a [ShadowBatchUploadResponse],
+    file_name: &str,
+) -> &'a BatchUploadStatus {
+    &results
+        .iter()
+        .find(|result| result.file_name == file_name)
+        .unwrap()
+        .status
+}
+
+#[tokio::test]
+async fn test_upload_files_retries() {
+    use shadow_drive_mock::MockShadowDrive;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let client = super::test_client(&drive);
+    let storage_account = Pubkey::new_unique();
+    let options =
+        || UploadOptions::default().backoff(Duration::from_millis(1), Duration::from_millis(1));
+
+    // The listing and the upload are both retried
+    drive.fail_next("/list-objects", 1, 503);
+    drive.fail_next("/upload", 2, 503);
+    let results = client
+        .upload_files(&storage_account, vec![test_file("a.txt", b"a")], options())
+        .await
+        .unwrap();
+    assert!(matches!(
+        test_status(&results, "a.txt"),
+        BatchUploadStatus::Uploaded
+    ));
+    assert_eq!(drive.requests("/list-objects"), 2);
+    assert_eq!(drive.requests("/upload"), 3);
+
+    // Rate limits are retried until max_retries runs out
+    drive.fail_next("/upload", 3, 429);
+    let results = client
+        .upload_files(
+            &storage_account,
+            vec![test_file("b.txt", b"b")],
+            options().max_retries(2),
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        test_status(&results, "b.txt"),
+        BatchUploadStatus::Error(_)
+    ));
+    assert_eq!(drive.requests("/upload"), 6);
+
+    // Client errors are not retried
+    drive.fail_next("/upload", 1, 400);
+    let results = client
+        .upload_files(&storage_account, vec![test_file("c.txt", b"c")], options())
+        .await
+        .unwrap();
+    assert!(matches!(
+        test_status(&results, "c.txt"),
+        BatchUploadStatus::Error(_)
+    ));
+    assert_eq!(drive.requests("/upload"), 7);
+
+    // Files are skipped by name, whatever their contents
+    let results = client
+        .upload_files(
+            &storage_account,
+            vec![test_file("a.txt", b"changed")],
+            options(),
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        test_status(&results, "a.txt"),
+        BatchUploadStatus::AlreadyExists
+    ));
+    assert_eq!(drive.requests("/upload"), 7);
+}
+
+#[tokio::test]
+async fn test_upload_files_manifest() {
+    use shadow_drive_mock::MockShadowDrive;
+
+    let drive = MockShadowDrive::start().unwrap();
+    let client = super::test_client(&drive);
+    let storage_account = Pubkey::new_unique();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("manifest.json");
+
+    let files = vec![test_file("a.txt", b"a"), test_file("b.txt", b"b")];
+    let results = client
+        .upload_files(
+            &storage_account,
+            files,
+            UploadOptions::default().manifest(&path),
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        test_status(&results, "a.txt"),
+        BatchUploadStatus::Uploaded
+    ));
+    assert!(matches!(
+        test_status(&results, "b.txt"),
+        BatchUploadStatus::Uploaded
+    ));
+    let manifest = UploadManifest::load(&path, &storage_account).await.unwrap();
+    assert_eq!(manifest.files.len(), 2);
+    assert_eq!(
+        manifest.files["a.txt"].location,
+        client.object_url(&storage_account, "a.txt")
+    );
+
+    // Running again resumes from the manifest, and rejects changed files
+    let files = vec![
+        test_file("a.txt", b"a"),
+        test_file("b.txt", b"changed"),
+        test_file("c.txt", b"c"),
+    ];
+    let results = client
+        .upload_files(
+            &storage_account,
+            files,
+            UploadOptions::default().manifest(&path),
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        test_status(&results, "a.txt"),
+        BatchUploadStatus::AlreadyExists
+    ));
+    assert!(matches!(
+        test_status(&results, "b.txt"),
+        BatchUploadStatus::Error(_)
+    ));
+    assert!(matches!(
+        test_status(&results, "c.txt"),
+        BatchUploadStatus::Uploaded
+    ));
+    assert_eq!(drive.requests("/upload"), 2);
+    let manifest = UploadManifest::load(&path, &storage_account).await.unwrap();
+    assert_eq!(manifest.files.len(), 3);
+
+    // The manifest can't be used for another storage account
+    let error = client
+        .upload_files(
+            &Pubkey::new_unique(),
+            vec![test_file("d.txt", b"d")],
+            UploadOptions::default().manifest(&path),
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, Error::UploadManifestMismatch { .. }),
+        "{error:?}"
+    );
+}
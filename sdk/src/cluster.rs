@@ -0,0 +1,228 @@
+//! Named sets of the endpoints and addresses a Shadow Drive deployment uses, so the same code
+//! can talk to Mainnet, Devnet or a local validator.
+//!
+//! Apply a profile with [`ShadowDriveClientBuilder::cluster`](crate::ShadowDriveClientBuilder::cluster)
+//! and [`PortalClient::with_cluster`](crate::portal::PortalClient::with_cluster).
+
+use std::str::FromStr;
+
+use chain_drive::{payout_authority, shdw, ADMIN};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    constants::{
+        EMISSIONS, PROGRAM_ADDRESS, SHDW_DRIVE_ENDPOINT, SHDW_DRIVE_OBJECT_PREFIX, TOKEN_MINT,
+        UPLOADER,
+    },
+    models::storage_acct::{deserialize_pubkey, serialize_pubkey},
+};
+
+/// SHDW mint that the portal program accepts when built without its `mainnet` feature.
+pub static PORTAL_TEST_SHDW_MINT: Pubkey =
+    solana_sdk::pubkey!("RUNEkHeD5P8DsSpuDwxyZZKsj3T9e1ooMiaXL9H71yc");
+
+/// Admin and payout authority of the Mainnet portal program.
+pub static PORTAL_MAINNET_AUTHORITY: Pubkey =
+    solana_sdk::pubkey!("D6wZ5U9onMC578mrKMp5PZtfyc5262426qKsYJW7nT3p");
+
+/// Admin and payout authority of the portal program built without its `mainnet` feature.
+pub static PORTAL_TEST_AUTHORITY: Pubkey =
+    solana_sdk::pubkey!("2tW633ym3SpeMaBaAoCM7XsdCSHFB7KRGq6YmUvaFHFu");
+
+/// Endpoints and addresses of one Shadow Drive deployment. Pubkeys (de)serialize as base58.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cluster {
+    pub rpc_url: String,
+    /// Endpoint used for file uploads and fetching object data
+    pub storage_endpoint: String,
+    /// Prefix of the public URLs that stored objects are served from
+    pub object_prefix: String,
+    /// Address of the Shadow Drive program
+    #[serde(
+        serialize_with = "serialize_pubkey",
+        deserialize_with = "deserialize_pubkey"
+    )]
+    pub program_id: Pubkey,
+    /// Address of the SHDW token mint used by the Shadow Drive program
+    #[serde(
+        serialize_with = "serialize_pubkey",
+        deserialize_with = "deserialize_pubkey"
+    )]
+    pub token_mint: Pubkey,
+    /// Address of the upload authority
+    #[serde(
+        serialize_with = "serialize_pubkey",
+        deserialize_with = "deserialize_pubkey"
+    )]
+    pub uploader: Pubkey,
+    /// Owner of the token account that receives fees for immutable storage
+    #[serde(
+        serialize_with = "serialize_pubkey",
+        deserialize_with = "deserialize_pubkey"
+    )]
+    pub emissions: Pubkey,
+    /// Address of the chain-drive portal program
+    #[serde(
+        serialize_with = "serialize_pubkey",
+        deserialize_with = "deserialize_pubkey"
+    )]
+    pub portal_program_id: Pubkey,
+    /// SHDW mint the portal program takes its fee in
+    #[serde(
+        serialize_with = "serialize_pubkey",
+        deserialize_with = "deserialize_pubkey"
+    )]
+    pub portal_shdw_mint: Pubkey,
+    /// Owner of the token account that uploads pay summons' SHDW into
+    #[serde(
+        serialize_with = "serialize_pubkey",
+        deserialize_with = "deserialize_pubkey"
+    )]
+    pub payout_authority: Pubkey,
+    /// The portal's admin, who may set its fee
+    #[serde(
+        serialize_with = "serialize_pubkey",
+        deserialize_with = "deserialize_pubkey"
+    )]
+    pub admin: Pubkey,
+    /// The portal's config account, the PDA of `portal_program_id` if not set
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_pubkey",
+        deserialize_with = "deserialize_optional_pubkey"
+    )]
+    pub portal_config: Option<Pubkey>,
+}
+
+impl Cluster {
+    /// GenesysGo's Mainnet deployment, with the Mainnet build of the portal program.
+    pub fn mainnet() -> Self {
+        Self {
+            rpc_url: "https://ssc-dao.genesysgo.net".to_string(),
+            storage_endpoint: SHDW_DRIVE_ENDPOINT.to_string(),
+            object_prefix: SHDW_DRIVE_OBJECT_PREFIX.to_string(),
+            program_id: PROGRAM_ADDRESS,
+            token_mint: TOKEN_MINT,
+            uploader: UPLOADER,
+            emissions: EMISSIONS,
+            portal_program_id: chain_drive::ID,
+            portal_shdw_mint: TOKEN_MINT,
+            payout_authority: PORTAL_MAINNET_AUTHORITY,
+            admin: PORTAL_MAINNET_AUTHORITY,
+            portal_config: None,
+        }
+    }
+
+    /// Solana Devnet with the portal program built without its `mainnet` feature. Shadow Drive
+    /// itself has no Devnet deployment, so storage requests still go to the Mainnet addresses
+    /// unless they are overridden.
+    pub fn devnet() -> Self {
+        Self {
+            rpc_url: "https://api.devnet.solana.com".to_string(),
+            portal_shdw_mint: PORTAL_TEST_SHDW_MINT,
+            payout_authority: PORTAL_TEST_AUTHORITY,
+            admin: PORTAL_TEST_AUTHORITY,
+            ..Self::mainnet()
+        }
+    }
+
+    /// A local validator with the programs at their Mainnet addresses, and the storage API
+    /// served on `localhost:8080`, e.g. by the mock server.
+    pub fn localnet() -> Self {
+        Self {
+            rpc_url: "http://localhost:8899".to_string(),
+            storage_endpoint: "http://localhost:8080".to_string(),
+            object_prefix: "http://localhost:8080".to_string(),
+            portal_shdw_mint: PORTAL_TEST_SHDW_MINT,
+            payout_authority: PORTAL_TEST_AUTHORITY,
+            admin: PORTAL_TEST_AUTHORITY,
+            ..Self::mainnet()
+        }
+    }
+
+    /// The built-in profile called `name`: `mainnet`, `devnet` or `localnet`.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "mainnet" | "mainnet-beta" => Some(Self::mainnet()),
+            "devnet" => Some(Self::devnet()),
+            "localnet" => Some(Self::localnet()),
+            _ => None,
+        }
+    }
+
+    /// The portal's config account.
+    pub fn portal_config(&self) -> Pubkey {
+        self.portal_config.unwrap_or_else(|| {
+            Pubkey::find_program_address(&[b"portal-room"], &self.portal_program_id).0
+        })
+    }
+}
+
+/// Mainnet storage with the portal program, SHDW mint, payout authority and admin that the
+/// chain-drive crate was built with, which is what the clients use without a cluster.
+impl Default for Cluster {
+    fn default() -> Self {
+        Self {
+            portal_program_id: chain_drive::ID,
+            portal_shdw_mint: shdw::ID,
+            payout_authority: payout_authority::ID,
+            admin: Pubkey::from_str(ADMIN).expect("ADMIN is a valid pubkey"),
+            ..Self::mainnet()
+        }
+    }
+}
+
+fn serialize_optional_pubkey<S>(pubkey: &Option<Pubkey>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match pubkey {
+        Some(pubkey) => serialize_pubkey(pubkey, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_optional_pubkey<'de, D>(deserializer: D) -> Result<Option<Pubkey>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_pubkey(deserializer).map(Some)
+}
+
+#[test]
+fn test_cluster_serde() {
+    for name in ["mainnet", "mainnet-beta", "devnet", "localnet"] {
+        let cluster = Cluster::builtin(name).unwrap();
+        let value = serde_json::to_value(&cluster).unwrap();
+        assert_eq!(
+            value["program_id"],
+            serde_json::json!(PROGRAM_ADDRESS.to_string())
+        );
+        assert!(value.get("portal_config").is_none());
+        assert_eq!(serde_json::from_value::<Cluster>(value).unwrap(), cluster);
+    }
+    assert_eq!(Cluster::builtin("testnet"), None);
+
+    // The portal config is derived from the portal program unless it is set
+    let mut value = serde_json::to_value(Cluster::localnet()).unwrap();
+    let config = Pubkey::new_unique();
+    value["portal_config"] = serde_json::json!(config.to_string());
+    let cluster: Cluster = serde_json::from_value(value.clone()).unwrap();
+    assert_eq!(cluster.portal_config(), config);
+    assert_eq!(
+        Cluster::localnet().portal_config(),
+        Pubkey::find_program_address(&[b"portal-room"], &chain_drive::ID).0
+    );
+
+    // Misspelled fields are rejected instead of silently ignored
+    value["rpc"] = serde_json::json!("http://localhost:8899");
+    let error = serde_json::from_value::<Cluster>(value).unwrap_err();
+    assert!(
+        error.to_string().starts_with("unknown field `rpc`"),
+        "{}",
+        error
+    );
+}
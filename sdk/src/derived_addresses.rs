@@ -2,62 +2,125 @@ use solana_sdk::pubkey::Pubkey;
 
 use crate::constants::PROGRAM_ADDRESS;
 
+/// Derives program addresses for a specific deployment of the Shadow Drive program.
+///
+/// The free functions in this module derive addresses for the Mainnet program at [`PROGRAM_ADDRESS`].
+/// A [`ShadowDriveClient`](crate::ShadowDriveClient) configured with a different program ID
+/// exposes the matching `DerivedAddresses` through [`derived_addresses`](crate::ShadowDriveClient::derived_addresses).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DerivedAddresses {
+    pub program_id: Pubkey,
+}
+
+impl Default for DerivedAddresses {
+    fn default() -> Self {
+        Self::new(PROGRAM_ADDRESS)
+    }
+}
+
+impl DerivedAddresses {
+    pub fn new(program_id: Pubkey) -> Self {
+        Self { program_id }
+    }
+
+    /// Returns the program derived address and bump seed for a [`StorageAccount`](crate::models::StorageAccount).
+    pub fn storage_account(&self, wallet_pubkey: &Pubkey, account_seed: u32) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                &b"storage-account"[..],
+                &wallet_pubkey.to_bytes(),
+                &account_seed.to_le_bytes(),
+            ],
+            &self.program_id,
+        )
+    }
+
+    /// Returns the program derived address and bump seed for a [`StorageAccount`](crate::models::StorageAccount)'s [`FileAccount`](crate::models::FileAccount).
+    pub fn file_account(&self, storage_account: &Pubkey, file_seed: u32) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&storage_account.to_bytes(), &file_seed.to_le_bytes()],
+            &self.program_id,
+        )
+    }
+
+    /// Returns the program derived address and bump seed for a wallet's [`UserInfo`](crate::models::UserInfo) account.
+    pub fn user_info(&self, wallet_pubkey: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&b"user-info"[..], &wallet_pubkey.to_bytes()],
+            &self.program_id,
+        )
+    }
+
+    /// Returns the program derived address and bump seed for a [`StorageAccount`](crate::models::StorageAccount)'s stake account.
+    /// The stake account is a SHDW token account that holds user's stake.
+    pub fn stake_account(&self, storage_account: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&b"stake-account"[..], &storage_account.to_bytes()],
+            &self.program_id,
+        )
+    }
+
+    /// Returns the program derived address and bump seed for a [`StorageAccount`](crate::models::StorageAccount)'s stake account.
+    /// The unstake account is a token account that handles SHDW when unstaking.
+    pub fn unstake_account(&self, storage_account: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&b"unstake-account"[..], &storage_account.to_bytes()],
+            &self.program_id,
+        )
+    }
+
+    /// Returns the program derived address and bump seed for an [`UnstakeInfo`](crate::models::UnstakeInfo).
+    pub fn unstake_info(&self, storage_account: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&b"unstake-info"[..], &storage_account.to_bytes()],
+            &self.program_id,
+        )
+    }
+
+    pub fn migration_helper(&self, storage_account: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&b"migration-helper"[..], &storage_account.to_bytes()],
+            &self.program_id,
+        )
+    }
+
+    /// Returns the program derived address that holds storage config parameters and admin pubkeys.
+    pub fn storage_config(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"storage-config"], &self.program_id).0
+    }
+}
+
 /// Returns the program derived address and bump seed for a [`StorageAccount`](crate::models::StorageAccount).
 pub fn storage_account(wallet_pubkey: &Pubkey, account_seed: u32) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[
-            &b"storage-account"[..],
-            &wallet_pubkey.to_bytes(),
-            &account_seed.to_le_bytes(),
-        ],
-        &PROGRAM_ADDRESS,
-    )
+    DerivedAddresses::default().storage_account(wallet_pubkey, account_seed)
 }
 
 /// Returns the program derived address and bump seed for a [`StorageAccount`](crate::models::StorageAccount)'s [`FileAccount`](crate::models::FileAccount).
 pub fn file_account(storage_account: &Pubkey, file_seed: u32) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[&storage_account.to_bytes(), &file_seed.to_le_bytes()],
-        &PROGRAM_ADDRESS,
-    )
+    DerivedAddresses::default().file_account(storage_account, file_seed)
 }
 
 /// Returns the program derived address and bump seed for a wallet's [`UserInfo`](crate::models::UserInfo) account.
 pub fn user_info(wallet_pubkey: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[&b"user-info"[..], &wallet_pubkey.to_bytes()],
-        &PROGRAM_ADDRESS,
-    )
+    DerivedAddresses::default().user_info(wallet_pubkey)
 }
 
 /// Returns the program derived address and bump seed for a [`StorageAccount`](crate::models::StorageAccount)'s stake account.
 /// The stake account is a SHDW token account that holds user's stake.
 pub fn stake_account(storage_account: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[&b"stake-account"[..], &storage_account.to_bytes()],
-        &PROGRAM_ADDRESS,
-    )
+    DerivedAddresses::default().stake_account(storage_account)
 }
 /// Returns the program derived address and bump seed for a [`StorageAccount`](crate::models::StorageAccount)'s stake account.
 /// The unstake account is a token account that handles SHDW when unstaking.
 pub fn unstake_account(storage_account: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[&b"unstake-account"[..], &storage_account.to_bytes()],
-        &PROGRAM_ADDRESS,
-    )
+    DerivedAddresses::default().unstake_account(storage_account)
 }
 
 /// Returns the program derived address and bump seed for an [`UnstakeInfo`](crate::models::UnstakeInfo).
 pub fn unstake_info(storage_account: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[&b"unstake-info"[..], &storage_account.to_bytes()],
-        &PROGRAM_ADDRESS,
-    )
+    DerivedAddresses::default().unstake_info(storage_account)
 }
 
 pub fn migration_helper(storage_account: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[&b"migration-helper"[..], &storage_account.to_bytes()],
-        &PROGRAM_ADDRESS,
-    )
+    DerivedAddresses::default().migration_helper(storage_account)
 }
@@ -0,0 +1,237 @@
+//! Optional client side encryption of objects with AES-256-GCM-SIV.
+//!
+//! An encrypted object is stored as
+//!
+//! ```text
+//! [ ENCRYPTION_MAGIC: 4 ][ key id: 8 ][ nonce: 12 ][ ciphertext and tag ]
+//! ```
+//!
+//! The header and the object's name are authenticated along with the contents, so an object
+//! can't be decrypted under another name. Runes of encrypted objects record the hash of the
+//! stored bytes, header included, and the key id (see `runes::keys`), so readers of a runes file
+//! know which key an object needs.
+//!
+//! The nonce is an HMAC-SHA256 of the name and contents keyed with the encryption key, so
+//! encrypting the same file twice gives the same bytes and unchanged files can still be detected
+//! by hash. AES-GCM-SIV stays secure when nonces repeat. Without the key the nonce can't be used
+//! to confirm a guess of the contents; the only thing it reveals is that an object was stored
+//! again unchanged.
+
+use std::{fmt, path::Path};
+
+use aes_gcm_siv::{
+    aead::{Aead, NewAead, Payload},
+    Aes256GcmSiv, Key, Nonce,
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use solana_sdk::signer::Signer;
+
+use crate::{error::Error, models::ShadowDriveResult};
+
+/// Marks the start of an encrypted object.
+pub const ENCRYPTION_MAGIC: [u8; 4] = *b"SDE1";
+
+/// Length of the header before the ciphertext of an encrypted object.
+pub const ENCRYPTION_HEADER_LEN: usize = ENCRYPTION_MAGIC.len() + 8 + 12;
+
+/// Message signed by [`EncryptionKey::from_signer`].
+pub const ENCRYPTION_KEY_MESSAGE: &str = "Shadow Drive encryption key v1";
+
+/// A 256-bit key for encrypting objects. [`Debug`](fmt::Debug) only shows its id.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    /// Derives a key from the signer's signature of [`ENCRYPTION_KEY_MESSAGE`]. Ed25519
+    /// signatures are deterministic, so the same signer always derives the same key.
+    pub fn from_signer<S: Signer + ?Sized>(signer: &S) -> ShadowDriveResult<Self> {
+        let signature = signer.try_sign_message(ENCRYPTION_KEY_MESSAGE.as_bytes())?;
+        let mut hasher = Sha256::new();
+        hasher.update(ENCRYPTION_KEY_MESSAGE);
+        hasher.update(signature);
+        Ok(Self(hasher.finalize().into()))
+    }
+
+    /// Reads a key file holding the 32 key bytes, either raw or hex encoded.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> ShadowDriveResult<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path)?;
+        let hex_key = std::str::from_utf8(&contents)
+            .ok()
+            .and_then(|text| hex::decode(text.trim()).ok());
+        hex_key
+            .as_deref()
+            .unwrap_or(&contents)
+            .try_into()
+            .map(Self)
+            .map_err(|_| {
+                Error::InvalidEncryptionKey(format!(
+                    "{} must hold 32 bytes, raw or hex encoded",
+                    path.display()
+                ))
+            })
+    }
+
+    /// Identifies the key without revealing it.
+    pub fn id(&self) -> [u8; 8] {
+        let hash = Sha256::new()
+            .chain_update("Shadow Drive encryption key id")
+            .chain_update(self.0)
+            .finalize();
+        hash[..8].try_into().expect("hash is 32 bytes")
+    }
+
+    /// Encrypts the contents of the object `name`.
+    pub fn encrypt(&self, name: &str, plaintext: &[u8]) -> ShadowDriveResult<Vec<u8>> {
+        let nonce = <Hmac<Sha256> as Mac>::new_from_slice(&self.0)
+            .expect("HMAC takes keys of any length")
+            .chain_update("Shadow Drive encryption nonce")
+            .chain_update((name.len() as u64).to_le_bytes())
+            .chain_update(name)
+            .chain_update(plaintext)
+            .finalize()
+            .into_bytes();
+
+        let mut data = Vec::with_capacity(ENCRYPTION_HEADER_LEN + plaintext.len() + 16);
+        data.extend_from_slice(&ENCRYPTION_MAGIC);
+        data.extend_from_slice(&self.id());
+        data.extend_from_slice(&nonce[..12]);
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                Nonce::from_slice(&nonce[..12]),
+                Payload {
+                    msg: plaintext,
+                    aad: &aad(&data, name),
+                },
+            )
+            .map_err(|_| Error::EncryptionFailed(name.to_string()))?;
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+
+    /// Decrypts the object `name`, which must have been encrypted with this key.
+    pub fn decrypt(&self, name: &str, data: &[u8]) -> ShadowDriveResult<Vec<u8>> {
+        let key_id =
+            encryption_key_id(data).ok_or_else(|| Error::NotEncrypted(name.to_string()))?;
+        if key_id != self.id() {
+            return Err(Error::WrongEncryptionKey {
+                name: name.to_string(),
+                expected: key_id,
+                actual: self.id(),
+            });
+        }
+        let (header, ciphertext) = data.split_at(ENCRYPTION_HEADER_LEN);
+        self.cipher()
+            .decrypt(
+                Nonce::from_slice(&header[12..]),
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad(header, name),
+                },
+            )
+            .map_err(|_| Error::DecryptionFailed(name.to_string()))
+    }
+
+    fn cipher(&self) -> Aes256GcmSiv {
+        Aes256GcmSiv::new(Key::from_slice(&self.0))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EncryptionKey({})", hex::encode(self.id()))
+    }
+}
+
+/// The id of the key an object was encrypted with, `None` if it is not encrypted.
+pub fn encryption_key_id(data: &[u8]) -> Option<[u8; 8]> {
+    if data.len() < ENCRYPTION_HEADER_LEN || data[..4] != ENCRYPTION_MAGIC {
+        return None;
+    }
+    data[4..12].try_into().ok()
+}
+
+fn aad(header: &[u8], name: &str) -> Vec<u8> {
+    [header, name.as_bytes()].concat()
+}
+
+#[test]
+fn test_encrypt_round_trip() {
+    let key = EncryptionKey::new([1; 32]);
+    let data = key.encrypt("a.txt", b"secret").unwrap();
+    assert_eq!(data.len(), ENCRYPTION_HEADER_LEN + b"secret".len() + 16);
+    assert_eq!(encryption_key_id(&data), Some(key.id()));
+    assert_eq!(key.decrypt("a.txt", &data).unwrap(), b"secret");
+
+    // Deterministic, but the nonce depends on the key
+    assert_eq!(key.encrypt("a.txt", b"secret").unwrap(), data);
+    let other = EncryptionKey::new([2; 32])
+        .encrypt("a.txt", b"secret")
+        .unwrap();
+    assert_ne!(
+        other[12..ENCRYPTION_HEADER_LEN],
+        data[12..ENCRYPTION_HEADER_LEN]
+    );
+
+    assert!(matches!(
+        key.decrypt("a.txt", b"secret"),
+        Err(Error::NotEncrypted(_))
+    ));
+}
+
+#[test]
+fn test_decrypt_wrong_key_or_name() {
+    let key = EncryptionKey::new([1; 32]);
+    let other = EncryptionKey::new([2; 32]);
+    let data = key.encrypt("a.txt", b"secret").unwrap();
+
+    match other.decrypt("a.txt", &data) {
+        Err(Error::WrongEncryptionKey {
+            expected, actual, ..
+        }) => {
+            assert_eq!(expected, key.id());
+            assert_eq!(actual, other.id());
+        }
+        result => panic!("decrypt with the wrong key: {:?}", result),
+    }
+    assert!(matches!(
+        key.decrypt("b.txt", &data),
+        Err(Error::DecryptionFailed(_))
+    ));
+}
+
+#[test]
+fn test_decrypt_tampered() {
+    let key = EncryptionKey::new([1; 32]);
+    let data = key.encrypt("a.txt", b"secret").unwrap();
+
+    // Nonce
+    let mut tampered = data.clone();
+    tampered[ENCRYPTION_HEADER_LEN - 1] ^= 1;
+    assert!(matches!(
+        key.decrypt("a.txt", &tampered),
+        Err(Error::DecryptionFailed(_))
+    ));
+
+    // Key id
+    let mut tampered = data.clone();
+    tampered[4] ^= 1;
+    assert!(matches!(
+        key.decrypt("a.txt", &tampered),
+        Err(Error::WrongEncryptionKey { .. })
+    ));
+
+    // Ciphertext
+    let mut tampered = data;
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        key.decrypt("a.txt", &tampered),
+        Err(Error::DecryptionFailed(_))
+    ));
+}
@@ -1,33 +1,115 @@
-use std::io::Error as IoError;
+use std::{fmt, io::Error as IoError, time::Duration};
 
 use anchor_lang::error::Error as AnchorError;
-use reqwest::Error as ReqwestError;
-use solana_client::client_error::ClientError;
-use solana_sdk::{pubkey::ParsePubkeyError, signer::SignerError};
+pub use chain_drive::PortalError;
+use reqwest::{header::RETRY_AFTER, Error as ReqwestError, Response, StatusCode};
+use serde_json::Value;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    pubsub_client::PubsubClientError,
+};
+use solana_sdk::{
+    pubkey::{ParsePubkeyError, Pubkey},
+    signer::SignerError,
+};
+use thiserror::Error as ThisError;
 use tokio::task::JoinError;
 
-#[derive(Debug)]
+#[derive(Debug, ThisError)]
 pub enum Error {
+    /// The storage account does not have enough free space for the request.
+    #[error("not enough storage: {message}")]
+    InsufficientStorage { message: String },
+    /// A file with the same name already exists in the storage account.
+    #[error("file already exists: {message}")]
+    FileAlreadyExists { message: String },
+    /// Shadow Drive rejected the request, retry after `retry_after` if it is set.
+    #[error("rate limited by Shadow Drive")]
+    RateLimited { retry_after: Option<Duration> },
+    /// Any other error response from Shadow Drive.
+    #[error("Shadow Drive server error {status}: {message}")]
     ShadowDriveServerError {
         status: u16,
         message: serde_json::Value,
     },
+    /// A portal instruction failed with one of the program's error codes.
+    #[error("portal error: {0}")]
+    PortalError(PortalError),
+    /// The summon's account was closed, or never existed, before its upload was seen.
+    #[error("summon {0} was closed before its data was uploaded")]
+    SummonClosed(Pubkey),
+    #[error("timed out waiting for summon {0} to be uploaded")]
+    UploadTimeout(Pubkey),
+    /// Only the portal's admin, the contained key, may change its config.
+    #[error("only the portal admin {0} may change its config")]
+    NotPortalAdmin(Pubkey),
+    #[error("websocket error: {0}")]
+    PubsubError(#[from] PubsubClientError),
+    #[error("file too large: {0}")]
     FileTooLarge(String),
+    #[error("failed to serialize transaction: {0}")]
     TransactionSerializationFailed(String),
-    InvalidJson(serde_json::Error),
-    SolanaRpcError(ClientError),
-    AccountDeserializeError(IoError),
-    InvalidStorage,
-    SignerError(SignerError),
-    AnchorError(AnchorError),
-    ReqwestError(ReqwestError),
-    AsyncJoinError(JoinError),
+    #[error("invalid json: {0}")]
+    InvalidJson(#[source] serde_json::Error),
+    #[error("solana rpc error: {0}")]
+    SolanaRpcError(#[source] ClientError),
+    #[error("failed to deserialize account: {0}")]
+    AccountDeserializeError(#[source] IoError),
+    #[error("requested storage of {0} bytes does not fit in a u64")]
+    InvalidStorage(u128),
+    /// No token could be had for an authenticated storage endpoint.
+    #[error("authentication failed: {0}")]
+    AuthFailed(String),
+    #[error("signer error: {0}")]
+    SignerError(#[from] SignerError),
+    #[error("anchor error: {0}")]
+    AnchorError(#[from] AnchorError),
+    #[error("http error: {0}")]
+    ReqwestError(#[from] ReqwestError),
+    #[error("async task failed: {0}")]
+    AsyncJoinError(#[from] JoinError),
+    #[error("invalid files: {}", FileErrors(.0))]
     FileValidationError(Vec<FileError>),
+    #[error("user info account has not been created")]
     UserInfoNotCreated,
-    FileSystemError(std::io::Error),
-    ParsePubkeyError(ParsePubkeyError),
+    #[error("filesystem error: {0}")]
+    FileSystemError(#[from] std::io::Error),
+    #[error("invalid pubkey: {0}")]
+    ParsePubkeyError(#[from] ParsePubkeyError),
+    #[error("signer does not own the storage account")]
     NotFileOwner,
+    #[error("storage account is not immutable")]
     StorageAccountIsNotImmutable,
+    /// The account is not owned by the program it was read for, the Shadow Drive program.
+    #[error("account {account} is owned by {owner}, not the Shadow Drive program")]
+    AccountOwnerMismatch { account: Pubkey, owner: Pubkey },
+    #[error("invalid byte range {start}..{end}")]
+    InvalidRange { start: u64, end: u64 },
+    #[error("object hash mismatch, expected {} but got {}", hex::encode(.expected), hex::encode(.actual))]
+    ObjectHashMismatch {
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    #[error("invalid encryption key: {0}")]
+    InvalidEncryptionKey(String),
+    #[error("failed to encrypt {0}")]
+    EncryptionFailed(String),
+    /// The object was changed, or encrypted under a different name.
+    #[error("failed to decrypt {0}, it is corrupt or was stored under another name")]
+    DecryptionFailed(String),
+    #[error("{0} is not encrypted")]
+    NotEncrypted(String),
+    #[error("{name} was encrypted with key {}, not {}", hex::encode(.expected), hex::encode(.actual))]
+    WrongEncryptionKey {
+        name: String,
+        expected: [u8; 8],
+        actual: [u8; 8],
+    },
+    #[error("upload manifest is for storage account {manifest}, not {storage_account}")]
+    UploadManifestMismatch {
+        manifest: String,
+        storage_account: String,
+    },
 }
 
 #[derive(Debug)]
@@ -36,44 +118,170 @@ pub struct FileError {
     pub error: String,
 }
 
-impl From<JoinError> for Error {
-    fn from(join_error: JoinError) -> Self {
-        Self::AsyncJoinError(join_error)
+struct FileErrors<'a>(&'a [FileError]);
+
+impl fmt::Display for FileErrors<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, FileError { file, error }) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", file, error)?;
+        }
+        Ok(())
     }
 }
 
-impl From<ClientError> for Error {
-    fn from(client_error: ClientError) -> Self {
-        Self::SolanaRpcError(client_error)
+impl Error {
+    /// Whether the same request may succeed if it is sent again later,
+    /// e.g. after being rate limited or on a server or network failure.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::RateLimited { .. } => true,
+            Error::ShadowDriveServerError { status, .. } => *status >= 500,
+            Error::ReqwestError(e) => is_retryable_reqwest(e),
+            Error::SolanaRpcError(e) => match e.kind() {
+                ClientErrorKind::Io(_) => true,
+                ClientErrorKind::Reqwest(e) => is_retryable_reqwest(e),
+                _ => false,
+            },
+            _ => false,
+        }
     }
-}
 
-impl From<SignerError> for Error {
-    fn from(signer_error: SignerError) -> Self {
-        Self::SignerError(signer_error)
+    /// Parses an error response from Shadow Drive into a known variant,
+    /// falling back to [`Error::ShadowDriveServerError`].
+    pub fn from_server_response(status: u16, retry_after: Option<Duration>, body: Value) -> Self {
+        if status == StatusCode::TOO_MANY_REQUESTS.as_u16() {
+            return Error::RateLimited { retry_after };
+        }
+
+        let message = match &body {
+            Value::String(message) => message.clone(),
+            Value::Object(fields) => match fields.get("error").or_else(|| fields.get("message")) {
+                Some(Value::String(message)) => message.clone(),
+                _ => body.to_string(),
+            },
+            _ => body.to_string(),
+        };
+        let lowercase = message.to_lowercase();
+
+        if lowercase.contains("rate limit") || lowercase.contains("too many requests") {
+            Error::RateLimited { retry_after }
+        } else if lowercase.contains("not enough storage")
+            || lowercase.contains("insufficient storage")
+        {
+            Error::InsufficientStorage { message }
+        } else if lowercase.contains("already exists") {
+            Error::FileAlreadyExists { message }
+        } else if lowercase.contains("does not own")
+            || lowercase.contains("not the owner")
+            || lowercase.contains("not owner")
+        {
+            Error::NotFileOwner
+        } else {
+            Error::ShadowDriveServerError {
+                status,
+                message: body,
+            }
+        }
     }
-}
 
-impl From<AnchorError> for Error {
-    fn from(anchor_error: AnchorError) -> Self {
-        Self::AnchorError(anchor_error)
+    /// Reads an unsuccessful response from Shadow Drive into an error.
+    pub(crate) async fn from_response(response: Response) -> Self {
+        let status = response.status().as_u16();
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs);
+        let body = match response.text().await {
+            Ok(text) => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+            Err(_) => Value::Null,
+        };
+        Self::from_server_response(status, retry_after, body)
     }
 }
 
-impl From<ReqwestError> for Error {
-    fn from(signer_error: ReqwestError) -> Self {
-        Self::ReqwestError(signer_error)
-    }
+fn is_retryable_reqwest(e: &ReqwestError) -> bool {
+    e.is_timeout() || e.is_connect() || e.status().map_or(false, |status| status.is_server_error())
 }
 
-impl From<ParsePubkeyError> for Error {
-    fn from(parse_pubkey_error: ParsePubkeyError) -> Self {
-        Self::ParsePubkeyError(parse_pubkey_error)
+impl From<ClientError> for Error {
+    fn from(client_error: ClientError) -> Self {
+        Self::SolanaRpcError(client_error)
     }
 }
 
-impl From<std::io::Error> for Error {
-    fn from(e: std::io::Error) -> Self {
-        Self::FileSystemError(e)
+#[test]
+fn test_from_server_response() {
+    use serde_json::json;
+
+    let retry_after = Some(Duration::from_secs(5));
+    for (status, body, expected) in [
+        (
+            507,
+            json!({"error": "Not enough storage available on this Storage Account"}),
+            Error::InsufficientStorage {
+                message: "Not enough storage available on this Storage Account".to_string(),
+            },
+        ),
+        (
+            500,
+            json!("Insufficient storage, add storage first"),
+            Error::InsufficientStorage {
+                message: "Insufficient storage, add storage first".to_string(),
+            },
+        ),
+        (
+            400,
+            json!({"message": "File a.txt already exists"}),
+            Error::FileAlreadyExists {
+                message: "File a.txt already exists".to_string(),
+            },
+        ),
+        (
+            429,
+            json!({"error": "slow down"}),
+            Error::RateLimited { retry_after },
+        ),
+        (
+            503,
+            json!({"error": "Too Many Requests"}),
+            Error::RateLimited { retry_after },
+        ),
+        (
+            401,
+            json!({"error": "Signer does not own the storage account"}),
+            Error::NotFileOwner,
+        ),
+        (
+            500,
+            json!({"error": {"code": 7}}),
+            Error::ShadowDriveServerError {
+                status: 500,
+                message: json!({"error": {"code": 7}}),
+            },
+        ),
+        (
+            502,
+            json!("Bad Gateway"),
+            Error::ShadowDriveServerError {
+                status: 502,
+                message: json!("Bad Gateway"),
+            },
+        ),
+        (
+            500,
+            Value::Null,
+            Error::ShadowDriveServerError {
+                status: 500,
+                message: Value::Null,
+            },
+        ),
+    ] {
+        let error = Error::from_server_response(status, retry_after, body);
+        assert_eq!(format!("{:?}", error), format!("{:?}", expected));
     }
 }
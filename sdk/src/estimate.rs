@@ -0,0 +1,356 @@
+//! Itemized cost quotes for storage operations and portal summons, computed from on-chain
+//! state without sending any transaction.
+
+use std::fmt;
+
+use byte_unit::Byte;
+use chain_drive::instructions::summon::DataToBeSummoned;
+use serde::Serialize;
+use shadow_drive_user_staking::instructions::initialize_account::UserInfo;
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL, program_pack::Pack, pubkey::Pubkey, signer::Signer,
+};
+
+use crate::{
+    error::Error,
+    models::{ShadowDriveResult, ShadowFile},
+    portal::{PortalClient, SummonOptions},
+    ShadowDriveClient,
+};
+
+/// Lamports charged per transaction signature.
+const SIGNATURE_FEE: u64 = 5_000;
+
+/// Smallest units of SHDW per SHDW.
+const SHADES_PER_SHDW: u64 = 1_000_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Currency {
+    /// 10^-9 SOL
+    Lamports,
+    /// 10^-9 SHDW
+    Shades,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct QuoteItem {
+    pub description: String,
+    pub amount: u64,
+    pub currency: Currency,
+    /// Returned to the payer when the account it pays for is closed.
+    pub refundable: bool,
+    /// Paid by someone else, e.g. the uploader of a summon, so left out of the totals.
+    pub external: bool,
+}
+
+/// The itemized cost of an operation. Amounts are upper bounds where the exact cost depends
+/// on state that may change before the operation is sent.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Quote {
+    pub items: Vec<QuoteItem>,
+}
+
+impl Quote {
+    fn push<D: Into<String>>(&mut self, description: D, amount: u64, currency: Currency) {
+        self.items.push(QuoteItem {
+            description: description.into(),
+            amount,
+            currency,
+            refundable: false,
+            external: false,
+        });
+    }
+
+    fn push_refundable<D: Into<String>>(&mut self, description: D, amount: u64) {
+        self.items.push(QuoteItem {
+            description: description.into(),
+            amount,
+            currency: Currency::Lamports,
+            refundable: true,
+            external: false,
+        });
+    }
+
+    fn push_external<D: Into<String>>(&mut self, description: D, amount: u64) {
+        self.items.push(QuoteItem {
+            description: description.into(),
+            amount,
+            currency: Currency::Lamports,
+            refundable: false,
+            external: true,
+        });
+    }
+
+    /// Sum of all items in `currency` paid by the caller, including refundable ones.
+    pub fn total(&self, currency: Currency) -> u64 {
+        self.items
+            .iter()
+            .filter(|item| item.currency == currency && !item.external)
+            .map(|item| item.amount)
+            .sum()
+    }
+
+    pub fn lamports(&self) -> u64 {
+        self.total(Currency::Lamports)
+    }
+
+    pub fn shades(&self) -> u64 {
+        self.total(Currency::Shades)
+    }
+}
+
+impl fmt::Display for Quote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            let note = if item.external {
+                " (not included)"
+            } else if item.refundable {
+                " (refundable)"
+            } else {
+                ""
+            };
+            writeln!(
+                f,
+                "  {:<48} {:>20}{}",
+                item.description,
+                format_amount(item.amount, item.currency),
+                note
+            )?;
+        }
+        writeln!(
+            f,
+            "  {:<48} {:>20}",
+            "total",
+            format_amount(self.lamports(), Currency::Lamports)
+        )?;
+        write!(
+            f,
+            "  {:<48} {:>20}",
+            "",
+            format_amount(self.shades(), Currency::Shades)
+        )
+    }
+}
+
+fn format_amount(amount: u64, currency: Currency) -> String {
+    let (unit, per) = match currency {
+        Currency::Lamports => ("SOL", LAMPORTS_PER_SOL),
+        Currency::Shades => ("SHDW", SHADES_PER_SHDW),
+    };
+    format!("{}.{:09} {}", amount / per, amount % per, unit)
+}
+
+/// SHDW staked for `bytes` of storage at `shades_per_gib`.
+fn storage_stake(bytes: u64, shades_per_gib: u64) -> u64 {
+    (bytes as u128 * shades_per_gib as u128 / (1u128 << 30)) as u64
+}
+
+fn byte_count(size: Byte) -> ShadowDriveResult<u64> {
+    size.get_bytes()
+        .try_into()
+        .map_err(|_| Error::InvalidStorage(size.get_bytes()))
+}
+
+impl<T> ShadowDriveClient<T>
+where
+    T: Signer,
+{
+    /// Quotes [`create_storage_account`](Self::create_storage_account) for `size` bytes.
+    /// Accounts smaller than the minimum account size are charged as the minimum size.
+    pub async fn estimate_create_storage_account(&self, size: Byte) -> ShadowDriveResult<Quote> {
+        let (shades_per_gib, min_size) = self.get_storage_price_and_min_account_size().await?;
+        let bytes = byte_count(size)?.max(min_size);
+
+        let mut quote = Quote::default();
+        quote.push(
+            format!(
+                "storage stake for {}",
+                Byte::from_bytes(bytes.into()).get_appropriate_unit(true)
+            ),
+            storage_stake(bytes, shades_per_gib),
+            Currency::Shades,
+        );
+
+        // Only a missing account means the user info is yet to be created, other errors are
+        // returned
+        let (user_info, _) = self.derived_addresses().user_info(&self.wallet().pubkey());
+        let user_info = self
+            .rpc()
+            .get_account_with_commitment(&user_info, self.rpc().commitment())
+            .await?;
+        if user_info.value.is_none() {
+            let rent = self
+                .rpc()
+                .get_minimum_balance_for_rent_exemption(8 + std::mem::size_of::<UserInfo>())
+                .await?;
+            quote.push("user info account rent", rent, Currency::Lamports);
+        }
+        let rent = self
+            .rpc()
+            .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+            .await?;
+        quote.push("stake account rent", rent, Currency::Lamports);
+        quote.push("transaction fee", SIGNATURE_FEE, Currency::Lamports);
+
+        Ok(quote)
+    }
+
+    /// Quotes [`add_storage`](Self::add_storage) of `size` bytes.
+    pub async fn estimate_add_storage(&self, size: Byte) -> ShadowDriveResult<Quote> {
+        let (shades_per_gib, _) = self.get_storage_price_and_min_account_size().await?;
+        let bytes = byte_count(size)?;
+
+        let mut quote = Quote::default();
+        quote.push(
+            format!("storage stake for {}", size.get_appropriate_unit(true)),
+            storage_stake(bytes, shades_per_gib),
+            Currency::Shades,
+        );
+        quote.push("transaction fee", SIGNATURE_FEE, Currency::Lamports);
+
+        Ok(quote)
+    }
+
+    /// Quotes uploading `files` to a storage account. Uploads are free while they fit in the
+    /// account; otherwise the quote includes the stake for the storage that must be added first.
+    pub async fn estimate_store_files(
+        &self,
+        storage_account_key: &Pubkey,
+        files: &[ShadowFile],
+    ) -> ShadowDriveResult<Quote> {
+        let mut bytes = 0;
+        for file in files {
+            bytes += file.len().await?;
+        }
+
+        let account = self.get_storage_account(storage_account_key).await?;
+        let available = account.storage().saturating_sub(account.current_usage());
+
+        let mut quote = Quote::default();
+        if bytes > available {
+            let (shades_per_gib, _) = self.get_storage_price_and_min_account_size().await?;
+            let missing = bytes - available;
+            quote.push(
+                format!(
+                    "storage stake for the missing {}",
+                    Byte::from_bytes(missing.into()).get_appropriate_unit(true)
+                ),
+                storage_stake(missing, shades_per_gib),
+                Currency::Shades,
+            );
+            quote.push(
+                "add storage transaction fee",
+                SIGNATURE_FEE,
+                Currency::Lamports,
+            );
+        }
+
+        Ok(quote)
+    }
+}
+
+impl<T> PortalClient<T>
+where
+    T: Signer,
+{
+    /// Quotes [`summon`](Self::summon). Without [`SummonOptions::expect`] the object is
+    /// downloaded to find its length.
+    ///
+    /// The clockwork thread fee is paid by the uploader, who is reimbursed from the summon's
+    /// extra lamports. It is listed, but left out of the totals, so that
+    /// [`SummonOptions::extra_lamports`] can be chosen to cover it.
+    pub async fn estimate_summon(
+        &self,
+        storage_account_key: &Pubkey,
+        filename: &str,
+        options: &SummonOptions,
+    ) -> ShadowDriveResult<Quote> {
+        let data_len = match options.expected {
+            Some((_, data_len)) => data_len,
+            None => {
+                self.download_len_and_hash(storage_account_key, filename)
+                    .await?
+                    .1
+            }
+        };
+
+        let config = self.get_config().await?;
+
+        let mut quote = Quote::default();
+        quote.push(
+            format!("portal fee for {} bytes", data_len),
+            data_len as u64 * config.shades_per_byte,
+            Currency::Shades,
+        );
+        let space = DataToBeSummoned::space(data_len, filename, &options.callback);
+        quote.push_refundable(
+            "summon account rent",
+            self.rpc()
+                .get_minimum_balance_for_rent_exemption(space)
+                .await?,
+        );
+        quote.push_refundable(
+            "SHDW vault rent",
+            self.rpc()
+                .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+                .await?,
+        );
+        if options.extra_lamports > 0 {
+            quote.push(
+                "extra lamports for the uploader",
+                options.extra_lamports,
+                Currency::Lamports,
+            );
+        }
+        quote.push_external(
+            "clockwork thread fee, paid by the uploader",
+            chain_drive::DELETE_TX_FEE,
+        );
+        quote.push("transaction fee", SIGNATURE_FEE, Currency::Lamports);
+
+        Ok(quote)
+    }
+}
+
+#[test]
+fn test_quote_totals() {
+    let mut quote = Quote::default();
+    quote.push("portal fee", 1_500, Currency::Shades);
+    quote.push_refundable("account rent", 2_000);
+    quote.push("transaction fee", SIGNATURE_FEE, Currency::Lamports);
+    quote.push_external("thread fee", 1_000_000);
+
+    assert_eq!(quote.lamports(), 2_000 + SIGNATURE_FEE);
+    assert_eq!(quote.shades(), 1_500);
+    assert_eq!(Quote::default().lamports(), 0);
+
+    let display = quote.to_string();
+    assert!(display.contains("(refundable)"), "{display}");
+    assert!(display.contains("(not included)"), "{display}");
+    assert!(display.contains("0.000007000 SOL"), "{display}");
+}
+
+#[test]
+fn test_storage_stake() {
+    assert_eq!(storage_stake(1 << 30, 250_000_000), 250_000_000);
+    assert_eq!(storage_stake(1 << 29, 250_000_000), 125_000_000);
+    assert_eq!(storage_stake(0, 250_000_000), 0);
+    // Rounds down, and does not overflow for large accounts
+    assert_eq!(storage_stake(1, 250_000_000), 0);
+    assert_eq!(storage_stake(1 << 50, 250_000_000), 250_000_000 << 20);
+}
+
+#[test]
+fn test_format_amount() {
+    assert_eq!(format_amount(0, Currency::Lamports), "0.000000000 SOL");
+    assert_eq!(format_amount(5_000, Currency::Lamports), "0.000005000 SOL");
+    assert_eq!(
+        format_amount(1_500_000_000, Currency::Shades),
+        "1.500000000 SHDW"
+    );
+    assert_eq!(
+        format_amount(42 * SHADES_PER_SHDW, Currency::Shades),
+        "42.000000000 SHDW"
+    );
+}
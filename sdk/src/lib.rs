@@ -14,10 +14,18 @@
 mod client;
 pub use client::*;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+pub mod auth;
+pub mod cluster;
 pub mod constants;
 pub mod derived_addresses;
+pub mod encryption;
 pub mod error;
+pub mod estimate;
 pub mod models;
+pub mod portal;
 
 pub use {
     // allows users to specify number of bytes
@@ -2,7 +2,7 @@ use std::path::Path;
 
 use bytes::Bytes;
 use reqwest::multipart::Part;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 //re-export structs from Shadow Drive Smart Contract that are used in the SDK
 pub use shadow_drive_user_staking::instructions::{
@@ -15,31 +15,31 @@ pub mod storage_acct;
 
 use payload::Payload;
 
-use crate::{constants::FILE_SIZE_LIMIT, error::Error};
+use crate::{constants::FILE_SIZE_LIMIT, encryption::EncryptionKey, error::Error};
 
 pub type ShadowDriveResult<T> = Result<T, Error>;
 
 const BUFFER_SIZE: usize = 4096;
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ShdwDriveResponse {
     pub txid: String,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StorageResponse {
     pub message: String,
     pub transaction_signature: String,
     pub error: Option<String>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CreateStorageAccountResponse {
     pub shdw_bucket: Option<String>,
     pub transaction_signature: String,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct DeleteFileResponse {
     pub message: String,
     pub error: Option<String>,
@@ -79,6 +79,18 @@ impl ShadowFile {
         }
     }
 
+    /// Encrypts the file's contents with `key`, see [`encryption`](crate::encryption).
+    /// The contents are read into memory, and the content type becomes
+    /// `application/octet-stream`. Hashes and runes of the returned file are of the ciphertext.
+    pub async fn encrypted(self, key: &EncryptionKey) -> ShadowDriveResult<Self> {
+        let data = match &self.data {
+            Payload::File(path) => Bytes::from(tokio::fs::read(path).await?),
+            Payload::Bytes(data) => data.clone(),
+        };
+        let data = key.encrypt(&self.name, &data)?;
+        Ok(Self::bytes(self.name, data).with_content_type("application/octet-stream".to_string()))
+    }
+
     pub(crate) async fn sha256(&self) -> ShadowDriveResult<String> {
         let result = match &self.data {
             Payload::File(path) => {
@@ -107,6 +119,14 @@ impl ShadowFile {
         Ok(hex::encode(result))
     }
 
+    /// Size of the file's contents in bytes.
+    pub(crate) async fn len(&self) -> ShadowDriveResult<u64> {
+        match &self.data {
+            Payload::File(path) => Ok(tokio::fs::metadata(path).await?.len()),
+            Payload::Bytes(data) => Ok(data.len() as u64),
+        }
+    }
+
     pub(crate) async fn into_form_part(self) -> ShadowDriveResult<Part> {
         let mut part = match self.data {
             Payload::File(path) => {
@@ -138,7 +158,7 @@ impl ShadowFile {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ShadowUploadResponse {
     #[serde(default)]
     pub finalized_locations: Vec<String>,
@@ -147,7 +167,7 @@ pub struct ShadowUploadResponse {
     pub upload_errors: Vec<UploadError>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ShadowEditResponse {
     #[serde(default)]
     pub finalized_location: String,
@@ -155,7 +175,7 @@ pub struct ShadowEditResponse {
     pub error: String,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct UploadError {
     pub file: String,
     pub storage_account: String,
@@ -169,13 +189,13 @@ pub(crate) struct ShdwDriveBatchServerResponse {
     pub transaction_signature: String,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum BatchUploadStatus {
     Uploaded,
     AlreadyExists,
     Error(String),
 }
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ShadowBatchUploadResponse {
     pub file_name: String,
     pub status: BatchUploadStatus,
@@ -183,19 +203,53 @@ pub struct ShadowBatchUploadResponse {
     pub transaction_signature: Option<String>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct FileDataResponse {
     pub file_data: FileData,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct FileData {
     pub owner_account_pubkey: String,
     pub storage_account_pubkey: String,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ListObjectsResponse {
     pub keys: Vec<String>,
 }
+
+/// An object in a storage account. Fields other than `key` are `None` when the server does
+/// not provide them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ObjectInfo {
+    pub key: String,
+    /// Size in bytes
+    pub size: Option<u64>,
+    pub content_type: Option<String>,
+    /// Seconds since the unix epoch
+    pub last_modified: Option<u64>,
+    pub sha256: Option<[u8; 32]>,
+}
+
+impl ObjectInfo {
+    pub(crate) fn from_key(key: String) -> Self {
+        Self {
+            key,
+            size: None,
+            content_type: None,
+            last_modified: None,
+            sha256: None,
+        }
+    }
+}
+
+/// A page of objects, ordered by key.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ObjectListing {
+    pub objects: Vec<ObjectInfo>,
+    /// Pass to [`ListObjectsOptions::cursor`](crate::ListObjectsOptions::cursor) to fetch the
+    /// next page, `None` on the last page.
+    pub next_cursor: Option<String>,
+}
@@ -1,10 +1,17 @@
 use std::str::FromStr;
 
-use anchor_lang::prelude::Pubkey;
-use serde::{Deserialize, Deserializer};
-#[derive(Clone, Debug, Deserialize)]
+use anchor_lang::{prelude::Pubkey, AccountDeserialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use shadow_drive_user_staking::instructions::initialize_account;
+
+use super::ShadowDriveResult;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StorageAccount {
-    #[serde(deserialize_with = "deserialize_pubkey")]
+    #[serde(
+        deserialize_with = "deserialize_pubkey",
+        serialize_with = "serialize_pubkey"
+    )]
     pub storage_account: Pubkey,
 
     /// Number of bytes of storage associated with this account
@@ -23,11 +30,19 @@ pub struct StorageAccount {
     pub delete_request_epoch: u32,
 
     /// Primary owner of StorageAccount (immutable)
-    #[serde(alias = "owner1", deserialize_with = "deserialize_pubkey")]
+    #[serde(
+        alias = "owner1",
+        deserialize_with = "deserialize_pubkey",
+        serialize_with = "serialize_pubkey"
+    )]
     pub owner_1: Pubkey,
 
     /// Optional owner 2
-    #[serde(alias = "owner2", deserialize_with = "deserialize_pubkey")]
+    #[serde(
+        alias = "owner2",
+        deserialize_with = "deserialize_pubkey",
+        serialize_with = "serialize_pubkey"
+    )]
     pub owner_2: Pubkey,
 
     /// Counter at time of initialization
@@ -48,9 +63,12 @@ pub struct StorageAccount {
 }
 
 // Copied from shadow-drive-user-staking crate to add JSON deserialization
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StorageAccountV2 {
-    #[serde(deserialize_with = "deserialize_pubkey")]
+    #[serde(
+        deserialize_with = "deserialize_pubkey",
+        serialize_with = "serialize_pubkey"
+    )]
     pub storage_account: Pubkey,
 
     /// Number of bytes of storage associated with this account
@@ -69,7 +87,11 @@ pub struct StorageAccountV2 {
     pub delete_request_epoch: u32,
 
     /// Primary owner of StorageAccount (immutable)
-    #[serde(alias = "owner1", deserialize_with = "deserialize_pubkey")]
+    #[serde(
+        alias = "owner1",
+        deserialize_with = "deserialize_pubkey",
+        serialize_with = "serialize_pubkey"
+    )]
     pub owner_1: Pubkey,
 
     /// Counter at time of initialization
@@ -89,7 +111,7 @@ pub struct StorageAccountV2 {
     pub identifier: String,
 }
 
-fn deserialize_pubkey<'de, D>(deserializer: D) -> Result<Pubkey, D::Error>
+pub(crate) fn deserialize_pubkey<'de, D>(deserializer: D) -> Result<Pubkey, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -97,7 +119,15 @@ where
     Pubkey::from_str(&s).map_err(serde::de::Error::custom)
 }
 
-#[derive(Debug, Deserialize)]
+/// Serializes a [`Pubkey`] as base58, the same way it is deserialized.
+pub(crate) fn serialize_pubkey<S>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_str(pubkey)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(tag = "version")]
 pub enum StorageAcct {
     V1(StorageAccount),
@@ -117,4 +147,130 @@ impl StorageAcct {
             StorageAcct::V2(acct) => acct.reserved_bytes,
         }
     }
+    /// Bytes in use. Always 0 for V2 accounts read from chain data, see
+    /// [`from_account_data`](Self::from_account_data).
+    pub fn current_usage(&self) -> u64 {
+        match self {
+            StorageAcct::V1(acct) => acct.current_usage,
+            StorageAcct::V2(acct) => acct.current_usage,
+        }
+    }
+    pub fn identifier(&self) -> &str {
+        match self {
+            StorageAcct::V1(acct) => &acct.identifier,
+            StorageAcct::V2(acct) => &acct.identifier,
+        }
+    }
+    pub fn to_be_deleted(&self) -> bool {
+        match self {
+            StorageAcct::V1(acct) => acct.to_be_deleted,
+            StorageAcct::V2(acct) => acct.to_be_deleted,
+        }
+    }
+}
+
+impl StorageAcct {
+    /// Deserializes a storage account of either version from its on-chain data.
+    ///
+    /// `current_usage` is only recorded on chain for V1 accounts, for V2 accounts
+    /// it is reported as 0.
+    pub fn from_account_data(key: Pubkey, data: &[u8]) -> ShadowDriveResult<Self> {
+        // the account discriminator tells the two versions apart
+        if let Ok(account) = initialize_account::StorageAccountV2::try_deserialize(&mut &data[..]) {
+            return Ok(StorageAcct::V2(StorageAccountV2 {
+                storage_account: key,
+                reserved_bytes: account.storage,
+                current_usage: 0,
+                immutable: account.immutable,
+                to_be_deleted: account.to_be_deleted,
+                delete_request_epoch: account.delete_request_epoch,
+                owner_1: account.owner_1,
+                account_counter_seed: account.account_counter_seed,
+                creation_time: account.creation_time,
+                creation_epoch: account.creation_epoch,
+                last_fee_epoch: account.last_fee_epoch,
+                identifier: account.identifier,
+            }));
+        }
+
+        let account = initialize_account::StorageAccount::try_deserialize(&mut &data[..])?;
+        Ok(StorageAcct::V1(StorageAccount {
+            storage_account: key,
+            reserved_bytes: account.storage,
+            current_usage: account.storage.saturating_sub(account.storage_available),
+            immutable: account.immutable,
+            to_be_deleted: account.to_be_deleted,
+            delete_request_epoch: account.delete_request_epoch,
+            owner_1: account.owner_1,
+            owner_2: account.owner_2,
+            account_counter_seed: account.account_counter_seed,
+            creation_time: account.creation_time,
+            creation_epoch: account.creation_epoch,
+            last_fee_epoch: account.last_fee_epoch,
+            identifier: account.identifier,
+        }))
+    }
+}
+
+/// On-chain data of an account of type `A` whose fields are zeroed, and then set by `init`.
+#[cfg(test)]
+pub(crate) fn test_account_data<A>(init: impl FnOnce(&mut A)) -> Vec<u8>
+where
+    A: AccountDeserialize + anchor_lang::AccountSerialize + anchor_lang::Discriminator,
+{
+    let mut zeroed = A::discriminator().to_vec();
+    zeroed.resize(1024, 0);
+    let mut account = A::try_deserialize(&mut zeroed.as_slice()).unwrap();
+    init(&mut account);
+    let mut data = vec![];
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn test_from_account_data() {
+    let key = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+
+    let data = test_account_data::<initialize_account::StorageAccount>(|account| {
+        account.storage = 1000;
+        account.storage_available = 400;
+        account.owner_1 = owner;
+        account.identifier = "v1".to_string();
+    });
+    match StorageAcct::from_account_data(key, &data).unwrap() {
+        StorageAcct::V1(account) => {
+            assert_eq!(account.storage_account, key);
+            assert_eq!(account.owner_1, owner);
+            assert_eq!(account.reserved_bytes, 1000);
+            assert_eq!(account.current_usage, 600);
+            assert_eq!(account.identifier, "v1");
+        }
+        account => panic!("expected a V1 account, got {:?}", account),
+    }
+
+    // V2 accounts do not record their usage
+    let data = test_account_data::<initialize_account::StorageAccountV2>(|account| {
+        account.storage = 1000;
+        account.owner_1 = owner;
+        account.immutable = true;
+        account.identifier = "v2".to_string();
+    });
+    match StorageAcct::from_account_data(key, &data).unwrap() {
+        StorageAcct::V2(account) => {
+            assert_eq!(account.storage_account, key);
+            assert_eq!(account.owner_1, owner);
+            assert_eq!(account.reserved_bytes, 1000);
+            assert_eq!(account.current_usage, 0);
+            assert!(account.immutable);
+            assert_eq!(account.identifier, "v2");
+        }
+        account => panic!("expected a V2 account, got {:?}", account),
+    }
+
+    // Other accounts of the program, and truncated data, are rejected
+    let user_info = test_account_data::<super::UserInfo>(|_| {});
+    assert!(StorageAcct::from_account_data(key, &user_info).is_err());
+    assert!(StorageAcct::from_account_data(key, &data[..data.len() - 1]).is_err());
+    assert!(StorageAcct::from_account_data(key, &[]).is_err());
 }
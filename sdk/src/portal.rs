@@ -0,0 +1,690 @@
+//! Client for the chain-drive portal program, which copies ("summons") Shadow Drive objects into
+//! on-chain accounts.
+//!
+//! A summon pays SHDW into a vault and creates a [`DataToBeSummoned`] metadata account. An
+//! uploader then writes the object's data into that account, after which a clockwork thread
+//! closes it again, so callers interested in the data should [`wait_for_upload`](PortalClient::wait_for_upload).
+
+use std::{str::FromStr, time::Duration};
+
+use anchor_lang::{
+    system_program, AccountDeserialize, Discriminator, Id, InstructionData, ToAccountMetas,
+};
+use bytes::Bytes;
+use chain_drive::{
+    clockwork_sdk::{state::Thread, ThreadProgram},
+    payout_authority, portal_config, shdw, ClockworkInstructionData, ADMIN,
+};
+use futures::{stream::BoxStream, StreamExt};
+use reqwest::Url;
+use runes::Rune;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_client::{
+    client_error::ClientError,
+    nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    pubsub_client::PubsubClientError,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+    rpc_response::Response,
+};
+use solana_sdk::{
+    account::Account,
+    commitment_config::CommitmentConfig,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    signer::Signer,
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::ID as TokenProgramID;
+
+use crate::{
+    cluster::Cluster,
+    constants::SHDW_DRIVE_OBJECT_PREFIX,
+    error::{Error, PortalError},
+    models::{storage_acct::serialize_pubkey, ShadowDriveResult, ShdwDriveResponse},
+};
+
+pub use chain_drive::{instructions::summon::DataToBeSummoned, PortalConfig};
+
+/// Options for [`summon`](PortalClient::summon).
+#[derive(Clone, Debug, Default)]
+pub struct SummonOptions {
+    pub(crate) callback: Option<ClockworkInstructionData>,
+    pub(crate) unique_thread: Option<u64>,
+    pub(crate) extra_lamports: u64,
+    pub(crate) expected: Option<([u8; 32], usize)>,
+}
+
+impl SummonOptions {
+    /// Instruction for the clockwork thread to run once the data is uploaded.
+    pub fn callback(mut self, callback: ClockworkInstructionData) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    /// Seeds the metadata account with `id` instead of the file name, so the same file can be
+    /// summoned more than once at a time.
+    pub fn unique_thread(mut self, id: u64) -> Self {
+        self.unique_thread = Some(id);
+        self
+    }
+
+    /// Lamports paid to the uploader on top of the SHDW fee.
+    pub fn extra_lamports(mut self, extra_lamports: u64) -> Self {
+        self.extra_lamports = extra_lamports;
+        self
+    }
+
+    /// The object's SHA-256 hash and length, e.g. from a rune. Without them the object is
+    /// downloaded once to compute them.
+    pub fn expect(mut self, hash: [u8; 32], data_len: usize) -> Self {
+        self.expected = Some((hash, data_len));
+        self
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SummonResponse {
+    pub txid: String,
+    /// The [`DataToBeSummoned`] account the data will be uploaded to.
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub metadata: Pubkey,
+}
+
+/// Summons Shadow Drive objects through the portal program and waits for their data.
+pub struct PortalClient<T>
+where
+    T: Signer,
+{
+    wallet: T,
+    rpc_client: RpcClient,
+    http_client: reqwest::Client,
+    websocket_url: String,
+    object_prefix: String,
+    program_id: Pubkey,
+    shdw_mint: Pubkey,
+    config: Pubkey,
+    payout_authority: Pubkey,
+    admin: Pubkey,
+}
+
+impl<T> PortalClient<T>
+where
+    T: Signer,
+{
+    /// Creates a new [`PortalClient`] from the given [`Signer`] and RPC URL.
+    /// The websocket URL is derived from the RPC URL the same way the solana CLI does.
+    /// # Example
+    ///
+    /// ```
+    /// # use shadow_drive_rust::portal::PortalClient;
+    /// # use solana_sdk::signer::keypair::read_keypair_file;
+    /// #
+    /// let keypair = read_keypair_file(KEYPAIR_PATH).expect("failed to load keypair at path");
+    /// let portal = PortalClient::new(keypair, "https://ssc-dao.genesysgo.net");
+    /// ```
+    pub fn new<U: ToString>(wallet: T, rpc_url: U) -> Self {
+        let rpc_url = rpc_url.to_string();
+        let websocket_url = websocket_url(&rpc_url);
+        let rpc_client = RpcClient::new_with_timeout_and_commitment(
+            rpc_url,
+            Duration::from_secs(120),
+            CommitmentConfig::confirmed(),
+        );
+        Self::new_with_rpc(wallet, rpc_client, websocket_url)
+    }
+
+    /// Creates a new [`PortalClient`] from the given [`Signer`], [`RpcClient`] and websocket URL.
+    pub fn new_with_rpc<U: ToString>(wallet: T, rpc_client: RpcClient, websocket_url: U) -> Self {
+        Self {
+            wallet,
+            rpc_client,
+            http_client: reqwest::Client::new(),
+            websocket_url: websocket_url.to_string(),
+            object_prefix: SHDW_DRIVE_OBJECT_PREFIX.to_string(),
+            program_id: chain_drive::ID,
+            shdw_mint: shdw::ID,
+            config: portal_config(),
+            payout_authority: payout_authority::ID,
+            admin: Pubkey::from_str(ADMIN).expect("ADMIN is a valid pubkey"),
+        }
+    }
+
+    /// Downloads objects from `object_prefix` instead of Mainnet Shadow Drive when computing
+    /// their hash, e.g. when summoning from a local mock.
+    pub fn with_object_prefix<U: ToString>(mut self, object_prefix: U) -> Self {
+        self.object_prefix = object_prefix.to_string().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Uses the portal program, SHDW mint, config account, payout authority, admin and object
+    /// prefix of `cluster` instead of the ones the chain-drive crate was built with.
+    pub fn with_cluster(self, cluster: &Cluster) -> Self {
+        let mut portal = self.with_object_prefix(&cluster.object_prefix);
+        portal.program_id = cluster.portal_program_id;
+        portal.shdw_mint = cluster.portal_shdw_mint;
+        portal.config = cluster.portal_config();
+        portal.payout_authority = cluster.payout_authority;
+        portal.admin = cluster.admin;
+        portal
+    }
+
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    /// The portal's config account.
+    pub fn config_address(&self) -> &Pubkey {
+        &self.config
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc_client
+    }
+
+    pub(crate) fn wallet(&self) -> &T {
+        &self.wallet
+    }
+
+    pub(crate) async fn download_len_and_hash(
+        &self,
+        storage_account_key: &Pubkey,
+        filename: &str,
+    ) -> ShadowDriveResult<([u8; 32], usize)> {
+        let data = self.download(storage_account_key, filename).await?;
+        Ok((Sha256::digest(&data).into(), data.len()))
+    }
+
+    /// The [`DataToBeSummoned`] account a summon by this client's wallet would use.
+    pub fn metadata_address(
+        &self,
+        storage_account_key: &Pubkey,
+        filename: &str,
+        unique_thread: Option<u64>,
+    ) -> Pubkey {
+        // The same seeds as `DataToBeSummoned::get_pda`, which only knows the built-in program
+        let thread_seed = unique_thread.map(u64::to_le_bytes);
+        let seed = thread_seed
+            .as_ref()
+            .map_or(filename.as_bytes(), |seed| &seed[..]);
+        Pubkey::find_program_address(
+            &[
+                self.wallet.pubkey().as_ref(),
+                storage_account_key.as_ref(),
+                seed,
+            ],
+            &self.program_id,
+        )
+        .0
+    }
+
+    /// The token account holding the SHDW paid for a summon.
+    pub fn vault_address(&self, metadata: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[metadata.as_ref()], &self.program_id).0
+    }
+
+    /// Summons a file on-chain, paying the portal fee in SHDW from the wallet's associated token
+    /// account.
+    /// * `storage_account_key` - The public key of the [`StorageAccount`](crate::models::StorageAccount) that contains the file.
+    /// * `filename` - The name of the file.
+    /// * `options` - Callback, thread id, uploader tip and the expected hash and length.
+    /// # Example
+    ///
+    /// ```
+    /// # use shadow_drive_rust::portal::{PortalClient, SummonOptions};
+    /// # use solana_sdk::signer::keypair::read_keypair_file;
+    /// # use std::time::Duration;
+    /// #
+    /// # let keypair = read_keypair_file(KEYPAIR_PATH).expect("failed to load keypair at path");
+    /// # let portal = PortalClient::new(keypair, "https://ssc-dao.genesysgo.net");
+    /// #
+    /// let summon = portal
+    ///     .summon(&storage_account_key, "example.png", SummonOptions::default())
+    ///     .await?;
+    /// let uploaded = portal
+    ///     .wait_for_upload(&summon.metadata, Duration::from_secs(60))
+    ///     .await?;
+    /// ```
+    pub async fn summon(
+        &self,
+        storage_account_key: &Pubkey,
+        filename: &str,
+        options: SummonOptions,
+    ) -> ShadowDriveResult<SummonResponse> {
+        let (hash, data_len) = match options.expected {
+            Some(expected) => expected,
+            None => {
+                self.download_len_and_hash(storage_account_key, filename)
+                    .await?
+            }
+        };
+
+        let summoner = self.wallet.pubkey();
+        let metadata = self.metadata_address(storage_account_key, filename, options.unique_thread);
+        let accounts = chain_drive::accounts::Summon {
+            summoner,
+            summoner_token_account: get_associated_token_address(&summoner, &self.shdw_mint),
+            payer: summoner,
+            metadata,
+            shdw_vault: self.vault_address(&metadata),
+            shdw_mint: self.shdw_mint,
+            portal_config: self.config,
+            token_program: TokenProgramID,
+            system_program: system_program::ID,
+        };
+        let args = chain_drive::instruction::Summon {
+            storage_account: *storage_account_key,
+            filename: filename.to_string(),
+            data_len,
+            hash,
+            callback: options.callback,
+            unique_thread: options.unique_thread,
+            extra_lamports: options.extra_lamports,
+        };
+
+        let txid = self
+            .send(accounts.to_account_metas(None), args.data())
+            .await?;
+
+        Ok(SummonResponse { txid, metadata })
+    }
+
+    /// Summons the file described by `rune`, using its hash and length instead of downloading
+    /// the file.
+    pub async fn summon_rune(
+        &self,
+        storage_account_key: &Pubkey,
+        rune: &Rune,
+        options: SummonOptions,
+    ) -> ShadowDriveResult<SummonResponse> {
+        self.summon(
+            storage_account_key,
+            &rune.name,
+            options.expect(rune.hash, rune.len as usize),
+        )
+        .await
+    }
+
+    /// Fetches the current state of a summon, or `None` if its account does not exist.
+    pub async fn get_summon(
+        &self,
+        metadata: &Pubkey,
+    ) -> ShadowDriveResult<Option<DataToBeSummoned>> {
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(metadata, self.rpc_client.commitment())
+            .await?
+            .value;
+
+        account
+            .map(|account| DataToBeSummoned::try_deserialize(&mut account.data.as_slice()))
+            .transpose()
+            .map_err(Error::from)
+    }
+
+    /// Waits until the summoned data has been uploaded and returns the account holding it.
+    ///
+    /// Fails with [`Error::SummonClosed`] if the account is closed, or never existed, before the
+    /// upload is seen, and with [`Error::UploadTimeout`] after `timeout`.
+    pub async fn wait_for_upload(
+        &self,
+        metadata: &Pubkey,
+        timeout: Duration,
+    ) -> ShadowDriveResult<DataToBeSummoned> {
+        let pubsub = PubsubClient::new(&self.websocket_url).await?;
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(self.rpc_client.commitment()),
+            ..Default::default()
+        };
+        let (mut updates, unsubscribe) = pubsub.account_subscribe(metadata, Some(config)).await?;
+
+        let result = tokio::time::timeout(timeout, self.watch_upload(metadata, &mut updates))
+            .await
+            .unwrap_or(Err(Error::UploadTimeout(*metadata)));
+        unsubscribe().await;
+
+        result
+    }
+
+    async fn watch_upload(
+        &self,
+        metadata: &Pubkey,
+        updates: &mut BoxStream<'_, Response<UiAccount>>,
+    ) -> ShadowDriveResult<DataToBeSummoned> {
+        // The subscription is opened before reading the account so an upload in between is
+        // not missed
+        match self.get_summon(metadata).await? {
+            Some(summon) if summon.uploaded => return Ok(summon),
+            Some(_) => {}
+            None => return Err(Error::SummonClosed(*metadata)),
+        }
+
+        while let Some(update) = updates.next().await {
+            match update.value.decode::<Account>() {
+                Some(account) if !account.data.is_empty() => {
+                    let summon = DataToBeSummoned::try_deserialize(&mut account.data.as_slice())?;
+                    if summon.uploaded {
+                        return Ok(summon);
+                    }
+                }
+                _ => return Err(Error::SummonClosed(*metadata)),
+            }
+        }
+
+        Err(PubsubClientError::ConnectionClosed(self.websocket_url.clone()).into())
+    }
+    /// Withdraws a summon that has not been uploaded yet, refunding its SHDW to the wallet and
+    /// its rent to the summon's payer. Fails with
+    /// [`PortalError::AlreadyUploaded`](crate::error::PortalError) otherwise.
+    pub async fn cancel(&self, metadata: &Pubkey) -> ShadowDriveResult<ShdwDriveResponse> {
+        let summon = self
+            .get_summon(metadata)
+            .await?
+            .ok_or(Error::SummonClosed(*metadata))?;
+        let summoner = self.wallet.pubkey();
+        let accounts = chain_drive::accounts::Cancel {
+            summoner,
+            summoner_token_account: get_associated_token_address(&summoner, &self.shdw_mint),
+            payer: summon.rent_payer(),
+            metadata: *metadata,
+            shdw_vault: self.vault_address(metadata),
+            shdw_mint: self.shdw_mint,
+            token_program: TokenProgramID,
+        };
+        let args = chain_drive::instruction::Cancel {};
+
+        let txid = self
+            .send(accounts.to_account_metas(None), args.data())
+            .await?;
+
+        Ok(ShdwDriveResponse { txid })
+    }
+
+    /// Downloads the object a summon asks for and checks it against the summon's hash.
+    /// Fails with [`Error::ObjectHashMismatch`] if the object changed since it was summoned.
+    pub async fn fetch_summoned_data(&self, summon: &DataToBeSummoned) -> ShadowDriveResult<Bytes> {
+        let data = self
+            .download(&summon.storage_account, &summon.filename)
+            .await?;
+        let hash: [u8; 32] = Sha256::digest(&data).into();
+        if hash != summon.hash {
+            return Err(Error::ObjectHashMismatch {
+                expected: summon.hash,
+                actual: hash,
+            });
+        }
+        Ok(data)
+    }
+
+    /// The clockwork thread that an upload creates to close the summon's account.
+    pub fn thread_address(&self, metadata: &Pubkey, summon: &DataToBeSummoned) -> Pubkey {
+        let thread_id = summon.unique_thread.map_or_else(
+            || summon.filename.as_bytes().to_vec(),
+            |id| id.to_le_bytes().to_vec(),
+        );
+        Thread::pubkey(*metadata, thread_id)
+    }
+
+    /// The token account that uploads pay the summon's SHDW into. Its owner is compiled into
+    /// the portal program, and set from the cluster's `payout_authority`.
+    pub fn payout_account(&self) -> Pubkey {
+        get_associated_token_address(&self.payout_authority, &self.shdw_mint)
+    }
+
+    /// Uploads a summon's data with the wallet as the uploader, who receives the summon's
+    /// `extra_lamports` and funds the clockwork thread that closes its account. `data` should
+    /// come from [`fetch_summoned_data`](Self::fetch_summoned_data).
+    ///
+    /// The data is sent in a single transaction, so this fails with [`Error::FileTooLarge`]
+    /// for objects that do not fit in one.
+    pub async fn upload(
+        &self,
+        metadata: &Pubkey,
+        summon: &DataToBeSummoned,
+        data: Vec<u8>,
+    ) -> ShadowDriveResult<ShdwDriveResponse> {
+        let accounts = chain_drive::accounts::Upload {
+            uploader: self.wallet.pubkey(),
+            metadata: *metadata,
+            metadata_token_account: self.vault_address(metadata),
+            payout_account: self.payout_account(),
+            sdrive_automation: self.thread_address(metadata, summon),
+            automation_program: ThreadProgram::id(),
+            token_program: TokenProgramID,
+            system_program: system_program::ID,
+        };
+        let instruction = Instruction {
+            program_id: self.program_id,
+            accounts: accounts.to_account_metas(None),
+            data: chain_drive::instruction::Upload { data }.data(),
+        };
+
+        // Unsigned transactions already hold placeholders for their signatures
+        let txn = Transaction::new_with_payer(&[instruction.clone()], Some(&self.wallet.pubkey()));
+        let txn_size = bincode::serialized_size(&txn)
+            .map_err(|e| Error::TransactionSerializationFailed(e.to_string()))?;
+        if txn_size > PACKET_DATA_SIZE as u64 {
+            return Err(Error::FileTooLarge(format!(
+                "{} does not fit in a transaction ({} bytes, at most {})",
+                summon.filename, txn_size, PACKET_DATA_SIZE
+            )));
+        }
+
+        let txid = self.send(instruction.accounts, instruction.data).await?;
+
+        Ok(ShdwDriveResponse { txid })
+    }
+
+    /// Fetches the portal's config account, holding its admin and fee.
+    pub async fn get_config(&self) -> ShadowDriveResult<PortalConfig> {
+        let data = self.rpc_client.get_account_data(&self.config).await?;
+        Ok(PortalConfig::try_deserialize(&mut data.as_slice())?)
+    }
+
+    /// Lists the open summons of `summoner` with their addresses. Summons are closed once their
+    /// data has been uploaded and their clockwork thread has run.
+    pub async fn list_summons(
+        &self,
+        summoner: &Pubkey,
+    ) -> ShadowDriveResult<Vec<(Pubkey, DataToBeSummoned)>> {
+        let mut summons = self.all_summons().await?;
+        summons.retain(|(_, summon)| summon.summoner == *summoner);
+        Ok(summons)
+    }
+
+    /// Lists the summons of every summoner whose data has not been uploaded yet, i.e. the work
+    /// waiting for an uploader.
+    pub async fn list_pending_summons(&self) -> ShadowDriveResult<Vec<(Pubkey, DataToBeSummoned)>> {
+        let mut summons = self.all_summons().await?;
+        summons.retain(|(_, summon)| !summon.uploaded);
+        Ok(summons)
+    }
+
+    /// Selects the portal's [`DataToBeSummoned`] accounts, for `getProgramAccounts` and
+    /// `programSubscribe`.
+    pub fn summon_accounts_config(&self) -> RpcProgramAccountsConfig {
+        // The summoner and upload state follow the variable length filename, so only the
+        // account type can be filtered for on the server
+        RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                0,
+                &DataToBeSummoned::discriminator(),
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(self.rpc_client.commitment()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    async fn all_summons(&self) -> ShadowDriveResult<Vec<(Pubkey, DataToBeSummoned)>> {
+        let accounts = self
+            .rpc_client
+            .get_program_accounts_with_config(&self.program_id, self.summon_accounts_config())
+            .await?;
+
+        accounts
+            .into_iter()
+            .map(|(key, account)| {
+                let summon = DataToBeSummoned::try_deserialize(&mut account.data.as_slice())?;
+                Ok((key, summon))
+            })
+            .collect()
+    }
+
+    /// Creates the portal's config account. The program sets its admin and initial fee, and
+    /// the wallet only pays the rent.
+    pub async fn init_config(&self) -> ShadowDriveResult<ShdwDriveResponse> {
+        let accounts = chain_drive::accounts::Init {
+            payer: self.wallet.pubkey(),
+            config: self.config,
+            system_program: system_program::ID,
+        };
+        let args = chain_drive::instruction::Init {};
+
+        let txid = self
+            .send(accounts.to_account_metas(None), args.data())
+            .await?;
+
+        Ok(ShdwDriveResponse { txid })
+    }
+
+    /// Sets the portal fee in shades per summoned byte.
+    /// Fails with [`Error::NotPortalAdmin`] unless the wallet is the cluster's `admin`.
+    pub async fn set_fee(&self, shades_per_byte: u64) -> ShadowDriveResult<ShdwDriveResponse> {
+        if self.admin != self.wallet.pubkey() {
+            return Err(Error::NotPortalAdmin(self.admin));
+        }
+
+        let accounts = chain_drive::accounts::Update {
+            payer: self.wallet.pubkey(),
+            config: self.config,
+            system_program: system_program::ID,
+        };
+        let args = chain_drive::instruction::Update {
+            fee: shades_per_byte,
+        };
+
+        let txid = self
+            .send(accounts.to_account_metas(None), args.data())
+            .await?;
+
+        Ok(ShdwDriveResponse { txid })
+    }
+
+    async fn download(
+        &self,
+        storage_account_key: &Pubkey,
+        filename: &str,
+    ) -> ShadowDriveResult<Bytes> {
+        let response = self
+            .http_client
+            .get(format!(
+                "{}/{}/{}",
+                self.object_prefix, storage_account_key, filename
+            ))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(Error::from_response(response).await);
+        }
+
+        Ok(response.bytes().await?)
+    }
+
+    async fn send(&self, accounts: Vec<AccountMeta>, data: Vec<u8>) -> ShadowDriveResult<String> {
+        let instruction = Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        };
+
+        let txn = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.wallet.pubkey()),
+            &[&self.wallet],
+            self.rpc_client.get_latest_blockhash().await?,
+        );
+
+        let txn_result = self
+            .rpc_client
+            .send_and_confirm_transaction(&txn)
+            .await
+            .map_err(|e| portal_error(&self.program_id, &txn, e))?;
+
+        Ok(txn_result.to_string())
+    }
+}
+
+/// Surfaces the portal's own error codes instead of the raw rpc error. Only errors of the
+/// portal's instructions are mapped, since other programs reuse the same custom codes.
+fn portal_error(program_id: &Pubkey, txn: &Transaction, error: ClientError) -> Error {
+    if let Some(TransactionError::InstructionError(index, InstructionError::Custom(code))) =
+        error.get_transaction_error()
+    {
+        if txn.message.program_id(index as usize) == Some(program_id) {
+            if let Some(portal_error) = PortalError::from_code(code) {
+                return Error::PortalError(portal_error);
+            }
+        }
+    }
+    Error::from(error)
+}
+
+/// The token account holding the SHDW paid for a summon to the built-in portal program, see
+/// [`PortalClient::vault_address`] for other deployments.
+pub fn vault_address(metadata: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[metadata.as_ref()], &chain_drive::ID).0
+}
+
+/// `http(s)://host:port` to `ws(s)://host:port+1`, as the solana CLI derives it.
+pub fn websocket_url(rpc_url: &str) -> String {
+    let mut url = match Url::parse(rpc_url) {
+        Ok(url) => url,
+        Err(_) => return rpc_url.to_string(),
+    };
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    let _ = url.set_scheme(scheme);
+    if let Some(port) = url.port() {
+        let _ = url.set_port(Some(port + 1));
+    }
+    url.to_string()
+}
+
+#[test]
+fn test_portal_error() {
+    let program_id = Pubkey::new_unique();
+    let other_program_id = Pubkey::new_unique();
+    let txn = Transaction::new_with_payer(
+        &[
+            Instruction::new_with_bytes(other_program_id, &[], vec![]),
+            Instruction::new_with_bytes(program_id, &[], vec![]),
+        ],
+        Some(&Pubkey::new_unique()),
+    );
+    let failure = |index, code| {
+        ClientError::from(TransactionError::InstructionError(
+            index,
+            InstructionError::Custom(code),
+        ))
+    };
+
+    let error = portal_error(&program_id, &txn, failure(1, 6004));
+    assert!(
+        matches!(error, Error::PortalError(PortalError::AlreadyUploaded)),
+        "{error:?}"
+    );
+    // The same code from another program, or a code the portal doesn't have
+    let error = portal_error(&program_id, &txn, failure(0, 6004));
+    assert!(matches!(error, Error::SolanaRpcError(_)), "{error:?}");
+    let error = portal_error(&program_id, &txn, failure(1, 6100));
+    assert!(matches!(error, Error::SolanaRpcError(_)), "{error:?}");
+}
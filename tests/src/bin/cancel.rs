@@ -0,0 +1,271 @@
+use std::{error::Error, path::PathBuf, rc::Rc, str::FromStr};
+
+use anchor_client::{
+    anchor_lang::{system_program, Id, InstructionData, ToAccountMetas},
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        instruction::{Instruction, InstructionError},
+        pubkey::Pubkey,
+        signature::{read_keypair_file, Keypair, Signature, Signer},
+        transaction::TransactionError,
+    },
+    Client, ClientError, Cluster, Program, RequestBuilder,
+};
+
+use anchor_spl::{associated_token::get_associated_token_address, token};
+use chain_drive::{
+    clockwork_sdk::{state::Thread, ThreadProgram},
+    instructions::{init::portal_config, summon::DataToBeSummoned},
+    payout_authority, shdw, PortalError,
+};
+use sha2::{Digest, Sha256};
+use shadow_portal_tests::mock_shdw_mint;
+use spl_associated_token_account::instruction::create_associated_token_account;
+
+/// Summons `filename` with a separate rent payer, returning the metadata
+/// address.
+fn summon(
+    program: &Program,
+    summoner: &Keypair,
+    payer: &Keypair,
+    summoner_ata: Pubkey,
+    storage_account: Pubkey,
+    filename: &str,
+    data: &[u8],
+) -> Pubkey {
+    let metadata = DataToBeSummoned::get_pda(
+        &summoner.pubkey(),
+        &storage_account,
+        filename,
+        None,
+    );
+    let shdw_vault =
+        Pubkey::find_program_address(&[metadata.as_ref()], &chain_drive::ID).0;
+    let hash: [u8; 32] = Sha256::digest(data).into();
+
+    program
+        .request()
+        .accounts(chain_drive::accounts::Summon {
+            summoner: summoner.pubkey(),
+            payer: payer.pubkey(),
+            summoner_token_account: summoner_ata,
+            metadata,
+            system_program: system_program::ID,
+            portal_config: portal_config(),
+            shdw_vault,
+            shdw_mint: shdw::ID,
+            token_program: token::ID,
+        })
+        .args(chain_drive::instruction::Summon {
+            storage_account,
+            filename: filename.to_string(),
+            callback: None,
+            hash,
+            data_len: data.len(),
+            extra_lamports: 0,
+            unique_thread: None,
+        })
+        .signer(summoner)
+        .signer(payer)
+        .send()
+        .expect("failed summon tx");
+    metadata
+}
+
+/// Appends a cancel of `metadata` to `request`, refunding the rent to
+/// `payer`.
+fn cancel<'a>(
+    request: RequestBuilder<'a>,
+    summoner: &'a Keypair,
+    summoner_ata: Pubkey,
+    payer: Pubkey,
+    metadata: Pubkey,
+) -> Result<Signature, ClientError> {
+    request
+        .accounts(chain_drive::accounts::Cancel {
+            summoner: summoner.pubkey(),
+            summoner_token_account: summoner_ata,
+            payer,
+            metadata,
+            shdw_vault: Pubkey::find_program_address(
+                &[metadata.as_ref()],
+                &chain_drive::ID,
+            )
+            .0,
+            shdw_mint: shdw::ID,
+            token_program: token::ID,
+        })
+        .args(chain_drive::instruction::Cancel {})
+        .signer(summoner)
+        .send()
+}
+
+fn token_balance(rpc: &RpcClient, account: &Pubkey) -> u64 {
+    rpc.get_token_account_balance(account)
+        .unwrap()
+        .amount
+        .parse()
+        .unwrap()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // Get admin and mint key.
+    let admin_key: Rc<Keypair> = Rc::new(
+        read_keypair_file(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .parent()
+                .unwrap()
+                .join("admin.json"),
+        )
+        .expect("example requires a keypair"),
+    );
+    let admin_ata = mock_shdw_mint(Rc::clone(&admin_key) as Rc<dyn Signer>)?;
+
+    // Get client, program, and rpc client
+    let client: Client = Client::new_with_options(
+        Cluster::Localnet,
+        Rc::clone(&admin_key) as Rc<dyn Signer>,
+        CommitmentConfig::confirmed(),
+    );
+    let portal_program: Program = client.program(chain_drive::ID);
+    let rpc = portal_program.rpc();
+
+    portal_program
+        .request()
+        .accounts(chain_drive::accounts::Init {
+            payer: admin_key.pubkey(),
+            config: portal_config(),
+            system_program: system_program::ID,
+        })
+        .args(chain_drive::instruction::Init {})
+        .send()
+        .unwrap();
+
+    // A separate, funded rent payer
+    let payer = Keypair::new();
+    let airdrop = rpc.request_airdrop(&payer.pubkey(), 1_000_000_000)?;
+    while !rpc.confirm_transaction(&airdrop)? {}
+
+    let storage_account =
+        Pubkey::from_str("53AqvNpBsk3wci9do6buRwaRr3spLZE1ySNfEYxMZEqG")
+            .unwrap();
+    let shdw_before = token_balance(&rpc, &admin_ata);
+    let metadata = summon(
+        &portal_program,
+        &admin_key,
+        &payer,
+        admin_ata,
+        storage_account,
+        "cancelled.txt",
+        b"never uploaded",
+    );
+    let summon_account: DataToBeSummoned = portal_program.account(metadata)?;
+    assert_eq!(summon_account.payer, payer.pubkey(), "payer");
+    println!("\nUser summoned data with a separate payer");
+
+    // The SHDW goes back to the summoner and the rent to the payer
+    let shdw_vault =
+        Pubkey::find_program_address(&[metadata.as_ref()], &chain_drive::ID).0;
+    let rent = rpc.get_balance(&metadata)? + rpc.get_balance(&shdw_vault)?;
+    let payer_before = rpc.get_balance(&payer.pubkey())?;
+    cancel(
+        portal_program.request(),
+        &admin_key,
+        admin_ata,
+        payer.pubkey(),
+        metadata,
+    )?;
+    assert_eq!(token_balance(&rpc, &admin_ata), shdw_before, "shdw refund");
+    assert_eq!(
+        rpc.get_balance(&payer.pubkey())?,
+        payer_before + rent,
+        "rent refund"
+    );
+    assert!(
+        portal_program
+            .account::<DataToBeSummoned>(metadata)
+            .is_err(),
+        "metadata should be closed"
+    );
+    println!("\nSummon cancelled, rent refunded to the payer");
+
+    // Uploaded summons can't be cancelled. The summon is closed soon after
+    // its upload, so upload and cancel in the same transaction
+    let payout_account =
+        get_associated_token_address(&payout_authority::ID, &shdw::ID);
+    if rpc.get_account(&payout_account).is_err() {
+        portal_program
+            .request()
+            .instruction(create_associated_token_account(
+                &admin_key.pubkey(),
+                &payout_authority::ID,
+                &shdw::ID,
+                &token::ID,
+            ))
+            .send()?;
+    }
+    let filename = "uploaded.txt";
+    let data = b"uploaded by the cancel test";
+    let metadata = summon(
+        &portal_program,
+        &admin_key,
+        &payer,
+        admin_ata,
+        storage_account,
+        filename,
+        data,
+    );
+    let upload = Instruction {
+        program_id: chain_drive::ID,
+        accounts: chain_drive::accounts::Upload {
+            uploader: admin_key.pubkey(),
+            metadata,
+            metadata_token_account: Pubkey::find_program_address(
+                &[metadata.as_ref()],
+                &chain_drive::ID,
+            )
+            .0,
+            payout_account,
+            sdrive_automation: Thread::pubkey(
+                metadata,
+                filename.as_bytes().to_vec(),
+            ),
+            automation_program: ThreadProgram::id(),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: chain_drive::instruction::Upload {
+            data: data.to_vec(),
+        }
+        .data(),
+    };
+
+    match cancel(
+        portal_program.request().instruction(upload),
+        &admin_key,
+        admin_ata,
+        payer.pubkey(),
+        metadata,
+    ) {
+        Err(ClientError::SolanaClientError(e)) => assert_eq!(
+            e.get_transaction_error(),
+            Some(TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(PortalError::AlreadyUploaded.into())
+            )),
+            "cancel error"
+        ),
+        result => panic!("cancel of an uploaded summon: {result:?}"),
+    }
+    assert!(
+        !portal_program
+            .account::<DataToBeSummoned>(metadata)?
+            .uploaded,
+        "the failed transaction should not upload"
+    );
+    println!("\nCancel of an uploaded summon rejected\n");
+
+    Ok(())
+}
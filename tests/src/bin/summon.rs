@@ -18,6 +18,7 @@ use chain_drive::{
     shdw,
 };
 use sha2::{Digest, Sha256};
+use shadow_drive_mock::MockShadowDrive;
 use shadow_portal_tests::mock_shdw_mint;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -59,13 +60,14 @@ fn main() -> Result<(), Box<dyn Error>> {
             .unwrap();
     println!("storage account {:?}", storage_account.to_bytes());
     let filename = "test.txt";
-    let data = reqwest::blocking::get(DataToBeSummoned::build_source(
-        &storage_account,
-        &filename,
-    ))
-    .unwrap()
-    .bytes()
-    .unwrap();
+
+    // Serve the object on the localnet object prefix, where
+    // `portal-worker --cluster localnet` downloads it from
+    let drive = MockShadowDrive::start_on("127.0.0.1:8080")?;
+    drive.put_object(storage_account, filename, b"summoned from localnet")?;
+    let data =
+        reqwest::blocking::get(drive.object_url(storage_account, filename))?
+            .bytes()?;
     let mut hasher = Sha256::new();
     hasher.update(&data);
     let hash: [u8; 32] = hasher.finalize().try_into().unwrap();
@@ -133,7 +135,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         if !metadata.data.is_empty() {
             assert_eq!(metadata.data, data, "data");
             println!(
-                "\nData uploaded from sdrive to solana by clockwork plugin"
+                "\nData uploaded from the mock drive to solana by portal-worker"
             );
             break;
         }